@@ -56,10 +56,20 @@ pub struct CliParser {
     pub threads: usize,
     pub verbosity: u32,
     pub tags: Option<Vec<String>>,
+    pub skip_tags: Option<Vec<String>>,
+    pub list_tags: bool,
+    pub list_tasks: bool,
+    pub list_hosts: bool,
+    pub start_at_task: Option<String>,
+    pub step: bool,
+    pub file_path: Option<PathBuf>,
+    pub vault_password: Option<String>,
     pub allow_localhost_delegation: bool,
     pub extra_vars: serde_yaml::Value,
     pub forward_agent: bool,
     pub login_password: Option<String>,
+    pub diff: bool,
+    pub report: Option<String>,
     pub argument_map: HashMap<String, Arguments>,
 }
 
@@ -75,7 +85,14 @@ pub enum CliMode {
     CLI_MODE_SSH,
     CLI_MODE_CHECK_SSH,
     CLI_MODE_SHOW_INVENTORY,
-    CLI_MODE_SIMULATE
+    CLI_MODE_SIMULATE,
+    CLI_MODE_ROLES,
+    CLI_MODE_ROLES_INSTALL,
+    CLI_MODE_VAULT,
+    CLI_MODE_VAULT_ENCRYPT,
+    CLI_MODE_VAULT_DECRYPT,
+    CLI_MODE_VAULT_EDIT,
+    CLI_MODE_VAULT_VIEW
 }
 
 fn is_cli_mode_valid(value: &String) -> bool {
@@ -93,6 +110,8 @@ fn cli_mode_from_string(s: &String) -> Result<CliMode, String> {
         "check-ssh"       => Ok(CliMode::CLI_MODE_CHECK_SSH),
         "__simulate"      => Ok(CliMode::CLI_MODE_SIMULATE),
         "show-inventory"  => Ok(CliMode::CLI_MODE_SHOW_INVENTORY),
+        "roles"           => Ok(CliMode::CLI_MODE_ROLES),
+        "vault"           => Ok(CliMode::CLI_MODE_VAULT),
         _ => Err(format!("invalid mode: {}", s))
     }
 }
@@ -119,6 +138,16 @@ pub enum Arguments {
     ARGUMENT_USER_SHORT,
     ARGUMENT_SUDO,
     ARGUMENT_TAGS,
+    ARGUMENT_SKIP_TAGS,
+    ARGUMENT_LIST_TAGS,
+    ARGUMENT_LIST_TASKS,
+    ARGUMENT_LIST_HOSTS,
+    ARGUMENT_START_AT_TASK,
+    ARGUMENT_STEP,
+    ARGUMENT_FILE,
+    ARGUMENT_FILE_SHORT,
+    ARGUMENT_VAULT_PASSWORD_FILE,
+    ARGUMENT_ASK_VAULT_PASSWORD,
     ARGUMENT_ALLOW_LOCALHOST,
     ARGUMENT_FORWARD_AGENT,
     ARGUMENT_THREADS,
@@ -131,7 +160,9 @@ pub enum Arguments {
     ARGUMENT_EXTRA_VARS_SHORT,
     ARGUMENT_ASK_LOGIN_PASSWORD,
     ARGUMENT_MODULES,
-    ARGUMENT_MODULES_SHORT
+    ARGUMENT_MODULES_SHORT,
+    ARGUMENT_DIFF,
+    ARGUMENT_REPORT
 }
 
 impl Arguments {
@@ -156,6 +187,16 @@ impl Arguments {
             Arguments::ARGUMENT_USER_SHORT => "-u",
             Arguments::ARGUMENT_SUDO => "--sudo",
             Arguments::ARGUMENT_TAGS => "--tags",
+            Arguments::ARGUMENT_SKIP_TAGS => "--skip-tags",
+            Arguments::ARGUMENT_LIST_TAGS => "--list-tags",
+            Arguments::ARGUMENT_LIST_TASKS => "--list-tasks",
+            Arguments::ARGUMENT_LIST_HOSTS => "--list-hosts",
+            Arguments::ARGUMENT_START_AT_TASK => "--start-at-task",
+            Arguments::ARGUMENT_STEP => "--step",
+            Arguments::ARGUMENT_FILE => "--file",
+            Arguments::ARGUMENT_FILE_SHORT => "-f",
+            Arguments::ARGUMENT_VAULT_PASSWORD_FILE => "--vault-password-file",
+            Arguments::ARGUMENT_ASK_VAULT_PASSWORD => "--ask-vault-password",
             Arguments::ARGUMENT_ALLOW_LOCALHOST => "--allow-localhost-delegation",
             Arguments::ARGUMENT_FORWARD_AGENT => "--forward-agent",
             Arguments::ARGUMENT_THREADS => "--threads",
@@ -167,6 +208,8 @@ impl Arguments {
             Arguments::ARGUMENT_EXTRA_VARS => "--extra-vars",
             Arguments::ARGUMENT_EXTRA_VARS_SHORT => "-e",
             Arguments::ARGUMENT_ASK_LOGIN_PASSWORD => "--ask-login-password",
+            Arguments::ARGUMENT_DIFF => "--diff",
+            Arguments::ARGUMENT_REPORT => "--report",
         }
     }
 }
@@ -193,6 +236,16 @@ fn build_argument_map() -> HashMap<String, Arguments> {
         (Arguments::ARGUMENT_USER_SHORT, "-u"),
         (Arguments::ARGUMENT_SUDO, "--sudo"),
         (Arguments::ARGUMENT_TAGS, "--tags"),
+        (Arguments::ARGUMENT_SKIP_TAGS, "--skip-tags"),
+        (Arguments::ARGUMENT_LIST_TAGS, "--list-tags"),
+        (Arguments::ARGUMENT_LIST_TASKS, "--list-tasks"),
+        (Arguments::ARGUMENT_LIST_HOSTS, "--list-hosts"),
+        (Arguments::ARGUMENT_START_AT_TASK, "--start-at-task"),
+        (Arguments::ARGUMENT_STEP, "--step"),
+        (Arguments::ARGUMENT_FILE, "--file"),
+        (Arguments::ARGUMENT_FILE_SHORT, "-f"),
+        (Arguments::ARGUMENT_VAULT_PASSWORD_FILE, "--vault-password-file"),
+        (Arguments::ARGUMENT_ASK_VAULT_PASSWORD, "--ask-vault-password"),
         (Arguments::ARGUMENT_ALLOW_LOCALHOST, "--allow-localhost-delegation"),
         (Arguments::ARGUMENT_FORWARD_AGENT, "--forward-agent"),
         (Arguments::ARGUMENT_THREADS, "--threads"),
@@ -204,6 +257,8 @@ fn build_argument_map() -> HashMap<String, Arguments> {
         (Arguments::ARGUMENT_EXTRA_VARS, "--extra-vars"),
         (Arguments::ARGUMENT_EXTRA_VARS_SHORT, "-e"),
         (Arguments::ARGUMENT_ASK_LOGIN_PASSWORD, "--ask-login-password"),
+        (Arguments::ARGUMENT_DIFF, "--diff"),
+        (Arguments::ARGUMENT_REPORT, "--report"),
     ];
     let mut map : HashMap<String, Arguments> = HashMap::new();
     for (e,i) in inputs.iter() {
@@ -240,6 +295,10 @@ fn show_help() {
                       | utility: |\n\
                       | | show-inventory | displays inventory, specify --show-groups group1:group2 or --show-hosts host1:host2\n\
                       | |\n\
+                      | | roles install | installs the roles listed in a requirements file (-f) into the first role path\n\
+                      | |\n\
+                      | | vault encrypt/decrypt/edit/view | manages an encrypted variables file (-f), using $EDITOR for edit\n\
+                      | |\n\
                       | --- | --- | ---\n\
                       | local machine management: |\n\
                       | | check-local| looks for configuration differences on the local machine\n\
@@ -266,6 +325,8 @@ fn show_help() {
                        | |\n\
                        | | -r, --roles path1:path2| adds additional role search paths. Also uses $JET_ROLES_PATH\n\
                        | |\n\
+                       | | -f, --file path| the requirements file for roles install, or the file for vault\n\
+                       | |\n\
                        | --- | ---\n\
                        | SSH options:\n\
                        | | --ask-login-password | prompt for the login password on standard input\n\
@@ -276,7 +337,7 @@ fn show_help() {
                        | |\n\
                        | | --limit-groups group1:group2 | further limits scope for playbook runs\n\
                        | |\n\
-                       | | --limit-hosts host1 | further limits scope for playbook runs\n\
+                       | | --limit-hosts host1 | further limits scope for playbook runs, @filename reads hosts from a file such as a .retry file\n\
                        | |\n\
                        | | --port N | use this default port instead of $JET_SSH_PORT or 22\n\
                        | |\n\
@@ -288,11 +349,31 @@ fn show_help() {
                        | Misc options:\n\
                        | | --allow-localhost-delegation | signs off on variable sourcing risks and enables localhost actions with delegate_to\n\
                        | |\n\
+                       | | --diff | show file content differences for the template and copy modules\n\
+                       | |\n\
                        | | -e, --extra-vars @filename | injects extra variables into the playbook runtime context from a YAML file, or quoted JSON\n\
                        | |\n\
+                       | | --list-hosts | show the hosts selected by each play and exit without running anything\n\
+                       | |\n\
+                       | | --list-tags | show the tags used by each play and exit without running anything\n\
+                       | |\n\
+                       | | --list-tasks | show the tasks each play would run and exit without running anything\n\
+                       | |\n\
+                       | | --report path | writes per-host task results to a JSON file, or JUnit XML if the path ends in .xml\n\
+                       | |\n\
+                       | | --skip-tags tag1:tag2 | skip tasks or roles with any of these tags\n\
+                       | |\n\
+                       | | --start-at-task name | skip the tasks before the first task with this name\n\
+                       | |\n\
+                       | | --step | ask before running each task\n\
+                       | |\n\
                        | | --sudo username | sudo to this user by default for all tasks\n\
                        | |\n\
-                       | | --tags tag1:tag2 | only run tasks or roles with one of these tags\n\
+                       | | --ask-vault-password | prompt for the password of encrypted variable files on standard input\n\
+                       | |\n\
+                       | | --vault-password-file path | read the password of encrypted variable files from a file. Alternatively set $JET_VAULT_PASSWORD\n\
+                       | |\n\
+                       | | --tags tag1:tag2 | only run tasks or roles with one of these tags, 'always' tasks still run and 'never' tasks need an explicit tag\n\
                        | |\n\
                        | | -v -vv -vvv| ever increasing verbosity\n\
                        | |\n\
@@ -358,10 +439,20 @@ impl CliParser  {
             limit_groups: Vec::new(),
             limit_hosts: Vec::new(),
             tags: None,
+            skip_tags: None,
+            list_tags: false,
+            list_tasks: false,
+            list_hosts: false,
+            start_at_task: None,
+            step: false,
+            file_path: None,
+            vault_password: None,
             allow_localhost_delegation: false,
             extra_vars: serde_yaml::Value::Mapping(serde_yaml::Mapping::new()),
             forward_agent: false,
             login_password: None,
+            diff: false,
+            report: None,
             argument_map: build_argument_map(),
         };
         return p;
@@ -419,6 +510,13 @@ impl CliParser  {
                 // we are reading a flag or a value, which alternate
                 _ => {
 
+                    // 'jetp roles' and 'jetp vault' take an action before any flags
+                    let takes_action = self.mode == CliMode::CLI_MODE_ROLES || self.mode == CliMode::CLI_MODE_VAULT;
+                    if arg_count == 3 && takes_action && ! argument_str.starts_with("-") {
+                        self.store_action(argument)?;
+                        continue 'each_argument;
+                    }
+
                     if next_is_value == false {
 
                         // if we expect a flag...
@@ -448,6 +546,12 @@ impl CliParser  {
                             Arguments::ARGUMENT_VERBOSER           => self.increase_verbosity(2),
                             Arguments::ARGUMENT_VERBOSEST          => self.increase_verbosity(3),
                             Arguments::ARGUMENT_ASK_LOGIN_PASSWORD => self.store_login_password(),
+                            Arguments::ARGUMENT_DIFF               => self.store_diff(),
+                            Arguments::ARGUMENT_LIST_TAGS          => self.store_list_tags(),
+                            Arguments::ARGUMENT_LIST_TASKS         => self.store_list_tasks(),
+                            Arguments::ARGUMENT_LIST_HOSTS         => self.store_list_hosts(),
+                            Arguments::ARGUMENT_STEP               => self.store_step(),
+                            Arguments::ARGUMENT_ASK_VAULT_PASSWORD => self.store_vault_password(),
                             _ => Ok({ standalone_arg_found = false; next_is_value = true; })
                         };
 
@@ -468,6 +572,8 @@ impl CliParser  {
                                     Arguments::ARGUMENT_INVENTORY_SHORT   => self.append_inventory(&args[arg_count]),
                                     Arguments::ARGUMENT_SUDO              => self.store_sudo(&args[arg_count]),
                                     Arguments::ARGUMENT_TAGS              => self.store_tags(&args[arg_count]),
+                                    Arguments::ARGUMENT_SKIP_TAGS         => self.store_skip_tags(&args[arg_count]),
+                                    Arguments::ARGUMENT_START_AT_TASK     => self.store_start_at_task(&args[arg_count]),
                                     Arguments::ARGUMENT_USER              => self.store_default_user(&args[arg_count]),
                                     Arguments::ARGUMENT_USER_SHORT        => self.store_default_user(&args[arg_count]),
                                     Arguments::ARGUMENT_SHOW_GROUPS       => self.store_show_groups(&args[arg_count]),
@@ -480,6 +586,10 @@ impl CliParser  {
                                     Arguments::ARGUMENT_PORT              => self.store_port(&args[arg_count]),
                                     Arguments::ARGUMENT_EXTRA_VARS        => self.store_extra_vars(&args[arg_count]),
                                     Arguments::ARGUMENT_EXTRA_VARS_SHORT  => self.store_extra_vars(&args[arg_count]),
+                                    Arguments::ARGUMENT_REPORT            => self.store_report(&args[arg_count]),
+                                    Arguments::ARGUMENT_FILE              => self.store_file_path(&args[arg_count]),
+                                    Arguments::ARGUMENT_FILE_SHORT        => self.store_file_path(&args[arg_count]),
+                                    Arguments::ARGUMENT_VAULT_PASSWORD_FILE => self.store_vault_password_file(&args[arg_count]),
                                     _  => Err(format!("invalid flag: {}", argument_str)),
                                 };
                             }
@@ -506,9 +616,31 @@ impl CliParser  {
             CliMode::CLI_MODE_SYNTAX      => { self.threads = 1 },
             CliMode::CLI_MODE_SHOW_INVENTORY        => { self.threads = 1 },
             CliMode::CLI_MODE_UNSET       => { self.needs_help = true; },
+            CliMode::CLI_MODE_ROLES       => { return Err(String::from("jetp roles requires an action, see --help")); },
+            CliMode::CLI_MODE_VAULT       => { return Err(String::from("jetp vault requires an action, see --help")); },
             _ => {}
         }
 
+        if self.mode == CliMode::CLI_MODE_ROLES_INSTALL {
+            if self.file_path.is_none() {
+                return Err(String::from("jetp roles install requires -f/--file"));
+            }
+            if ! self.file_path.as_ref().unwrap().is_file() {
+                return Err(format!("{} {}: no such file", Arguments::ARGUMENT_FILE.as_str(), self.file_path.as_ref().unwrap().display()));
+            }
+            self.add_role_paths_from_environment()?;
+        }
+
+        if self.is_vault_mode() && self.file_path.is_none() {
+            return Err(String::from("jetp vault requires -f/--file"));
+        }
+
+        if self.vault_password.is_none() {
+            if let Ok(x) = env::var("JET_VAULT_PASSWORD") {
+                self.vault_password = Some(x);
+            }
+        }
+
         if self.playbook_set {
             self.add_role_paths_from_environment()?;
             self.add_implicit_role_paths()?;
@@ -527,6 +659,26 @@ impl CliParser  {
         return Err(format!("jetp mode ({}) is not valid, see --help", value))
      }
 
+    fn store_action(&mut self, value: &String) -> Result<(), String> {
+        self.mode = match (&self.mode, value.as_str()) {
+            (CliMode::CLI_MODE_ROLES, "install") => CliMode::CLI_MODE_ROLES_INSTALL,
+            (CliMode::CLI_MODE_VAULT, "encrypt") => CliMode::CLI_MODE_VAULT_ENCRYPT,
+            (CliMode::CLI_MODE_VAULT, "decrypt") => CliMode::CLI_MODE_VAULT_DECRYPT,
+            (CliMode::CLI_MODE_VAULT, "edit")    => CliMode::CLI_MODE_VAULT_EDIT,
+            (CliMode::CLI_MODE_VAULT, "view")    => CliMode::CLI_MODE_VAULT_VIEW,
+            (CliMode::CLI_MODE_ROLES, _) => { return Err(format!("jetp roles action ({}) is not valid, see --help", value)); },
+            _ => { return Err(format!("jetp vault action ({}) is not valid, see --help", value)); }
+        };
+        return Ok(());
+    }
+
+    pub fn is_vault_mode(&self) -> bool {
+        return match self.mode {
+            CliMode::CLI_MODE_VAULT_ENCRYPT | CliMode::CLI_MODE_VAULT_DECRYPT | CliMode::CLI_MODE_VAULT_EDIT | CliMode::CLI_MODE_VAULT_VIEW => true,
+            _ => false
+        };
+    }
+
     fn append_playbook(&mut self, value: &String) -> Result<(), String> {
         self.playbook_set = true;
         match parse_paths(&String::from("-p/--playbook"), value) {
@@ -625,6 +777,13 @@ impl CliParser  {
     }
 
     fn store_limit_hosts(&mut self, value: &String) -> Result<(), String> {
+        if let Some(path) = value.strip_prefix("@") {
+            match read_host_file(path) {
+                Ok(values)  =>  { self.limit_hosts = values; },
+                Err(err_msg) =>  return Err(format!("--{} {}", Arguments::ARGUMENT_LIMIT_HOSTS.as_str(), err_msg)),
+            }
+            return Ok(());
+        }
         match split_string(value) {
             Ok(values)  =>  { self.limit_hosts = values; },
             Err(err_msg) =>  return Err(format!("--{} {}", Arguments::ARGUMENT_LIMIT_HOSTS.as_str(), err_msg)),
@@ -640,6 +799,43 @@ impl CliParser  {
         return Ok(());
     }
 
+    fn store_skip_tags(&mut self, value: &String) -> Result<(), String> {
+        match split_string(value) {
+            Ok(values)  =>  { self.skip_tags = Some(values); },
+            Err(err_msg) =>  return Err(format!("--{} {}", Arguments::ARGUMENT_SKIP_TAGS.as_str(), err_msg)),
+        }
+        return Ok(());
+    }
+
+    fn store_file_path(&mut self, value: &String) -> Result<(), String> {
+        // vault edit may create the file, so only existing files are made absolute here
+        let path = PathBuf::from(value);
+        self.file_path = match path.is_file() {
+            true  => Some(fs::canonicalize(path.as_path()).unwrap()),
+            false => Some(path)
+        };
+        return Ok(());
+    }
+
+    fn store_vault_password_file(&mut self, value: &String) -> Result<(), String> {
+        match fs::read_to_string(value) {
+            Ok(x) => {
+                let password = x.lines().next().unwrap_or("").trim().to_string();
+                if password.is_empty() {
+                    return Err(format!("{} {}: the file is empty", Arguments::ARGUMENT_VAULT_PASSWORD_FILE.as_str(), value));
+                }
+                self.vault_password = Some(password);
+            },
+            Err(e) => { return Err(format!("{} {}: {}", Arguments::ARGUMENT_VAULT_PASSWORD_FILE.as_str(), value, e)); }
+        }
+        return Ok(());
+    }
+
+    fn store_start_at_task(&mut self, value: &String) -> Result<(), String> {
+        self.start_at_task = Some(value.clone());
+        return Ok(());
+    }
+
     fn store_sudo(&mut self, value: &String) -> Result<(), String> {
         self.sudo = Some(value.clone());
         return Ok(());
@@ -660,6 +856,19 @@ impl CliParser  {
         }
     }
 
+    fn store_report(&mut self, value: &String) -> Result<(), String> {
+        if self.report.is_some() {
+            return Err(format!("{} has been specified already", Arguments::ARGUMENT_REPORT.as_str()));
+        }
+        // the run changes directory into playbooks and roles, and may stop inside one of them
+        let path = match env::current_dir() {
+            Ok(cwd) => cwd.join(value),
+            Err(_) => PathBuf::from(value)
+        };
+        self.report = Some(path.display().to_string());
+        return Ok(());
+    }
+
     fn store_threads(&mut self, value: &String) -> Result<(), String> {
         match value.parse::<usize>() {
             Ok(n) =>  { self.threads = n; return Ok(()); }
@@ -796,6 +1005,40 @@ impl CliParser  {
         return Ok(());
      }
 
+     fn store_diff(&mut self) -> Result<(), String>{
+        self.diff = true;
+        return Ok(());
+     }
+
+     fn store_list_tags(&mut self) -> Result<(), String>{
+        self.list_tags = true;
+        return Ok(());
+     }
+
+     fn store_list_tasks(&mut self) -> Result<(), String>{
+        self.list_tasks = true;
+        return Ok(());
+     }
+
+     fn store_list_hosts(&mut self) -> Result<(), String>{
+        self.list_hosts = true;
+        return Ok(());
+     }
+
+     fn store_step(&mut self) -> Result<(), String>{
+        self.step = true;
+        return Ok(());
+     }
+
+     fn store_vault_password(&mut self) -> Result<(), String>{
+        // read without echo so the password does not stay on screen
+        match rpassword::prompt_password("enter vault password: ") {
+            Ok(value) => { self.vault_password = Some(String::from(value.trim())); }
+            Err(e) =>  return Err(format!("failure reading input: {}", e))
+        }
+        return Ok(());
+     }
+
      fn store_login_password(&mut self) -> Result<(), String>{
         let mut value = String::new();
         println!("enter login password:");
@@ -812,6 +1055,31 @@ fn split_string(value: &String) -> Result<Vec<String>, String> {
     return Ok(value.split(":").map(|x| String::from(x)).collect());
 }
 
+fn read_host_file(path: &str) -> Result<Vec<String>, String> {
+
+    // host files such as the retry file written after a failed run have one host per line,
+    // anything after a '#' is a comment
+
+    let contents = match fs::read_to_string(path) {
+        Ok(x) => x,
+        Err(y) => { return Err(format!("could not read host file {}: {}", path, y)); }
+    };
+    let mut results : Vec<String> = Vec::new();
+    for line in contents.lines() {
+        let host = match line.find('#') {
+            Some(index) => line[..index].trim(),
+            None => line.trim()
+        };
+        if ! host.is_empty() {
+            results.push(String::from(host));
+        }
+    }
+    if results.is_empty() {
+        return Err(format!("host file {} does not list any hosts", path));
+    }
+    return Ok(results);
+}
+
 // accept paths eliminated by ":" and return a list of paths, provided they exist
 fn parse_paths(from: &String, value: &String) -> Result<Vec<PathBuf>, String> {
     let string_paths = value.split(":");
@@ -827,3 +1095,46 @@ fn parse_paths(from: &String, value: &String) -> Result<Vec<PathBuf>, String> {
     }
     return Ok(results);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        return env::temp_dir().join(format!("jet-parser-{}-{}", name, guid_create::GUID::rand()));
+    }
+
+    #[test]
+    fn test_read_host_file() {
+        // (file contents, expected hosts)
+        let cases = vec![
+            ("web1\nweb2\n", vec!["web1", "web2"]),
+            ("web1", vec!["web1"]),
+            ("\n  web1  \n\n\t\nweb2\n\n", vec!["web1", "web2"]),
+            ("# failed hosts\nweb1 # shell task\n  # indented comment\nweb2#no space\n", vec!["web1", "web2"]),
+            ("web1\r\nweb2\r\n", vec!["web1", "web2"]),
+        ];
+        for (contents, expected) in cases.iter() {
+            let path = temp_path("hosts");
+            fs::write(&path, contents).unwrap();
+            let result = read_host_file(&path.display().to_string());
+            fs::remove_file(&path).unwrap();
+            assert_eq!(result.unwrap(), *expected, "{:?}", contents);
+        }
+    }
+
+    #[test]
+    fn test_read_host_file_errors() {
+        let missing = temp_path("missing");
+        let err = read_host_file(&missing.display().to_string()).unwrap_err();
+        assert!(err.starts_with(&format!("could not read host file {}", missing.display())), "{}", err);
+
+        for contents in ["", "\n\n", "# only a comment\n  # and another\n"].iter() {
+            let path = temp_path("empty");
+            fs::write(&path, contents).unwrap();
+            let err = read_host_file(&path.display().to_string()).unwrap_err();
+            fs::remove_file(&path).unwrap();
+            assert!(err.ends_with("does not list any hosts"), "{:?}: {}", contents, err);
+        }
+    }
+}
@@ -20,10 +20,13 @@ use crate::connection::ssh::SshFactory;
 use crate::connection::local::LocalFactory;
 use crate::connection::no::NoFactory;
 use crate::playbooks::traversal::{playbook_traversal,RunState};
+use crate::playbooks::listing::{list_hosts,list_tasks,list_tags};
 use crate::playbooks::context::PlaybookContext;
 use crate::playbooks::visitor::{PlaybookVisitor,CheckMode};
+use crate::playbooks::t_helpers::{allow_pipe_lookups,create_password_files};
 use crate::inventory::inventory::Inventory;
 use std::sync::{Arc,RwLock};
+use std::fs;
 
 // code behind *most* playbook related CLI commands, launched from main.rs
 
@@ -54,6 +57,10 @@ pub fn playbook_simulate(inventory: &Arc<RwLock<Inventory>>, parser: &CliParser)
 }
 
 fn playbook(inventory: &Arc<RwLock<Inventory>>, parser: &CliParser, check_mode: CheckMode, connection_mode: ConnectionMode) -> i32 {
+    // the pipe template helper runs commands on this machine, same as delegating to localhost
+    allow_pipe_lookups(parser.allow_localhost_delegation);
+    // check mode must not leave new password files behind
+    create_password_files(check_mode == CheckMode::No);
     let run_state = Arc::new(RunState {
         // every object gets an inventory, though with local modes it's empty.
         inventory: Arc::clone(inventory),
@@ -67,18 +74,75 @@ fn playbook(inventory: &Arc<RwLock<Inventory>>, parser: &CliParser, check_mode:
         // to run-state.  Context should mostly *not* get parameters from the parser unless they
         // are going to appear in variables.
         context: Arc::new(RwLock::new(PlaybookContext::new(parser))),
-        visitor: Arc::new(RwLock::new(PlaybookVisitor::new(check_mode))),
+        visitor: Arc::new(RwLock::new(PlaybookVisitor::new(check_mode, &parser.report))),
         connection_factory: match connection_mode {
             ConnectionMode::Ssh => Arc::new(RwLock::new(SshFactory::new(inventory, parser.forward_agent, parser.login_password.clone()))),
             ConnectionMode::Local => Arc::new(RwLock::new(LocalFactory::new(inventory))),
             ConnectionMode::Simulate => Arc::new(RwLock::new(NoFactory::new()))
         },
         tags: parser.tags.clone(),
-        allow_localhost_delegation: parser.allow_localhost_delegation
+        skip_tags: parser.skip_tags.clone(),
+        start_at_task: Arc::new(RwLock::new(parser.start_at_task.clone())),
+        step: Arc::new(RwLock::new(parser.step)),
+        allow_localhost_delegation: parser.allow_localhost_delegation,
+        vault_password: parser.vault_password.clone(),
+        diff: parser.diff
     });
-    return match playbook_traversal(&run_state) {
+    if parser.list_hosts || parser.list_tasks || parser.list_tags {
+        return match list_playbooks(&run_state, parser) {
+            Ok(_)  => 0,
+            Err(s) => { println!("{}", s); 1 }
+        };
+    }
+    let result = playbook_traversal(&run_state);
+    if result.is_err() {
+        println!("{}", result.as_ref().unwrap_err());
+    }
+    // the summary and --report are written however the run ended, failed runs need them most
+    run_state.visitor.read().unwrap().on_exit(&run_state.context);
+    write_retry_file(&run_state);
+    return match result {
         Ok(_)  => run_state.visitor.read().unwrap().get_exit_status(&run_state.context),
-        Err(s) => { println!("{}", s); 1 }
+        Err(_) => 1
     };
 }
 
+fn write_retry_file(run_state: &Arc<RunState>) {
+
+    // when hosts fail, their names are written next to the first playbook with a .retry extension
+    // so the run can be repeated on just those hosts with --limit-hosts @file.  a later run without
+    // failures removes it.
+
+    let retry_path = match run_state.playbook_paths.read().unwrap().first() {
+        Some(x) => x.with_extension("retry"),
+        None => { return; }
+    };
+    let failed = run_state.context.read().unwrap().get_failed_hosts();
+    if failed.is_empty() {
+        if retry_path.is_file() {
+            let _ = fs::remove_file(&retry_path);
+        }
+        return;
+    }
+    let mut contents = format!("# failed hosts, retry with: --limit-hosts @{}\n", retry_path.display());
+    for (host, task) in failed.iter() {
+        match task {
+            Some(task) => contents.push_str(&format!("{} # {}\n", host, task)),
+            None => contents.push_str(&format!("{}\n", host))
+        }
+    }
+    match fs::write(&retry_path, contents) {
+        Ok(_) => { println!("failed hosts written to {}", retry_path.display()); },
+        Err(y) => { println!("could not write retry file {}: {}", retry_path.display(), y); }
+    }
+}
+
+fn list_playbooks(run_state: &Arc<RunState>, parser: &CliParser) -> Result<(), String> {
+
+    // the --list-* options only look at the playbooks, nothing is connected to or run
+
+    if parser.list_hosts { list_hosts(run_state)?; }
+    if parser.list_tasks { list_tasks(run_state)?; }
+    if parser.list_tags  { list_tags(run_state)?; }
+    return Ok(());
+}
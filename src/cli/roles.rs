@@ -0,0 +1,498 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::cli::parser::CliParser;
+use crate::util::io::{jet_file_open,jet_read_dir,path_as_string};
+use crate::util::yaml::show_yaml_error_in_context;
+use crate::util::terminal::two_column_table;
+use serde::{Deserialize,Serialize};
+use sha2::{Sha512, Digest};
+use std::path::{Path,PathBuf};
+use std::process::Command;
+use std::fs;
+
+// cli support for the roles install subcommand
+//
+// a requirements file is a list of roles to fetch, either from git or from a local archive:
+//
+// - name: redis
+//   git: https://example.com/roles/redis.git
+//   version: v1.2.0
+// - name: common
+//   archive: archives/common.tar.gz
+//
+// roles are installed into the first role path, and what was installed is recorded
+// in roles.lock in that directory. a role that was changed after it was installed is
+// never overwritten, nor is a role directory that was not installed by this command.
+// archives are checksummed too, so a new archive at the same path is installed again.
+
+const LOCK_FILE: &str = "roles.lock";
+
+#[derive(Debug,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoleRequirement {
+    pub name: String,
+    pub git: Option<String>,
+    pub version: Option<String>,
+    pub archive: Option<String>
+}
+
+#[derive(Debug,Deserialize,Serialize,Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LockedRole {
+    pub name: String,
+    pub source: String,
+    pub version: Option<String>,
+    pub commit: Option<String>,
+    pub checksum: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_checksum: Option<String>
+}
+
+// ==============================================================================================================
+// PUBLIC API
+// ==============================================================================================================
+
+// jetp roles install -f requirements.yml [-r path]
+
+pub fn roles_install(parser: &CliParser) -> i32 {
+    return match install_requirements(parser) {
+        Ok(failures) => match failures {
+            0 => 0,
+            _ => 1
+        },
+        Err(s) => {
+            println!("{}", s);
+            1
+        }
+    };
+}
+
+// ==============================================================================================================
+// PRIVATE
+// ==============================================================================================================
+
+fn install_requirements(parser: &CliParser) -> Result<usize, String> {
+
+    let requirements_path = parser.file_path.as_ref().unwrap();
+    let requirements = load_requirements(requirements_path)?;
+    let requirements_dir = requirements_path.parent().unwrap().to_path_buf();
+
+    let role_path = match parser.role_paths.read().unwrap().first() {
+        Some(x) => x.clone(),
+        None => requirements_dir.join("roles")
+    };
+    if ! role_path.exists() {
+        if let Err(e) = fs::create_dir_all(&role_path) {
+            return Err(format!("unable to create role path {}: {}", path_as_string(&role_path), e));
+        }
+    }
+
+    let mut locked = load_lock_file(&role_path)?;
+    let mut elements : Vec<(String,String)> = Vec::new();
+    let mut failures : usize = 0;
+
+    for requirement in requirements.iter() {
+        let status = match install_role(requirement, &requirements_dir, &role_path, &mut locked) {
+            Ok(x) => x,
+            Err(e) => {
+                failures = failures + 1;
+                format!("failed: {}", e)
+            }
+        };
+        // write as we go so a later failure does not lose track of what was installed
+        write_lock_file(&role_path, &locked)?;
+        elements.push((requirement.name.clone(), status));
+    }
+
+    two_column_table(&format!("Role (in {})", path_as_string(&role_path)), &String::from("Status"), &elements);
+    println!("");
+    return Ok(failures);
+}
+
+fn load_requirements(path: &PathBuf) -> Result<Vec<RoleRequirement>, String> {
+    let fh = jet_file_open(path)?;
+    let parsed: Result<Vec<RoleRequirement>, serde_yaml::Error> = serde_yaml::from_reader(fh);
+    if parsed.is_err() {
+        show_yaml_error_in_context(&parsed.unwrap_err(), path);
+        return Err(format!("edit the file and try again?"));
+    }
+    let requirements = parsed.unwrap();
+    let mut names : Vec<String> = Vec::new();
+    for requirement in requirements.iter() {
+        if requirement.name.is_empty() || requirement.name.contains('/') || requirement.name.starts_with('.') {
+            return Err(format!("{}: role name ({}) is not valid", path_as_string(path), requirement.name));
+        }
+        if names.contains(&requirement.name) {
+            return Err(format!("{}: role ({}) is listed more than once", path_as_string(path), requirement.name));
+        }
+        names.push(requirement.name.clone());
+        // like the url, a version starting with '-' would be read by git as an option
+        if requirement.version.is_some() && requirement.version.as_ref().unwrap().starts_with('-') {
+            return Err(format!("{}: role ({}): version ({}) is not valid", path_as_string(path), requirement.name, requirement.version.as_ref().unwrap()));
+        }
+        match (&requirement.git, &requirement.archive) {
+            (Some(_), None) => {},
+            (None, Some(_)) => {
+                if requirement.version.is_some() {
+                    return Err(format!("{}: role ({}): version can only be used with git", path_as_string(path), requirement.name));
+                }
+            },
+            _ => {
+                return Err(format!("{}: role ({}): exactly one of git or archive is required", path_as_string(path), requirement.name));
+            }
+        }
+    }
+    return Ok(requirements);
+}
+
+fn install_role(requirement: &RoleRequirement, requirements_dir: &PathBuf, role_path: &PathBuf, locked: &mut Vec<LockedRole>) -> Result<String, String> {
+
+    let dest = role_path.join(&requirement.name);
+    let source = match requirement.git.is_some() {
+        true  => requirement.git.as_ref().unwrap().clone(),
+        false => path_as_string(&requirements_dir.join(requirement.archive.as_ref().unwrap()))
+    };
+    let archive_checksum = match requirement.archive.is_some() {
+        true  => Some(file_checksum(&PathBuf::from(&source))?),
+        false => None
+    };
+    let previous = locked.iter().find(|x| x.name.eq(&requirement.name)).cloned();
+
+    if dest.exists() {
+        if previous.is_none() {
+            return Err(format!("{} exists and was not installed by jetp roles install, remove it first", path_as_string(&dest)));
+        }
+        let previous = previous.as_ref().unwrap();
+        if ! directory_checksum(&dest)?.eq(&previous.checksum) {
+            return Err(format!("{} was modified since it was installed, remove it first", path_as_string(&dest)));
+        }
+        if previous.source.eq(&source) && previous.version.eq(&requirement.version) && previous.archive_checksum.eq(&archive_checksum) {
+            return Ok(String::from("already installed"));
+        }
+    }
+
+    // fetch into a scratch directory first so a failed fetch leaves the installed role alone
+    let tmp = role_path.join(format!(".{}.tmp", requirement.name));
+    if tmp.exists() {
+        remove_directory(&tmp)?;
+    }
+    let result = match requirement.git.is_some() {
+        true  => fetch_git(&source, &requirement.version, &tmp),
+        false => fetch_archive(&source, &tmp)
+    };
+    let (fetched, commit) = match result {
+        Ok(x) => x,
+        Err(e) => {
+            if tmp.exists() { let _ = remove_directory(&tmp); }
+            return Err(e);
+        }
+    };
+    if ! fetched.join("role.yml").is_file() {
+        let _ = remove_directory(&tmp);
+        return Err(format!("{} does not contain a role.yml", source));
+    }
+
+    if dest.exists() {
+        remove_directory(&dest)?;
+    }
+    if let Err(e) = fs::rename(&fetched, &dest) {
+        return Err(format!("unable to move role into {}: {}", path_as_string(&dest), e));
+    }
+    if tmp.exists() {
+        remove_directory(&tmp)?;
+    }
+
+    let entry = LockedRole {
+        name: requirement.name.clone(),
+        source: source,
+        version: requirement.version.clone(),
+        commit: commit,
+        checksum: directory_checksum(&dest)?,
+        archive_checksum: archive_checksum
+    };
+    locked.retain(|x| ! x.name.eq(&requirement.name));
+    locked.push(entry);
+    locked.sort_by(|a, b| a.name.cmp(&b.name));
+
+    return match previous.is_some() {
+        true  => Ok(String::from("updated")),
+        false => Ok(String::from("installed"))
+    };
+}
+
+fn fetch_git(url: &String, version: &Option<String>, tmp: &PathBuf) -> Result<(PathBuf, Option<String>), String> {
+    let tmp_str = path_as_string(tmp);
+    // '--' keeps a url starting with '-' from being read as an option
+    run_command("git", &vec![String::from("clone"), String::from("--quiet"), String::from("--"), url.clone(), tmp_str.clone()])?;
+    if version.is_some() {
+        run_command("git", &vec![String::from("-C"), tmp_str.clone(), String::from("checkout"), String::from("--quiet"), version.as_ref().unwrap().clone()])?;
+    }
+    let commit = run_command("git", &vec![String::from("-C"), tmp_str.clone(), String::from("rev-parse"), String::from("HEAD")])?;
+    return Ok((tmp.clone(), Some(commit.trim().to_string())));
+}
+
+fn fetch_archive(archive: &String, tmp: &PathBuf) -> Result<(PathBuf, Option<String>), String> {
+    if ! Path::new(archive).is_file() {
+        return Err(format!("{}: no such file", archive));
+    }
+    if let Err(e) = fs::create_dir_all(tmp) {
+        return Err(format!("unable to create {}: {}", path_as_string(tmp), e));
+    }
+    run_command("tar", &vec![String::from("-xf"), archive.clone(), String::from("-C"), path_as_string(tmp)])?;
+
+    // archives usually wrap the role in a single top level directory
+    if tmp.join("role.yml").is_file() {
+        return Ok((tmp.clone(), None));
+    }
+    let mut children : Vec<PathBuf> = Vec::new();
+    for entry in jet_read_dir(tmp)? {
+        children.push(entry.unwrap().path());
+    }
+    if children.len() == 1 && children[0].is_dir() {
+        return Ok((children[0].clone(), None));
+    }
+    return Ok((tmp.clone(), None));
+}
+
+fn run_command(cmd: &str, args: &Vec<String>) -> Result<String, String> {
+    let output = match Command::new(cmd).args(args).output() {
+        Ok(x) => x,
+        Err(e) => { return Err(format!("unable to run {}: {}", cmd, e)); }
+    };
+    if ! output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{} {} failed: {}", cmd, args.join(" "), stderr.trim()));
+    }
+    return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+}
+
+fn remove_directory(path: &PathBuf) -> Result<(), String> {
+    return match fs::remove_dir_all(path) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("unable to remove {}: {}", path_as_string(path), e))
+    };
+}
+
+fn load_lock_file(role_path: &PathBuf) -> Result<Vec<LockedRole>, String> {
+    let path = role_path.join(LOCK_FILE);
+    if ! path.exists() {
+        return Ok(Vec::new());
+    }
+    let fh = jet_file_open(&path)?;
+    let parsed: Result<Vec<LockedRole>, serde_yaml::Error> = serde_yaml::from_reader(fh);
+    if parsed.is_err() {
+        show_yaml_error_in_context(&parsed.unwrap_err(), &path);
+        return Err(format!("edit the file and try again?"));
+    }
+    return Ok(parsed.unwrap());
+}
+
+fn write_lock_file(role_path: &PathBuf, locked: &Vec<LockedRole>) -> Result<(), String> {
+    let path = role_path.join(LOCK_FILE);
+    let mut contents = String::from("# written by jetp roles install, do not edit\n");
+    contents.push_str(&serde_yaml::to_string(locked).unwrap());
+    return match fs::write(&path, contents) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("unable to write {}: {}", path_as_string(&path), e))
+    };
+}
+
+fn directory_checksum(path: &PathBuf) -> Result<String, String> {
+
+    // covers file names and contents, so renames and deletions count as modifications.
+    // the git metadata is left out as it changes on its own.
+
+    let mut files : Vec<PathBuf> = Vec::new();
+    collect_files(path, &mut files)?;
+    files.sort();
+    let mut hasher = Sha512::new();
+    for file in files.iter() {
+        let relative = file.strip_prefix(path).unwrap();
+        let contents = match fs::read(file) {
+            Ok(x) => x,
+            Err(e) => { return Err(format!("unable to read {}: {}", path_as_string(file), e)); }
+        };
+        hasher.update(path_as_string(relative).as_bytes());
+        hasher.update([0u8]);
+        hasher.update(&contents);
+        hasher.update([0u8]);
+    }
+    let result = hasher.finalize();
+    return Ok(format!("{result:x}"));
+}
+
+fn file_checksum(path: &PathBuf) -> Result<String, String> {
+    let contents = match fs::read(path) {
+        Ok(x) => x,
+        Err(e) => { return Err(format!("unable to read {}: {}", path_as_string(path), e)); }
+    };
+    let mut hasher = Sha512::new();
+    hasher.update(&contents);
+    let result = hasher.finalize();
+    return Ok(format!("{result:x}"));
+}
+
+fn collect_files(path: &PathBuf, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    for entry in jet_read_dir(path)? {
+        let entry_path = entry.unwrap().path();
+        if entry_path.file_name().unwrap().eq(".git") {
+            continue;
+        }
+        if entry_path.is_dir() {
+            collect_files(&entry_path, files)?;
+        } else {
+            files.push(entry_path);
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn temp_path(name: &str) -> PathBuf {
+        return env::temp_dir().join(format!("jet-roles-{}-{}", name, guid_create::GUID::rand()));
+    }
+
+    fn load(contents: &str) -> Result<Vec<RoleRequirement>, String> {
+        let path = temp_path("requirements.yml");
+        fs::write(&path, contents).unwrap();
+        let result = load_requirements(&path);
+        fs::remove_file(&path).unwrap();
+        return result;
+    }
+
+    #[test]
+    fn test_load_requirements() {
+        let requirements = load("- name: redis\n  git: https://example.com/redis.git\n  version: v1.2.0\n- name: common\n  archive: common.tar.gz\n").unwrap();
+        assert_eq!(requirements.len(), 2);
+        assert_eq!(requirements[0].name, "redis");
+        assert_eq!(requirements[0].version, Some(String::from("v1.2.0")));
+        assert_eq!(requirements[1].archive, Some(String::from("common.tar.gz")));
+    }
+
+    #[test]
+    fn test_load_requirements_invalid() {
+        let cases = vec![
+            ("- name: ''\n  git: x\n", "is not valid"),
+            ("- name: a/b\n  git: x\n", "is not valid"),
+            ("- name: ..\n  git: x\n", "is not valid"),
+            ("- name: a\n  git: x\n- name: a\n  git: y\n", "listed more than once"),
+            ("- name: a\n", "exactly one of git or archive"),
+            ("- name: a\n  git: x\n  archive: y\n", "exactly one of git or archive"),
+            ("- name: a\n  archive: y\n  version: v1\n", "version can only be used with git"),
+            ("- name: a\n  git: x\n  version: --upload-pack=y\n", "version (--upload-pack=y) is not valid"),
+        ];
+        for (contents, expected) in cases.iter() {
+            let err = load(contents).unwrap_err();
+            assert!(err.contains(expected), "{:?}: {}", contents, err);
+        }
+        assert!(load("- name: a\n  url: x\n").is_err());
+    }
+
+    // builds common.tar.gz in the role path holding a role.yml with the given contents
+    fn create_archive(role_path: &PathBuf, contents: &str) {
+        let staging = role_path.join("staging");
+        fs::create_dir_all(staging.join("common")).unwrap();
+        fs::write(staging.join("common").join("role.yml"), contents).unwrap();
+        let archive = path_as_string(&role_path.join("common.tar.gz"));
+        run_command("tar", &vec![String::from("-czf"), archive, String::from("-C"), path_as_string(&staging), String::from("common")]).unwrap();
+        fs::remove_dir_all(&staging).unwrap();
+    }
+
+    #[test]
+    fn test_install_role_refuses_to_overwrite() {
+        let role_path = temp_path("roles");
+        let dest = role_path.join("common");
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(dest.join("role.yml"), "name: common\n").unwrap();
+        create_archive(&role_path, "name: common\n");
+        let requirement = RoleRequirement {
+            name: String::from("common"),
+            git: None,
+            version: None,
+            archive: Some(String::from("common.tar.gz"))
+        };
+
+        // a role directory that was not installed by this command
+        let mut locked : Vec<LockedRole> = Vec::new();
+        let err = install_role(&requirement, &role_path, &role_path, &mut locked).unwrap_err();
+        assert!(err.contains("was not installed by jetp roles install"), "{}", err);
+
+        // an installed role that has not changed is left alone
+        locked.push(LockedRole {
+            name: String::from("common"),
+            source: path_as_string(&role_path.join("common.tar.gz")),
+            version: None,
+            commit: None,
+            checksum: directory_checksum(&dest).unwrap(),
+            archive_checksum: Some(file_checksum(&role_path.join("common.tar.gz")).unwrap())
+        });
+        assert_eq!(install_role(&requirement, &role_path, &role_path, &mut locked).unwrap(), "already installed");
+
+        // an installed role that was modified locally is never overwritten
+        fs::write(dest.join("role.yml"), "name: common\nchanged: true\n").unwrap();
+        let err = install_role(&requirement, &role_path, &role_path, &mut locked).unwrap_err();
+        assert!(err.contains("was modified since it was installed"), "{}", err);
+        assert_eq!(fs::read_to_string(dest.join("role.yml")).unwrap(), "name: common\nchanged: true\n");
+        assert_eq!(locked.len(), 1);
+
+        fs::remove_dir_all(&role_path).unwrap();
+    }
+
+    #[test]
+    fn test_install_role_archive_changed() {
+        let role_path = temp_path("roles");
+        create_archive(&role_path, "name: common\n");
+        let requirement = RoleRequirement {
+            name: String::from("common"),
+            git: None,
+            version: None,
+            archive: Some(String::from("common.tar.gz"))
+        };
+        let mut locked : Vec<LockedRole> = Vec::new();
+        assert_eq!(install_role(&requirement, &role_path, &role_path, &mut locked).unwrap(), "installed");
+        assert_eq!(install_role(&requirement, &role_path, &role_path, &mut locked).unwrap(), "already installed");
+
+        // a new archive at the same path replaces the installed role
+        create_archive(&role_path, "name: common\nversion: 2\n");
+        assert_eq!(install_role(&requirement, &role_path, &role_path, &mut locked).unwrap(), "updated");
+        assert_eq!(fs::read_to_string(role_path.join("common").join("role.yml")).unwrap(), "name: common\nversion: 2\n");
+        assert_eq!(locked[0].archive_checksum, Some(file_checksum(&role_path.join("common.tar.gz")).unwrap()));
+
+        // lock files written before archives were checksummed install the role again once
+        locked[0].archive_checksum = None;
+        assert_eq!(install_role(&requirement, &role_path, &role_path, &mut locked).unwrap(), "updated");
+        assert_eq!(install_role(&requirement, &role_path, &role_path, &mut locked).unwrap(), "already installed");
+
+        fs::remove_dir_all(&role_path).unwrap();
+    }
+
+    #[test]
+    fn test_lock_file_without_archive_checksum() {
+        let role_path = temp_path("roles");
+        fs::create_dir_all(&role_path).unwrap();
+        fs::write(role_path.join(LOCK_FILE), "- name: redis\n  source: x\n  version: null\n  commit: abc\n  checksum: def\n").unwrap();
+        let locked = load_lock_file(&role_path).unwrap();
+        assert_eq!(locked[0].archive_checksum, None);
+        write_lock_file(&role_path, &locked).unwrap();
+        assert!(! fs::read_to_string(role_path.join(LOCK_FILE)).unwrap().contains("archive_checksum"));
+        fs::remove_dir_all(&role_path).unwrap();
+    }
+}
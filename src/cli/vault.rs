@@ -0,0 +1,182 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::cli::parser::{CliParser,CliMode};
+use crate::util::io::{read_local_file,path_as_string};
+use crate::util::vault::{is_vault_encrypted,vault_encrypt,vault_decrypt};
+use guid_create::GUID;
+use std::os::unix::fs::OpenOptionsExt;
+use std::io::Write;
+use std::path::{Path,PathBuf};
+use std::process::Command;
+use std::env;
+use std::fs;
+
+// cli support for the vault subcommand
+//
+// jetp vault encrypt -f group_vars/dbservers
+// jetp vault decrypt -f group_vars/dbservers
+// jetp vault view -f group_vars/dbservers
+// jetp vault edit -f group_vars/dbservers
+//
+// the password comes from --vault-password-file, --ask-vault-password or $JET_VAULT_PASSWORD
+// and is asked for on standard input otherwise.
+
+// ==============================================================================================================
+// PUBLIC API
+// ==============================================================================================================
+
+pub fn vault_command(parser: &CliParser) -> i32 {
+    return match handle_vault(parser) {
+        Ok(_) => 0,
+        Err(s) => {
+            println!("{}", s);
+            1
+        }
+    };
+}
+
+// ==============================================================================================================
+// PRIVATE
+// ==============================================================================================================
+
+fn handle_vault(parser: &CliParser) -> Result<(), String> {
+    let path = parser.file_path.as_ref().unwrap();
+    let creating = parser.mode == CliMode::CLI_MODE_VAULT_EDIT && ! path.exists();
+    if ! creating && ! path.is_file() {
+        return Err(format!("{}: no such file", path_as_string(path)));
+    }
+
+    // a new password is asked for twice, since a typo would lock the file away
+    let confirm = parser.mode == CliMode::CLI_MODE_VAULT_ENCRYPT || creating;
+    let password = match parser.vault_password.is_some() {
+        true  => parser.vault_password.as_ref().unwrap().clone(),
+        false => prompt_password(confirm)?
+    };
+
+    return match parser.mode {
+        CliMode::CLI_MODE_VAULT_ENCRYPT => encrypt_file(path, &password),
+        CliMode::CLI_MODE_VAULT_DECRYPT => decrypt_file(path, &password),
+        CliMode::CLI_MODE_VAULT_VIEW    => view_file(path, &password),
+        CliMode::CLI_MODE_VAULT_EDIT    => edit_file(path, &password, creating),
+        _ => Err(String::from("invalid vault mode"))
+    };
+}
+
+fn encrypt_file(path: &PathBuf, password: &String) -> Result<(), String> {
+    let contents = read_local_file(path)?;
+    if is_vault_encrypted(&contents) {
+        return Err(format!("{} is already vault encrypted", path_as_string(path)));
+    }
+    write_file(path, &vault_encrypt(&contents, password)?)?;
+    println!("encrypted {}", path_as_string(path));
+    return Ok(());
+}
+
+fn decrypt_file(path: &PathBuf, password: &String) -> Result<(), String> {
+    let contents = read_local_file(path)?;
+    write_file(path, &vault_decrypt(&contents, password, path)?)?;
+    println!("decrypted {}", path_as_string(path));
+    return Ok(());
+}
+
+fn view_file(path: &PathBuf, password: &String) -> Result<(), String> {
+    let contents = read_local_file(path)?;
+    print!("{}", vault_decrypt(&contents, password, path)?);
+    return Ok(());
+}
+
+fn edit_file(path: &PathBuf, password: &String, creating: bool) -> Result<(), String> {
+    let plaintext = match creating {
+        true  => String::new(),
+        false => vault_decrypt(&read_local_file(path)?, password, path)?
+    };
+
+    // the cleartext only lives in a private temp file for as long as the editor runs
+    let tmp = env::temp_dir().join(format!("jet-vault-{}.yml", GUID::rand()));
+    write_private_file(&tmp, &plaintext)?;
+    let edited = run_editor(&tmp);
+    let _ = fs::remove_file(&tmp);
+    let edited = edited?;
+
+    if edited.eq(&plaintext) && ! creating {
+        println!("no changes to {}", path_as_string(path));
+        return Ok(());
+    }
+    write_file(path, &vault_encrypt(&edited, password)?)?;
+    println!("saved {}", path_as_string(path));
+    return Ok(());
+}
+
+fn run_editor(tmp: &Path) -> Result<String, String> {
+    let editor = match env::var("EDITOR") {
+        Ok(x) => x,
+        Err(_) => String::from("vi")
+    };
+    // editors are often given with arguments, as in 'code --wait'
+    let mut words = editor.split_whitespace();
+    let program = match words.next() {
+        Some(x) => x,
+        None => { return Err(String::from("$EDITOR is empty")); }
+    };
+    match Command::new(program).args(words).arg(tmp).status() {
+        Ok(status) => {
+            if ! status.success() {
+                return Err(format!("{} exited with {}, the file was not changed", editor, status));
+            }
+        },
+        Err(e) => { return Err(format!("unable to run {} (set $EDITOR): {}", editor, e)); }
+    }
+    return read_local_file(tmp);
+}
+
+fn prompt_password(confirm: bool) -> Result<String, String> {
+    let password = read_password("enter vault password:")?;
+    if password.is_empty() {
+        return Err(String::from("the vault password cannot be empty"));
+    }
+    if confirm && ! password.eq(&read_password("confirm vault password:")?) {
+        return Err(String::from("the vault passwords do not match"));
+    }
+    return Ok(password);
+}
+
+fn read_password(prompt: &str) -> Result<String, String> {
+    // read without echo so the password does not stay on screen
+    return match rpassword::prompt_password(format!("{} ", prompt)) {
+        Ok(value) => Ok(String::from(value.trim())),
+        Err(e) => Err(format!("failure reading input: {}", e))
+    };
+}
+
+fn write_file(path: &Path, contents: &String) -> Result<(), String> {
+    return match fs::write(path, contents) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("unable to write {}: {}", path.display(), e))
+    };
+}
+
+fn write_private_file(path: &Path, contents: &String) -> Result<(), String> {
+    let file = fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path);
+    let result = match file {
+        Ok(mut f) => f.write_all(contents.as_bytes()),
+        Err(e) => Err(e)
+    };
+    return match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("unable to write {}: {}", path.display(), e))
+    };
+}
@@ -43,7 +43,8 @@ pub struct Remote {
     connection: Arc<Mutex<dyn Connection>>,
     host: Arc<RwLock<Host>>, 
     template: Arc<Template>,
-    response: Arc<Response>
+    response: Arc<Response>,
+    quiet_response: Arc<Response>
 }
 
 #[derive(Debug,Copy,Clone,PartialEq)]
@@ -52,6 +53,12 @@ pub enum UseSudo {
     No
 }
 
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum ShowOutput {
+    Yes,
+    No
+}
+
 impl Remote {
 
     pub fn new(
@@ -61,12 +68,14 @@ impl Remote {
         template: Arc<Template>,
         response: Arc<Response>) -> Self {
         
+        let quiet_response = Arc::new(Response::new_quiet(Arc::clone(&run_state), Arc::clone(&host)));
         Self {
             run_state,
             connection,
             host,
             template,
             response,
+            quiet_response,
         }
     }
 
@@ -108,7 +117,13 @@ impl Remote {
     // wrappers around running CLI commands
 
     pub fn run(&self, request: &Arc<TaskRequest>, cmd: &String, check_rc: CheckRc) -> Result<Arc<TaskResponse>,Arc<TaskResponse>> {
-        return self.internal_run(request, cmd, Safety::Safe, check_rc, UseSudo::Yes, Forward::No);
+        return self.internal_run(request, cmd, Safety::Safe, check_rc, UseSudo::Yes, Forward::No, ShowOutput::Yes);
+    }
+
+    // like run, but the output is not shown even in verbose mode, for commands that read file contents
+
+    fn run_quiet(&self, request: &Arc<TaskRequest>, cmd: &String, check_rc: CheckRc) -> Result<Arc<TaskResponse>,Arc<TaskResponse>> {
+        return self.internal_run(request, cmd, Safety::Safe, check_rc, UseSudo::Yes, Forward::No, ShowOutput::No);
     }
 
     pub fn run_with_backup_cmd(&self, request: &Arc<TaskRequest>, main_cmd: &String, backup_cmd: &String, check_rc: CheckRc) -> (Result<Arc<TaskResponse>,Arc<TaskResponse>>, SuccessfulCommand) {
@@ -116,22 +131,22 @@ impl Remote {
     }
 
     pub fn run_forwardable(&self, request: &Arc<TaskRequest>, cmd: &String, check_rc: CheckRc) -> Result<Arc<TaskResponse>,Arc<TaskResponse>> {
-        return self.internal_run(request, cmd, Safety::Safe, check_rc, UseSudo::Yes, Forward::Yes);
+        return self.internal_run(request, cmd, Safety::Safe, check_rc, UseSudo::Yes, Forward::Yes, ShowOutput::Yes);
     }
 
     pub fn run_no_sudo(&self, request: &Arc<TaskRequest>, cmd: &String, check_rc: CheckRc) -> Result<Arc<TaskResponse>,Arc<TaskResponse>> {
-        return self.internal_run(request, cmd, Safety::Safe, check_rc, UseSudo::No, Forward::No);
+        return self.internal_run(request, cmd, Safety::Safe, check_rc, UseSudo::No, Forward::No, ShowOutput::Yes);
     }
 
     // the unsafe version of this doesn't check the shell string for possible shell variable injections, the most obvious and basic being ";"
     // usage of unsafe requires a special keyword in the 'shell' module for instance, or that no variables are present in the cmd parameter.
 
     pub fn run_unsafe(&self, request: &Arc<TaskRequest>, cmd: &String, check_rc: CheckRc) -> Result<Arc<TaskResponse>,Arc<TaskResponse>> {
-        return self.internal_run(request, cmd, Safety::Unsafe, check_rc, UseSudo::Yes, Forward::No);
+        return self.internal_run(request, cmd, Safety::Unsafe, check_rc, UseSudo::Yes, Forward::No, ShowOutput::Yes);
     }
 
     fn internal_run(&self, request: &Arc<TaskRequest>, cmd: &String, 
-        safe: Safety, check_rc: CheckRc, use_sudo: UseSudo, forward: Forward) -> Result<Arc<TaskResponse>,Arc<TaskResponse>> {
+        safe: Safety, check_rc: CheckRc, use_sudo: UseSudo, forward: Forward, show_output: ShowOutput) -> Result<Arc<TaskResponse>,Arc<TaskResponse>> {
         
         assert!(request.request_type != TaskRequestType::Validate, "commands cannot be run in validate stage");
 
@@ -159,7 +174,11 @@ impl Remote {
 
         self.response.get_visitor().read().expect("read visitor").on_command_run(&self.response.get_context(), &Arc::clone(&self.host), &cmd);
 
-        let result = self.connection.lock().unwrap().run_command(&self.response, request, &cmd_out, forward);
+        let response = match show_output {
+            ShowOutput::Yes => &self.response,
+            ShowOutput::No  => &self.quiet_response
+        };
+        let result = self.connection.lock().unwrap().run_command(response, request, &cmd_out, forward);
 
         // if requested, turn non-zero return codes into errors
 
@@ -167,7 +186,7 @@ impl Remote {
             let ok_result = result.as_ref().unwrap();
             let cmd_result = ok_result.command_result.as_ref().as_ref().unwrap();
             if cmd_result.rc != 0 {
-                return Err(self.response.command_failed(request, &Arc::new(Some(cmd_result.clone()))));
+                return Err(response.command_failed(request, &Arc::new(Some(cmd_result.clone()))));
             }
         }
 
@@ -471,6 +490,36 @@ impl Remote {
         };
     }
 
+    // reads the contents of a remote file, returning None if the file cannot be read.  This is only
+    // used for showing differences with --diff, transfers should use the connection functions above.
+
+    pub fn get_file_contents(&self, request: &Arc<TaskRequest>, path: &String) -> Result<Option<String>,Arc<TaskResponse>> {
+        let get_cmd_result = crate::tasks::cmd_library::get_read_file_command(self.get_os_type(), path);
+        let cmd = self.unwrap_string_result(&request, &get_cmd_result)?;
+        // the contents are shown as a diff, there is no need to show them again with the command output
+        let result = self.run_quiet(request, &cmd, CheckRc::Unchecked)?;
+        let (rc, out) = cmd_info(&result);
+        return match rc {
+            0 => Ok(Some(out)),
+            _ => Ok(None)
+        };
+    }
+
+    // when --diff is given, modules that replace file contents call this to show what would change.
+    // a file that does not exist yet is compared against an empty file.
+
+    pub fn show_diff(&self, request: &Arc<TaskRequest>, path: &String, desired: &String) -> Result<(),Arc<TaskResponse>> {
+        if ! self.run_state.diff {
+            return Ok(());
+        }
+        let current = match self.get_file_contents(request, path)? {
+            Some(x) => x,
+            None => String::new()
+        };
+        self.response.get_visitor().read().expect("read visitor").on_file_diff(&self.response.get_context(), &Arc::clone(&self.host), path, &current, desired);
+        return Ok(());
+    }
+
     // supporting code for any tasks that has an 'attributes' member, see 'template' for one example of usage
     // TODO: add SELinux
 
@@ -33,6 +33,7 @@ use std::sync::RwLock;
 pub struct Response {
     run_state: Arc<RunState>, 
     host: Arc<RwLock<Host>>, 
+    quiet: bool
 }
 
 impl Response {
@@ -41,6 +42,16 @@ impl Response {
         Self {
             run_state: run_state_handle,
             host: host_handle,
+            quiet: false
+        }
+    }
+
+    pub fn new_quiet(run_state_handle: Arc<RunState>, host_handle: Arc<RwLock<Host>>) -> Self {
+        // a quiet response does not show command results, for commands whose output should not be echoed
+        Self {
+            run_state: run_state_handle,
+            host: host_handle,
+            quiet: true
         }
     }
 
@@ -71,7 +82,9 @@ impl Response {
 
     pub fn command_failed(&self, _request: &Arc<TaskRequest>, result: &Arc<Option<CommandResult>>) -> Arc<TaskResponse> {
         // used internally by run functions in remote.rs when commands fail, suitable for use as a final module response
-        self.get_visitor().read().expect("read visitor").on_command_failed(&self.get_context(), &Arc::clone(&self.host), &Arc::clone(result));
+        if ! self.quiet {
+            self.get_visitor().read().expect("read visitor").on_command_failed(&self.get_context(), &Arc::clone(&self.host), &Arc::clone(result));
+        }
         return Arc::new(TaskResponse {
             status: TaskStatus::Failed,
             changes: Vec::new(), 
@@ -84,7 +97,9 @@ impl Response {
 
     pub fn command_ok(&self, _request: &Arc<TaskRequest>, result: &Arc<Option<CommandResult>>) -> Arc<TaskResponse> {
         // used internally by run functions in remote.rs when commands succeed, suitable for use as a final module response
-        self.get_visitor().read().expect("read visitor").on_command_ok(&self.get_context(), &Arc::clone(&self.host), &Arc::clone(result));
+        if ! self.quiet {
+            self.get_visitor().read().expect("read visitor").on_command_ok(&self.get_context(), &Arc::clone(&self.host), &Arc::clone(result));
+        }
         return Arc::new(TaskResponse {
             status: TaskStatus::IsExecuted,
             changes: Vec::new(), msg: None, command_result: Arc::clone(&result), beforetask: Arc::new(None), aftertask: Arc::new(None)
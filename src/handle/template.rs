@@ -15,7 +15,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::sync::{Arc,RwLock};
-use std::path::PathBuf;
+use std::path::{Path,PathBuf};
 use crate::tasks::request::TaskRequest;
 use crate::tasks::response::TaskResponse;
 use crate::inventory::hosts::Host;
@@ -23,7 +23,7 @@ use crate::playbooks::traversal::RunState;
 use crate::playbooks::context::PlaybookContext;
 use crate::tasks::cmd_library::{screen_path,screen_general_input_strict};
 use crate::handle::response::Response;
-use crate::playbooks::templar::{Templar,TemplateMode};
+use crate::playbooks::templar::{Templar,TemplateMode,TemplateEngine};
 
 // template contains support code for all variable evaluation in the playbook language, as well as
 // support for the template module, and ALSO the code to validate and process module arguments to make
@@ -91,8 +91,8 @@ impl Template {
         };
     }
 
-    fn template_unsafe_internal(&self, request: &Arc<TaskRequest>, tm: TemplateMode, _field: &String, template: &String, blend_target: BlendTarget) -> Result<String,Arc<TaskResponse>> {
-        let result = self.run_state.context.read().unwrap().render_template(template, &self.host, blend_target, tm);
+    fn template_unsafe_internal(&self, request: &Arc<TaskRequest>, tm: TemplateMode, _field: &String, template: &String, blend_target: BlendTarget, engine: TemplateEngine) -> Result<String,Arc<TaskResponse>> {
+        let result = self.run_state.context.read().unwrap().render_template_with_engine(template, &self.host, blend_target, tm, engine);
         if result.is_ok() {
             let result_ok = result.as_ref().unwrap();
             if result_ok.eq("") {
@@ -103,15 +103,15 @@ impl Template {
         return Ok(result2);
     }
     
-    pub fn string_for_template_module_use_only(&self, request: &Arc<TaskRequest>, tm: TemplateMode, field: &String, template: &String) -> Result<String,Arc<TaskResponse>> {
+    pub fn string_for_template_module_use_only(&self, request: &Arc<TaskRequest>, tm: TemplateMode, field: &String, template: &String, engine: TemplateEngine) -> Result<String,Arc<TaskResponse>> {
         // this is the version of templating that gives access to secret variables, we don't allow them elsewhere as they would be easy to leak to CI/CD/build output/logs
         // and the contents to templates are not shown to anything
-        return self.template_unsafe_internal(request, tm, field, template, BlendTarget::TemplateModule);
+        return self.template_unsafe_internal(request, tm, field, template, BlendTarget::TemplateModule, engine);
     }
 
     pub fn string_unsafe_for_shell(&self, request: &Arc<TaskRequest>, tm: TemplateMode, field: &String, template: &String) -> Result<String,Arc<TaskResponse>> {
         // indicates templating a string that will not without further processing, be passed to a shell command
-        return self.template_unsafe_internal(request, tm, field, template, BlendTarget::NotTemplateModule);
+        return self.template_unsafe_internal(request, tm, field, template, BlendTarget::NotTemplateModule, TemplateEngine::Handlebars);
     }
 
 
@@ -131,7 +131,7 @@ impl Template {
         // indicates templating a string that will not without further processing, be passed to a shell command
         return match template.is_none() {
             true => Ok(None),
-            false => Ok(Some(self.template_unsafe_internal(request, tm, field, &template.as_ref().unwrap(), BlendTarget::NotTemplateModule)?))
+            false => Ok(Some(self.template_unsafe_internal(request, tm, field, &template.as_ref().unwrap(), BlendTarget::NotTemplateModule, TemplateEngine::Handlebars)?))
         }
     }
 
@@ -177,16 +177,6 @@ impl Template {
         }
     }
 
-    pub fn string_option_trim(&self, request: &Arc<TaskRequest>, tm: TemplateMode, field: &String, template: &Option<String>) -> Result<Option<String>,Arc<TaskResponse>> {
-        // for processing parameters that take optional strings, but make sure to remove any extra surrounding whitespace
-        // YAML should do this anyway so it's mostly overkill but may prevent some rare errors from inventory variable sources
-        let prelim = self.string_option(request, tm, field, template)?;
-        if prelim.is_some() {
-            return Ok(Some(prelim.unwrap().trim().to_string()));
-        }
-        return Ok(None);
-    }
-
     pub fn no_template_string_option_trim(&self, input: &Option<String>) -> Option<String> {
         // takes a string option and uses it verbatim, for parameters that do not allow variables in them
         if input.is_some() {
@@ -329,6 +319,18 @@ impl Template {
         }
     }
 
+    pub fn template_engine(&self, request: &Arc<TaskRequest>, tm: TemplateMode, field: &String, template: &Option<String>, src: &String) -> Result<TemplateEngine,Arc<TaskResponse>> {
+        // templates the engine choice of the template module, which otherwise follows the extension of the source file
+        if tm == TemplateMode::Off {
+            return Ok(TemplateEngine::Handlebars);
+        }
+        let engine = self.string_option_no_spaces(request, tm, field, template)?;
+        return match TemplateEngine::select(&engine, Path::new(src)) {
+            Ok(x) => Ok(x),
+            Err(y) => Err(self.response.is_failed(request, &format!("field ({}): {}", field, y)))
+        };
+    }
+
     pub fn test_condition(&self, request: &Arc<TaskRequest>, tm: TemplateMode, expr: &String) -> Result<bool, Arc<TaskResponse>> {
         // used to evaluate in-language conditionals throughout the program.
         if tm == TemplateMode::Off {
@@ -397,15 +399,36 @@ impl Template {
                 return Err(self.response.is_failed(request, &format!("field ({}): no such file: {}", field, str_path)));
             }
         } else {
-            let mut path2 = PathBuf::new();
-            path2.push(prefix);
-            path2.push(str_path);
-            if path2.is_file() {
-                return Ok(path2);
-            } else {
-                return Err(self.response.is_failed(request, &format!("field ({}): no such file: {}", field, str_path)));
+            // relative paths are looked for in the role first, then next to the playbook
+            let mut tried : Vec<String> = Vec::new();
+            for base in self.get_search_directories().iter() {
+                let mut path2 = PathBuf::new();
+                path2.push(base);
+                path2.push(prefix);
+                path2.push(str_path);
+                if path2.is_file() {
+                    return Ok(path2);
+                }
+                tried.push(path2.display().to_string());
             }
+            return Err(self.response.is_failed(request, &format!("field ({}): no such file: {}, tried: {}", field, str_path, tried.join(", "))));
+        }
+    }
+
+    fn get_search_directories(&self) -> Vec<String> {
+        let context = self.get_context();
+        let ctx = context.read().unwrap();
+        let mut results : Vec<String> = Vec::new();
+        if ctx.role_path.is_some() {
+            results.push(ctx.role_path.as_ref().unwrap().clone());
+        }
+        if ctx.playbook_directory.is_some() {
+            results.push(ctx.playbook_directory.as_ref().unwrap().clone());
+        }
+        if results.is_empty() {
+            results.push(String::from("."));
         }
+        return results;
     }
 
     fn has_spaces(&self, input: &String) -> bool {
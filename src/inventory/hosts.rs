@@ -45,7 +45,8 @@ pub struct Host {
     checksum_cache_task_id : usize,
     facts                  : serde_yaml::Value,
     pub package_preference : Option<PackagePreference>,
-    notified_handlers      : HashMap<usize, HashSet<String>>
+    notified_handlers      : HashMap<usize, HashSet<String>>,
+    running_handlers       : HashMap<usize, HashSet<String>>
 }
 
 impl Host {
@@ -60,6 +61,7 @@ impl Host {
             checksum_cache_task_id: 0,
             facts: serde_yaml::Value::from(serde_yaml::Mapping::new()),
             notified_handlers: HashMap::new(),
+            running_handlers: HashMap::new(),
             package_preference: None
         }
     }
@@ -72,8 +74,12 @@ impl Host {
         entry.insert(signal.clone());
     }
 
+    // handlers run in passes. at the start of a pass, notifications made so far are moved aside and
+    // those are the ones handlers are checked against, while any handler that notifies another
+    // queues it for the next pass. they are cleared at the end of the pass so each runs once.
+
     pub fn is_notified(&self, play_number: usize, signal: &String) -> bool {
-        let entry = self.notified_handlers.get(&play_number);
+        let entry = self.running_handlers.get(&play_number);
         if entry.is_none() {
             return false;
         } else {
@@ -81,6 +87,20 @@ impl Host {
         }
     }
 
+    pub fn start_handlers(&mut self, play_number: usize) -> bool {
+        let pending = match self.notified_handlers.remove(&play_number) {
+            Some(x) => x,
+            None => HashSet::new()
+        };
+        let has_pending = ! pending.is_empty();
+        self.running_handlers.insert(play_number, pending);
+        return has_pending;
+    }
+
+    pub fn finish_handlers(&mut self, play_number: usize) {
+        self.running_handlers.remove(&play_number);
+    }
+
     pub fn set_checksum_cache(&mut self, path: &String, checksum: &String) {
         self.checksum_cache.insert(path.clone(), checksum.clone());
     }
@@ -209,6 +229,14 @@ impl Host {
         blend_variables(&mut self.facts, serde_yaml::Value::Mapping(mapping));
     }
 
+    pub fn clear_fact(&mut self, key: &String) {
+        // facts are blended, so this is needed when a value must be replaced rather than merged
+        match self.facts.as_mapping_mut() {
+            Some(x) => { x.remove(&serde_yaml::Value::String(key.clone())); },
+            None => {}
+        };
+    }
+
     pub fn get_variables_yaml(&self) -> Result<String, String> {
         let result = serde_yaml::to_string(&self.get_variables());
         return match result {
@@ -19,6 +19,7 @@ use Vec;
 use serde::Deserialize;
 use crate::util::io::{path_walk,jet_file_open,path_basename_as_string,is_executable};
 use crate::util::yaml::show_yaml_error_in_context;
+use crate::util::vault::load_vars_file;
 use crate::inventory::inventory::Inventory;
 use std::sync::Arc;
 use std::sync::RwLock;
@@ -61,7 +62,7 @@ pub struct DynamicInventoryJsonEntry {
 // PUBLIC API
 // ==============================================================================================================
 
-pub fn load_inventory(inventory: &Arc<RwLock<Inventory>>, inventory_paths: Arc<RwLock<Vec<PathBuf>>>) -> Result<(), String> {
+pub fn load_inventory(inventory: &Arc<RwLock<Inventory>>, inventory_paths: Arc<RwLock<Vec<PathBuf>>>, vault_password: &Option<String>) -> Result<(), String> {
 
     {
         let mut inv_obj = inventory.write().unwrap();
@@ -75,7 +76,7 @@ pub fn load_inventory(inventory: &Arc<RwLock<Inventory>>, inventory_paths: Arc<R
             let groups_path         = groups_pathbuf.as_path();
 
             if groups_path.exists() && groups_path.is_dir() {
-                load_on_disk_inventory_tree(inventory, true, &inventory_path)?;
+                load_on_disk_inventory_tree(inventory, true, &inventory_path, vault_password)?;
             } else {
                 return Err(format!("missing groups/ in --inventory path parameter ({})", inventory_path.display()))
             }
@@ -84,7 +85,7 @@ pub fn load_inventory(inventory: &Arc<RwLock<Inventory>>, inventory_paths: Arc<R
                 load_dynamic_inventory(inventory, &inventory_path)?;
                 let dirname = directory_as_string(&inventory_path);
                 let dir = Path::new(&dirname);
-                load_on_disk_inventory_tree(inventory, false, &dir)?;
+                load_on_disk_inventory_tree(inventory, false, &dir, vault_password)?;
             } else {
                 return Err(format!("non-directory path to --inventory ({}) is not executable", inventory_path.display()))
             }    
@@ -98,7 +99,7 @@ pub fn load_inventory(inventory: &Arc<RwLock<Inventory>>, inventory_paths: Arc<R
 // ==============================================================================================================
 
 // loads an entire on-disk inventory tree structure (groups/, group_vars/, host_vars/)
-fn load_on_disk_inventory_tree(inventory: &Arc<RwLock<Inventory>>, include_groups: bool, path: &Path) -> Result<(), String> {
+fn load_on_disk_inventory_tree(inventory: &Arc<RwLock<Inventory>>, include_groups: bool, path: &Path, vault_password: &Option<String>) -> Result<(), String> {
     let path_buf           = PathBuf::from(path);
     let group_vars_pathbuf = path_buf.join("group_vars");
     let host_vars_pathbuf  = path_buf.join("host_vars");
@@ -110,10 +111,10 @@ fn load_on_disk_inventory_tree(inventory: &Arc<RwLock<Inventory>>, include_group
         load_groups_directory(inventory, &groups_path)?;
     }
     if group_vars_path.exists() {
-        load_vars_directory(inventory, &group_vars_path, true)?;
+        load_vars_directory(inventory, &group_vars_path, true, vault_password)?;
     }
     if host_vars_path.exists() {
-        load_vars_directory(inventory, &host_vars_path, false)?;
+        load_vars_directory(inventory, &host_vars_path, false, vault_password)?;
     }
     return Ok(())
 }
@@ -170,7 +171,7 @@ fn add_group_file_contents_to_inventory(inventory: &Arc<RwLock<Inventory>>, grou
 }
             
 // this is used by both on-disk and dynamic inventory sources to load group_vars/ and host_vars/ directories
-fn load_vars_directory(inventory: &Arc<RwLock<Inventory>>, path: &Path, is_group: bool) -> Result<(), String> {
+fn load_vars_directory(inventory: &Arc<RwLock<Inventory>>, path: &Path, is_group: bool, vault_password: &Option<String>) -> Result<(), String> {
 
     let inv = inventory.write().unwrap();
 
@@ -195,13 +196,8 @@ fn load_vars_directory(inventory: &Arc<RwLock<Inventory>>, path: &Path, is_group
             }
         }
         
-        let file = jet_file_open(&vars_path)?;
-        let file_parse_result: Result<serde_yaml::Mapping, serde_yaml::Error> = serde_yaml::from_reader(file);
-        if file_parse_result.is_err() {
-             show_yaml_error_in_context(&file_parse_result.unwrap_err(), &vars_path);
-             return Err(format!("edit the file and try again?"));
-        } 
-        let yaml_result = file_parse_result.unwrap();
+        // files may be vault encrypted, see 'jetp vault'
+        let yaml_result = load_vars_file(&vars_path, vault_password)?;
         
         // serialize the vars again just to make them easier to store/output elsewhere
         // this will also remove any comments and shorten things up
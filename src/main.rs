@@ -29,6 +29,8 @@ use crate::inventory::inventory::Inventory;
 use crate::inventory::loading::load_inventory;
 use crate::cli::show::{show_inventory_group,show_inventory_host};
 use crate::cli::parser::CliParser;
+use crate::cli::roles::roles_install;
+use crate::cli::vault::vault_command;
 use crate::cli::playbooks::{playbook_ssh,playbook_local,playbook_check_ssh,playbook_check_local,playbook_simulate}; // FIXME: check modes coming
 use std::sync::{Arc,RwLock};
 use std::process;
@@ -52,11 +54,29 @@ fn liftoff() -> Result<(),String> {
         return Ok(());
     }
 
+    // roles install works on the local role paths only
+    if cli_parser.mode == cli::parser::CliMode::CLI_MODE_ROLES_INSTALL {
+        let exit_status = roles_install(&cli_parser);
+        if exit_status != 0 {
+            process::exit(exit_status);
+        }
+        return Ok(());
+    }
+
+    // so does the vault, which only touches the one file
+    if cli_parser.is_vault_mode() {
+        let exit_status = vault_command(&cli_parser);
+        if exit_status != 0 {
+            process::exit(exit_status);
+        }
+        return Ok(());
+    }
+
     let inventory : Arc<RwLock<Inventory>> = Arc::new(RwLock::new(Inventory::new()));
 
     match cli_parser.mode {
         cli::parser::CliMode::CLI_MODE_SSH | cli::parser::CliMode::CLI_MODE_CHECK_SSH | cli::parser::CliMode::CLI_MODE_SHOW_INVENTORY | cli::parser::CliMode::CLI_MODE_SIMULATE => {
-            load_inventory(&inventory, Arc::clone(&cli_parser.inventory_paths))?;
+            load_inventory(&inventory, Arc::clone(&cli_parser.inventory_paths), &cli_parser.vault_password)?;
             if ! cli_parser.inventory_set {
                 return Err(String::from("--inventory is required"));
             }
@@ -75,6 +75,7 @@ impl IsAction for CopyAction {
                 let mut changes : Vec<Field> = Vec::new();
                 let remote_mode = handle.remote.query_common_file_attributes(request, &self.dest, &self.attributes, &mut changes, Recurse::No)?;                   
                 if remote_mode.is_none() {
+                    self.show_diff(handle, request)?;
                     return Ok(handle.response.needs_creation(request));
                 }
                 // this query leg is (at least originally) the same as the template module query except these two lines
@@ -84,6 +85,7 @@ impl IsAction for CopyAction {
                 let remote_512 = handle.remote.get_sha512(request, &self.dest)?;
                 if ! remote_512.eq(&local_512) { 
                     changes.push(Field::Content); 
+                    self.show_diff(handle, request)?;
                 }
                 if ! changes.is_empty() {
                     return Ok(handle.response.needs_modification(request, &changes));
@@ -124,4 +126,15 @@ impl CopyAction {
         return Ok(());
     }
 
+    fn show_diff(&self, handle: &Arc<TaskHandle>, request: &Arc<TaskRequest>) -> Result<(), Arc<TaskResponse>> {
+        // only text files can be shown with --diff, binary sources are silently skipped
+        if ! handle.run_state.diff {
+            return Ok(());
+        }
+        return match handle.local.read_file(request, &self.src) {
+            Ok(data) => handle.remote.show_diff(request, &self.dest, &data),
+            Err(_) => Ok(())
+        };
+    }
+
 }
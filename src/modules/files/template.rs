@@ -19,6 +19,7 @@ use crate::handle::handle::TaskHandle;
 use crate::tasks::checksum::sha512;
 use crate::tasks::fields::Field;
 use std::path::PathBuf;
+use crate::playbooks::templar::TemplateEngine;
 use serde::Deserialize;
 use std::sync::Arc;
 use std::vec::Vec;
@@ -32,6 +33,7 @@ pub struct TemplateTask {
     pub name: Option<String>,
     pub src: String,
     pub dest: String,
+    pub engine: Option<String>,
     pub attributes: Option<FileAttributesInput>,
     pub beforetask: Option<PreLogicInput>,
     pub aftertask: Option<PostLogicInput>
@@ -40,6 +42,7 @@ pub struct TemplateTask {
 struct TemplateAction {
     pub src: PathBuf,
     pub dest: String,
+    pub engine: TemplateEngine,
     pub attributes: Option<FileAttributesEvaluated>,
 }
 
@@ -56,6 +59,7 @@ impl IsTask for TemplateTask {
                 action: Arc::new(TemplateAction {
                     src:        handle.template.find_template_path(request, tm, &String::from("src"), &src)?,
                     dest:       handle.template.path(&request, tm, &String::from("dest"), &self.dest)?,
+                    engine:     handle.template.template_engine(&request, tm, &String::from("engine"), &self.engine, &src)?,
                     attributes: FileAttributesInput::template(&handle, &request, tm, &self.attributes)?
                 }),
                 beforetask: Arc::new(PreLogicInput::template(&handle, &request, tm, &self.beforetask)?),
@@ -77,6 +81,10 @@ impl IsAction for TemplateAction {
                 let mut changes : Vec<Field> = Vec::new();
                 let remote_mode = handle.remote.query_common_file_attributes(request, &self.dest, &self.attributes, &mut changes, Recurse::No)?;                   
                 if remote_mode.is_none() {
+                    if handle.run_state.diff {
+                        let data = self.do_template(handle, request, false, None)?;
+                        handle.remote.show_diff(request, &self.dest, &data)?;
+                    }
                     return Ok(handle.response.needs_creation(request));
                 }
                 let data = self.do_template(handle, request, false, None)?;
@@ -84,6 +92,7 @@ impl IsAction for TemplateAction {
                 let remote_512 = handle.remote.get_sha512(request, &self.dest)?;
                 if ! remote_512.eq(&local_512) { 
                     changes.push(Field::Content); 
+                    handle.remote.show_diff(request, &self.dest, &data)?;
                 }
                 if ! changes.is_empty() {
                     return Ok(handle.response.needs_modification(request, &changes));
@@ -117,7 +126,7 @@ impl TemplateAction {
 
     pub fn do_template(&self, handle: &Arc<TaskHandle>, request: &Arc<TaskRequest>, write: bool, _changes: Option<Vec<Field>>) -> Result<String, Arc<TaskResponse>> {
         let template_contents = handle.local.read_file(&request, &self.src)?;
-        let data = handle.template.string_for_template_module_use_only(&request, TemplateMode::Strict, &String::from("src"), &template_contents)?;
+        let data = handle.template.string_for_template_module_use_only(&request, TemplateMode::Strict, &String::from("src"), &template_contents, self.engine)?;
         if write {
             handle.remote.write_data(&request, &data, &self.dest, |f| { /* after save */
                 match handle.remote.process_all_common_file_attributes(request, &f, &self.attributes, Recurse::No) {
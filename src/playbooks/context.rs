@@ -23,7 +23,7 @@ use std::sync::{Arc,RwLock};
 use crate::connection::cache::ConnectionCache;
 use crate::registry::list::Task;
 use crate::util::yaml::blend_variables;
-use crate::playbooks::templar::{Templar,TemplateMode};
+use crate::playbooks::templar::{Templar,TemplateMode,TemplateEngine};
 use crate::cli::parser::CliParser;
 use crate::handle::template::BlendTarget;
 use std::ops::Deref;
@@ -50,10 +50,24 @@ pub struct PlaybookContext {
 
     pub task_count: usize,
     pub task: Option<String>,
+
+    // with the 'free' strategy hosts are not all on the same task
+    host_tasks:               HashMap<String, (usize, String)>,
     
     seen_hosts:               HashMap<String, Arc<RwLock<Host>>>,
     targetted_hosts:          HashMap<String, Arc<RwLock<Host>>>,
     failed_hosts:             HashMap<String, Arc<RwLock<Host>>>,
+    batch_host_count:         usize,
+    // hosts that failed inside a block, waiting on rescue or always tasks, innermost block last
+    block_failed_hosts:       Vec<HashMap<String, Arc<RwLock<Host>>>>,
+    // how many includes the linear strategy is inside of, the free strategy counts per host
+    include_depth:            usize,
+    // handlers without subscribe only run in the first handler pass at the end of a batch
+    unsubscribed_handlers:    bool,
+    batch_failed_count:       usize,
+    // the task each failed host failed in, for the retry file
+    failed_host_tasks:        HashMap<String, String>,
+    set_aside_host_tasks:     HashMap<String, String>,
 
     attempted_count_for_host: HashMap<String, usize>,
     adjusted_count_for_host:  HashMap<String, usize>,
@@ -98,9 +112,17 @@ impl PlaybookContext {
             play_count : 0,
             role_count : 0,
             task_count : 0,
+            host_tasks: HashMap::new(),
             seen_hosts: HashMap::new(),
             targetted_hosts: HashMap::new(),
             failed_hosts: HashMap::new(),
+            batch_host_count: 0,
+            block_failed_hosts: Vec::new(),
+            include_depth: 0,
+            unsubscribed_handlers: false,
+            batch_failed_count: 0,
+            failed_host_tasks: HashMap::new(),
+            set_aside_host_tasks: HashMap::new(),
             role_path: None,
             adjusted_count_for_host:  HashMap::new(),
             attempted_count_for_host: HashMap::new(),
@@ -166,6 +188,8 @@ impl PlaybookContext {
                 }
             }
         }
+        self.batch_host_count = hosts.len();
+        self.batch_failed_count = 0;
     }
 
     // called when a host returns an unacceptable final response.  removes
@@ -178,10 +202,110 @@ impl PlaybookContext {
         self.failed_tasks = self.failed_tasks + 1;
 
         
-        self.targetted_hosts.remove(&hostname);
+        if self.targetted_hosts.remove(&hostname).is_some() {
+            self.batch_failed_count = self.batch_failed_count + 1;
+        }
+        // a host failing at the end of a block failed in whatever task put it aside
+        let task = match self.set_aside_host_tasks.remove(&hostname) {
+            Some(x) => Some(x),
+            None => self.get_current_task(&hostname)
+        };
+        if task.is_some() {
+            self.failed_host_tasks.insert(hostname.clone(), task.unwrap());
+        }
         self.failed_hosts.insert(hostname.clone(), Arc::clone(&host));
     }
 
+    fn get_current_task(&self, hostname: &String) -> Option<String> {
+        return match self.host_tasks.get(hostname) {
+            Some((_, task)) => Some(task.clone()),
+            None => self.task.clone()
+        };
+    }
+
+    // failed hosts in the order they are listed in the retry file, with the task they failed in
+
+    pub fn get_failed_hosts(&self) -> Vec<(String, Option<String>)> {
+        let mut names : Vec<String> = self.failed_hosts.keys().cloned().collect();
+        names.sort();
+        return names.into_iter().map(|x| { let task = self.failed_host_tasks.get(&x).cloned(); (x, task) }).collect();
+    }
+
+    // blocks with rescue or always sections hold on to failed hosts instead of failing them
+    // outright. the traversal code decides what happens to them once the block tasks are done.
+
+    pub fn push_block(&mut self) {
+        self.block_failed_hosts.push(HashMap::new());
+    }
+
+    pub fn pop_block(&mut self) -> HashMap<String, Arc<RwLock<Host>>> {
+        return self.block_failed_hosts.pop().expect("block stack underflow");
+    }
+
+    pub fn in_block(&self) -> bool {
+        return ! self.block_failed_hosts.is_empty();
+    }
+
+    pub fn get_include_depth(&self) -> usize {
+        return self.include_depth;
+    }
+
+    pub fn enter_include(&mut self) {
+        self.include_depth = self.include_depth + 1;
+    }
+
+    pub fn leave_include(&mut self) {
+        self.include_depth = self.include_depth - 1;
+    }
+
+    pub fn set_unsubscribed_handlers(&mut self, value: bool) {
+        self.unsubscribed_handlers = value;
+    }
+
+    pub fn get_unsubscribed_handlers(&self) -> bool {
+        return self.unsubscribed_handlers;
+    }
+
+    pub fn set_aside_host(&mut self, host: &Arc<RwLock<Host>>) {
+        let hostname = host.read().unwrap().name.clone();
+        match self.get_current_task(&hostname) {
+            Some(task) => { self.set_aside_host_tasks.insert(hostname.clone(), task); },
+            None => {}
+        }
+        if self.block_failed_hosts.is_empty() {
+            return;
+        }
+        self.targetted_hosts.remove(&hostname);
+        self.block_failed_hosts.last_mut().unwrap().insert(hostname, Arc::clone(&host));
+    }
+
+    pub fn rescue_host(&mut self, host: &Arc<RwLock<Host>>) {
+        let hostname = host.read().unwrap().name.clone();
+        self.set_aside_host_tasks.remove(&hostname);
+    }
+
+    // used when moving hosts between the tasks, rescue and always sections of a block
+
+    pub fn set_remaining_hosts(&mut self, hosts: &HashMap<String, Arc<RwLock<Host>>>) {
+        self.targetted_hosts.clear();
+        for (k,v) in hosts.iter() {
+            self.targetted_hosts.insert(k.clone(), Arc::clone(&v));
+        }
+    }
+
+    // how many hosts of the current batch have failed, used by max_fail_percentage and any_errors_fatal
+
+    pub fn get_batch_failed_count(&self) -> usize {
+        return self.batch_failed_count;
+    }
+
+    pub fn get_batch_failed_percentage(&self) -> f64 {
+        return match self.batch_host_count {
+            0 => 0.0,
+            n => (self.batch_failed_count as f64 * 100.0) / (n as f64)
+        };
+    }
+
     pub fn set_playbook_path(&mut self, path: &PathBuf) {
         self.playbook_path = Some(path_as_string(&path));
         self.playbook_directory = Some(directory_as_string(&path));
@@ -191,6 +315,20 @@ impl PlaybookContext {
         self.task = Some(task.get_display_name());
     }
 
+    // used by the 'free' strategy, where each host may be on a different task
+
+    pub fn set_host_task(&mut self, host: &String, task_ct: usize, task: &Task) {
+        self.host_tasks.insert(host.clone(), (task_ct, task.get_display_name()));
+    }
+
+    pub fn get_host_task(&self, host: &String) -> Option<(usize, String)> {
+        return self.host_tasks.get(host).cloned();
+    }
+
+    pub fn clear_host_tasks(&mut self) {
+        self.host_tasks.clear();
+    }
+
     pub fn set_play(&mut self, play: &Play) {
         self.play = Some(play.name.clone());
         self.play_count = self.play_count + 1;
@@ -206,12 +344,23 @@ impl PlaybookContext {
     pub fn set_role(&mut self, role: &Role, invocation: &RoleInvocation, role_path: &String) {
         self.role = Some(role.clone());
         self.role_path = Some(role_path.clone());
-        if role.defaults.is_some() { 
-             *self.role_defaults_storage.write().unwrap() = role.defaults.as_ref().unwrap().clone();
+        // roles without defaults or vars must not see the ones of the previous role
+        *self.role_defaults_storage.write().unwrap() = match &role.defaults {
+            Some(x) => x.clone(),
+            None => serde_yaml::Mapping::new()
+        };
+        // vars given when invoking the role win over the role's own vars
+        let mut role_vars = serde_yaml::Value::from(serde_yaml::Mapping::new());
+        if role.vars.is_some() {
+            blend_variables(&mut role_vars, serde_yaml::Value::Mapping(role.vars.as_ref().unwrap().clone()));
         }
-        if invocation.vars.is_some() { 
-            *self.role_vars_storage.write().unwrap() = invocation.vars.as_ref().unwrap().clone();
+        if invocation.vars.is_some() {
+            blend_variables(&mut role_vars, serde_yaml::Value::Mapping(invocation.vars.as_ref().unwrap().clone()));
         }
+        *self.role_vars_storage.write().unwrap() = match role_vars {
+            serde_yaml::Value::Mapping(x) => x,
+            _ => panic!("unexpected, get_blended_variables produced a non-mapping (4)")
+        };
     }
 
     pub fn unset_role(&mut self) {
@@ -274,8 +423,12 @@ impl PlaybookContext {
     // variables in the correct order.
 
     pub fn render_template(&self, template: &String, host: &Arc<RwLock<Host>>, blend_target: BlendTarget, template_mode: TemplateMode) -> Result<String,String> {
+        return self.render_template_with_engine(template, host, blend_target, template_mode, TemplateEngine::Handlebars);
+    }
+
+    pub fn render_template_with_engine(&self, template: &String, host: &Arc<RwLock<Host>>, blend_target: BlendTarget, template_mode: TemplateMode, engine: TemplateEngine) -> Result<String,String> {
         let vars = self.get_complete_blended_variables(host, blend_target);
-        return self.templar.read().unwrap().render(template, vars, template_mode);
+        return self.templar.read().unwrap().render_with_engine(template, vars, template_mode, engine);
     }
 
     // testing conditions for truthiness works much like templating strings
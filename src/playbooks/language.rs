@@ -16,9 +16,26 @@
 
 use serde::Deserialize;
 use crate::registry::list::Task;
+use crate::tasks::logic::{PreLogicInput,get_loop_var};
 
 // all the playbook language YAML structures!
 
+// a playbook is a list of plays, any of which may instead be an import_playbook
+// line that pulls in the plays of another playbook file, so a site playbook can
+// be composed from per-service ones. paths are relative to the importing playbook.
+
+#[derive(Debug)]
+pub enum PlaybookEntry {
+    ImportPlaybook(ImportPlaybook),
+    Play(Play)
+}
+
+#[derive(Debug,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImportPlaybook {
+    pub import_playbook: String
+}
+
 #[derive(Debug,Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Play {
@@ -35,6 +52,11 @@ pub struct Play {
     pub tasks : Option<Vec<Task>>,
     pub handlers : Option<Vec<Task>>,
     pub batch_size : Option<usize>,
+    pub strategy : Option<String>,
+    pub max_fail_percentage : Option<usize>,
+    pub any_errors_fatal : Option<bool>,
+    pub tags : Option<Vec<String>>,
+    pub secrets : Option<Vec<String>>,
 }
 
 #[derive(Debug,Deserialize,Clone)]
@@ -42,11 +64,30 @@ pub struct Play {
 pub struct Role {
     pub name: String,
     pub defaults: Option<serde_yaml::Mapping>,
+    pub vars: Option<serde_yaml::Mapping>,
     pub tasks: Option<Vec<String>>,
-    pub handlers: Option<Vec<String>>
+    pub handlers: Option<Vec<String>>,
+    pub dependencies: Option<Vec<RoleInvocation>>,
+    pub argument_spec: Option<Vec<RoleArgument>>
 }
 
-#[derive(Debug,Deserialize)]
+// roles may describe the variables they take, which are checked before the role runs.
+// arguments with a default work like role defaults.
+
+#[derive(Debug,Deserialize,Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RoleArgument {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub argument_type: Option<String>,
+    pub required: Option<bool>,
+    pub default: Option<serde_yaml::Value>,
+    pub choices: Option<Vec<serde_yaml::Value>>
+}
+
+// roles are invoked from the play's roles list, or from the dependencies of another role
+
+#[derive(Debug,Deserialize,Clone)]
 #[serde(deny_unknown_fields)]
 pub struct RoleInvocation {
     pub role: String,
@@ -54,4 +95,62 @@ pub struct RoleInvocation {
     pub tags: Option<Vec<String>>
 }
 
+// a block groups tasks so that failures can be handled together. hosts that fail
+// in 'tasks' run the 'rescue' tasks instead of dropping out of the play, and every
+// host that started the block runs the 'always' tasks afterwards.
+
+#[derive(Debug,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Block {
+    pub name: Option<String>,
+    pub tasks: Vec<Task>,
+    pub rescue: Option<Vec<Task>>,
+    pub always: Option<Vec<Task>>
+}
+
+impl Block {
+    pub fn get_display_name(&self) -> String {
+        return match &self.name { Some(x) => x.clone(), None => String::from("block") };
+    }
+}
+
+// task files can be pulled into a play, role or block two ways. import_tasks is
+// resolved when the file containing it is loaded, so it cannot use variables.
+// include is evaluated for each host when it is reached, so the path may be
+// templated and it works with beforetask checkcondition and items.
+
+#[derive(Debug,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImportTasks {
+    pub name: Option<String>,
+    pub file: String
+}
+
+#[derive(Debug,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Include {
+    pub name: Option<String>,
+    pub file: String,
+    pub beforetask: Option<PreLogicInput>
+}
+
+// flush_handlers runs any handlers notified so far, rather than waiting
+// for the end of the play, for instance to restart a service before a
+// later task depends on it.
+
+#[derive(Debug,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FlushHandlers {
+    pub name: Option<String>
+}
+
+impl Include {
+    pub fn get_loop_var(&self) -> String {
+        return match &self.beforetask {
+            Some(x) => get_loop_var(&x.loop_var),
+            None => String::from("item")
+        };
+    }
+}
+
 // for Task/module definitions see registry/list.rs
@@ -0,0 +1,201 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::playbooks::language::{Play,PlaybookEntry,RoleInvocation};
+use crate::playbooks::traversal::{RunState,HandlerMode,load_playbook,load_tasks_file,find_role,
+    get_imported_playbook_path,get_role_task_path,get_task_tags,tags_selected,get_play_hosts,
+    validate_limit_groups,validate_limit_hosts,validate_groups};
+use crate::registry::list::Task;
+use crate::util::terminal::two_column_table;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// the listing modes walk the playbooks the same way the traversal does, but only to
+// report on what is inside of them. no hosts are contacted and nothing is run. includes
+// are decided per host at runtime so they are listed as they are, without their contents.
+
+pub struct ListedTask {
+    pub name: String,
+    pub tags: Vec<String>,
+    pub role: Option<String>,
+    pub handler: bool
+}
+
+pub fn list_hosts(run_state: &Arc<RunState>) -> Result<(), String> {
+
+    // shows the hosts each play would run against after --limit-groups and --limit-hosts
+
+    walk_playbooks(run_state, &mut |_playbook_path, play, _tasks| {
+        validate_limit_groups(run_state, play)?;
+        validate_limit_hosts(run_state, play)?;
+        validate_groups(run_state, play)?;
+        let mut elements : Vec<(String,String)> = Vec::new();
+        for host in get_play_hosts(run_state, play).iter() {
+            let host = host.read().unwrap();
+            let mut groups = host.get_group_names();
+            groups.sort();
+            elements.push((host.name.clone(), groups.join(", ")));
+        }
+        elements.sort();
+        two_column_table(&format!("Play: {} ({} hosts)", play.name, elements.len()), &String::from("Groups"), &elements);
+        println!("");
+        return Ok(());
+    })?;
+    return Ok(());
+}
+
+pub fn list_tasks(run_state: &Arc<RunState>) -> Result<(), String> {
+
+    // shows the tasks and handlers each play would run, in order, after --tags and --skip-tags
+
+    walk_playbooks(run_state, &mut |_playbook_path, play, tasks| {
+        let mut elements : Vec<(String,String)> = Vec::new();
+        for task in tasks.iter() {
+            if ! tags_selected(&run_state.tags, &run_state.skip_tags, &task.tags) {
+                continue;
+            }
+            let mut label = String::new();
+            if task.role.is_some() { label.push_str(&format!("({}) ", task.role.as_ref().unwrap())); }
+            if task.handler { label.push_str("handler: "); }
+            label.push_str(&task.name);
+            elements.push((label, task.tags.join(", ")));
+        }
+        two_column_table(&format!("Play: {}", play.name), &String::from("Tags"), &elements);
+        println!("");
+        return Ok(());
+    })?;
+    return Ok(());
+}
+
+pub fn list_tags(run_state: &Arc<RunState>) -> Result<(), String> {
+
+    // shows the tags that can be used with --tags and --skip-tags, including those
+    // tasks inherit from their role invocation and play
+
+    let mut tables : Vec<(PathBuf, Vec<(String,String)>)> = Vec::new();
+    walk_playbooks(run_state, &mut |playbook_path, play, tasks| {
+        let mut tags : Vec<String> = Vec::new();
+        for task in tasks.iter() {
+            for tag in task.tags.iter() {
+                if ! tags.contains(tag) { tags.push(tag.clone()); }
+            }
+        }
+        tags.sort();
+        let row = (play.name.clone(), tags.join(", "));
+        match tables.iter_mut().find(|(p, _)| p.eq(playbook_path)) {
+            Some((_, rows)) => { rows.push(row); },
+            None => { tables.push((playbook_path.clone(), vec![row])); }
+        }
+        return Ok(());
+    })?;
+    for (playbook_path, rows) in tables.iter() {
+        two_column_table(&format!("Playbook: {}", playbook_path.display()), &String::from("Tags"), rows);
+        println!("");
+    }
+    return Ok(());
+}
+
+pub fn walk_playbooks(run_state: &Arc<RunState>, on_play: &mut dyn FnMut(&PathBuf, &Play, &Vec<ListedTask>) -> Result<(), String>) -> Result<(), String> {
+    for playbook_path in run_state.playbook_paths.read().unwrap().iter() {
+        let mut importers : Vec<PathBuf> = Vec::new();
+        walk_playbook(run_state, playbook_path, &mut importers, on_play)?;
+    }
+    return Ok(());
+}
+
+fn walk_playbook(run_state: &Arc<RunState>, playbook_path: &PathBuf, importers: &mut Vec<PathBuf>,
+    on_play: &mut dyn FnMut(&PathBuf, &Play, &Vec<ListedTask>) -> Result<(), String>) -> Result<(), String> {
+
+    if importers.contains(playbook_path) {
+        return Err(format!("playbook import loop detected: {} imports itself", playbook_path.display()));
+    }
+    let entries = load_playbook(run_state, playbook_path)?;
+    let pb_base = playbook_path.parent().unwrap().to_path_buf();
+    for entry in entries.iter() {
+        match entry {
+            PlaybookEntry::Play(play) => {
+                let tasks = collect_play_tasks(run_state, play)?;
+                on_play(playbook_path, play, &tasks)?;
+            },
+            PlaybookEntry::ImportPlaybook(import) => {
+                let imported = get_imported_playbook_path(run_state, &pb_base, &import.import_playbook)?;
+                importers.push(playbook_path.clone());
+                walk_playbook(run_state, &imported, importers, on_play)?;
+                importers.pop();
+            }
+        }
+    }
+    return Ok(());
+}
+
+fn collect_play_tasks(run_state: &Arc<RunState>, play: &Play) -> Result<Vec<ListedTask>, String> {
+
+    // same order as a run: role tasks, loose tasks, then role handlers and loose handlers
+
+    let mut results : Vec<ListedTask> = Vec::new();
+    for are_handlers in [HandlerMode::NormalTasks, HandlerMode::Handlers] {
+        if play.roles.is_some() {
+            for invocation in play.roles.as_ref().unwrap().iter() {
+                collect_role_tasks(run_state, play, invocation, are_handlers, &mut results)?;
+            }
+        }
+        let loose = match are_handlers {
+            HandlerMode::NormalTasks => &play.tasks,
+            HandlerMode::Handlers    => &play.handlers
+        };
+        if loose.is_some() {
+            collect_tasks(play, loose.as_ref().unwrap(), are_handlers, None, &mut results);
+        }
+    }
+    return Ok(results);
+}
+
+fn collect_role_tasks(run_state: &Arc<RunState>, play: &Play, invocation: &RoleInvocation, are_handlers: HandlerMode, results: &mut Vec<ListedTask>) -> Result<(), String> {
+    let (role, role_path) = find_role(run_state, play, invocation.role.clone())?;
+    let files = match are_handlers {
+        HandlerMode::NormalTasks => role.tasks,
+        HandlerMode::Handlers    => role.handlers
+    };
+    if files.is_some() {
+        for task_file in files.unwrap().iter() {
+            let task_buf = get_role_task_path(&role_path, task_file, are_handlers);
+            let tasks = load_tasks_file(&task_buf.as_path())?;
+            collect_tasks(play, &tasks, are_handlers, Some(invocation), results);
+        }
+    }
+    return Ok(());
+}
+
+fn collect_tasks(play: &Play, tasks: &Vec<Task>, are_handlers: HandlerMode, role_invocation: Option<&RoleInvocation>, results: &mut Vec<ListedTask>) {
+    for task in tasks.iter() {
+        match task {
+            Task::Block(block) => {
+                collect_tasks(play, &block.tasks, are_handlers, role_invocation, results);
+                if block.rescue.is_some() { collect_tasks(play, block.rescue.as_ref().unwrap(), are_handlers, role_invocation, results); }
+                if block.always.is_some() { collect_tasks(play, block.always.as_ref().unwrap(), are_handlers, role_invocation, results); }
+            },
+            Task::Flush_Handlers(_) => {},
+            _ => {
+                results.push(ListedTask {
+                    name: task.get_display_name(),
+                    tags: get_task_tags(play, task, role_invocation),
+                    role: role_invocation.map(|x| x.role.clone()),
+                    handler: are_handlers == HandlerMode::Handlers
+                });
+            }
+        }
+    }
+}
@@ -18,6 +18,9 @@ pub mod language;
 pub mod context;
 pub mod visitor;
 pub mod traversal;
+pub mod listing;
 pub mod templar;
 pub mod task_fsm;
 pub mod t_helpers;
+pub mod t_jinja;
+pub mod report;
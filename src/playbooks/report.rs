@@ -0,0 +1,392 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::playbooks::visitor::LogData;
+use crate::tasks::response::{TaskResponse,TaskStatus};
+use crate::util::secrets::{redact_option,NO_LOG_MESSAGE};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::fs::File;
+use std::io::prelude::*;
+use serde_json::json;
+use chrono::prelude::*;
+
+// the run report is the machine readable counterpart to the summary table printed at the
+// end of a playbook run. the visitor feeds it the same events it writes to the JET_LOG
+// file and it is written out once, on exit, either as JSON or as JUnit XML so CI systems
+// can show jetp runs as test results. Paths ending in .xml select JUnit.
+
+#[derive(PartialEq,Copy,Clone,Debug)]
+pub enum ReportFormat {
+    Json,
+    JUnit
+}
+
+pub struct TaskResult {
+    pub play: Option<String>,
+    pub role: Option<String>,
+    pub task: Option<String>,
+    pub task_ct: Option<usize>,
+    pub host: String,
+    pub status: String,
+    pub changes: Vec<String>,
+    pub msg: Option<String>,
+    pub cmd: Option<String>,
+    pub cmd_rc: Option<i32>,
+    pub cmd_out: Option<String>,
+    pub duration: f64,
+    pub failed: bool,
+    pub skipped: bool,
+    pub ignored: bool
+}
+
+pub struct RunReport {
+    pub path: String,
+    pub format: ReportFormat,
+    results: Vec<TaskResult>,
+    // keyed by host and task number, with the 'free' strategy hosts are on different tasks
+    task_starts: HashMap<(String, Option<usize>), DateTime<Utc>>
+}
+
+impl RunReport {
+
+    pub fn new(path: &String) -> Self {
+        Self {
+            path: path.clone(),
+            format: match path.to_lowercase().ends_with(".xml") {
+                true => ReportFormat::JUnit,
+                false => ReportFormat::Json
+            },
+            results: Vec::new(),
+            task_starts: HashMap::new()
+        }
+    }
+
+    // called when a host begins a task, so we can work out how long it took
+
+    pub fn start_host_task(&mut self, host: &String, task_ct: Option<usize>) {
+        self.task_starts.insert((host.clone(), task_ct), Utc::now());
+    }
+
+    // called with the same log entries that go to the JET_LOG file, plus the task response if there is one
+
+    pub fn record(&mut self, log: &LogData, response: Option<&Arc<TaskResponse>>, failed: bool, hidden: bool) {
+        let host = match &log.host {
+            Some(x) => x.clone(),
+            None => { return; }
+        };
+        let duration = match self.task_starts.remove(&(host.clone(), log.task_ct)) {
+            Some(start) => (Utc::now() - start).num_milliseconds() as f64 / 1000.0,
+            None => 0.0
+        };
+        let mut result = TaskResult {
+            play: log.play.clone(),
+            role: log.role.clone(),
+            task: log.task.clone(),
+            task_ct: log.task_ct,
+            host: host.clone(),
+            status: match &log.task_status {
+                Some(x) => x.clone(),
+                None => log.event.clone()
+            },
+            changes: Vec::new(),
+            msg: None,
+            cmd: log.cmd.clone(),
+            cmd_rc: log.cmd_rc,
+            cmd_out: log.cmd_out.clone(),
+            duration: duration,
+            failed: failed,
+            skipped: false,
+            ignored: false
+        };
+        if response.is_some() {
+            let response = response.unwrap();
+            result.changes = response.changes.iter().map(|x| format!("{:?}", x)).collect();
+            result.msg = response.msg.clone();
+            result.skipped = log.task_status.as_ref().map(|x| x.eq("IsSkipped")).unwrap_or(false);
+            // failures under ignore_errors are recorded as passed
+            result.ignored = ! failed && response.status == TaskStatus::Failed;
+            if response.command_result.is_some() {
+                let cmd_result = response.command_result.as_ref().as_ref().unwrap();
+                result.cmd = Some(cmd_result.cmd.clone());
+                result.cmd_rc = Some(cmd_result.rc);
+                result.cmd_out = Some(cmd_result.out.clone());
+            }
+        }
+        // the report ends up in CI artifacts, so it gets the same masking as the console
+        let scrub = |x: &Option<String>| match hidden {
+            true  => x.as_ref().map(|_| String::from(NO_LOG_MESSAGE)),
+            false => redact_option(x)
+        };
+        result.msg = scrub(&result.msg);
+        result.cmd = scrub(&result.cmd);
+        result.cmd_out = scrub(&result.cmd_out);
+        self.results.push(result);
+    }
+
+    pub fn write(&self, run_id: &String, utc_start: &DateTime<Utc>, check_mode: bool,
+        summary: &serde_json::map::Map<String,serde_json::Value>) -> Result<(), String> {
+
+        let contents = match self.format {
+            ReportFormat::Json  => {
+                let doc = self.to_json(run_id, utc_start, check_mode, summary);
+                match serde_json::to_string_pretty(&doc) {
+                    Ok(x) => x,
+                    Err(y) => { return Err(format!("unable to serialize report: {}", y)); }
+                }
+            },
+            ReportFormat::JUnit => self.to_junit(run_id, utc_start)
+        };
+        let mut file = match File::create(&self.path) {
+            Ok(x) => x,
+            Err(y) => { return Err(format!("unable to write report {}: {}", self.path, y)); }
+        };
+        return match file.write_all(contents.as_bytes()) {
+            Ok(_) => Ok(()),
+            Err(y) => Err(format!("unable to write report {}: {}", self.path, y))
+        };
+    }
+
+    fn get_host_names(&self) -> Vec<String> {
+        let mut hosts : Vec<String> = Vec::new();
+        for result in self.results.iter() {
+            if ! hosts.contains(&result.host) {
+                hosts.push(result.host.clone());
+            }
+        }
+        hosts.sort();
+        return hosts;
+    }
+
+    fn to_json(&self, run_id: &String, utc_start: &DateTime<Utc>, check_mode: bool,
+        summary: &serde_json::map::Map<String,serde_json::Value>) -> serde_json::Value {
+
+        let now = Utc::now();
+        let mut hosts = serde_json::map::Map::new();
+        for host in self.get_host_names().iter() {
+            let mut tasks : Vec<serde_json::Value> = Vec::new();
+            let mut host_failed = false;
+            for result in self.results.iter().filter(|x| x.host.eq(host)) {
+                if result.failed {
+                    host_failed = true;
+                }
+                tasks.push(json!({
+                    "play":     result.play,
+                    "role":     result.role,
+                    "task":     result.task,
+                    "task_ct":  result.task_ct,
+                    "status":   result.status,
+                    "failed":   result.failed,
+                    "ignored":  result.ignored,
+                    "changes":  result.changes,
+                    "msg":      result.msg,
+                    "cmd":      result.cmd,
+                    "cmd_rc":   result.cmd_rc,
+                    "cmd_out":  result.cmd_out,
+                    "duration": result.duration
+                }));
+            }
+            hosts.insert(host.clone(), json!({ "failed": host_failed, "tasks": tasks }));
+        }
+
+        return json!({
+            "run":        run_id,
+            "start":      utc_start.to_rfc3339(),
+            "end":        now.to_rfc3339(),
+            "elapsed":    (now - *utc_start).num_milliseconds() as f64 / 1000.0,
+            "check_mode": check_mode,
+            "summary":    summary,
+            "hosts":      hosts
+        });
+    }
+
+    fn to_junit(&self, run_id: &String, utc_start: &DateTime<Utc>) -> String {
+
+        // one test suite per host, one test case per task attempted on that host
+
+        let elapsed = (Utc::now() - *utc_start).num_milliseconds() as f64 / 1000.0;
+        let total_failures = self.results.iter().filter(|x| x.failed).count();
+        let mut buffer = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        buffer.push_str(&format!("<testsuites name=\"jetp\" id=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+            xml_escape(run_id), self.results.len(), total_failures, elapsed));
+
+        for host in self.get_host_names().iter() {
+            let results : Vec<&TaskResult> = self.results.iter().filter(|x| x.host.eq(host)).collect();
+            let failures = results.iter().filter(|x| x.failed).count();
+            let skipped = results.iter().filter(|x| x.skipped).count();
+            let time : f64 = results.iter().map(|x| x.duration).sum();
+            buffer.push_str(&format!("  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\" timestamp=\"{}\">\n",
+                xml_escape(host), results.len(), failures, skipped, time, utc_start.to_rfc3339()));
+
+            for result in results.iter() {
+                let classname = match (&result.play, &result.role) {
+                    (Some(p), Some(r)) => format!("{}.{}", p, r),
+                    (Some(p), None)    => p.clone(),
+                    _                  => String::from("jetp")
+                };
+                let name = match (&result.task_ct, &result.task) {
+                    (Some(ct), Some(t)) => format!("{:04} {}", ct, t),
+                    _                   => result.status.clone()
+                };
+                buffer.push_str(&format!("    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                    xml_escape(&classname), xml_escape(&name), result.duration));
+
+                if result.failed {
+                    let message = match &result.msg {
+                        Some(x) => x.clone(),
+                        None => result.status.clone()
+                    };
+                    buffer.push_str(">\n");
+                    buffer.push_str(&format!("      <failure message=\"{}\" type=\"{}\">", xml_escape(&message), xml_escape(&result.status)));
+                    if result.cmd.is_some() {
+                        buffer.push_str(&xml_escape(&format!("cmd: {}\nout: {}\nrc: {}",
+                            result.cmd.as_ref().unwrap(),
+                            result.cmd_out.as_ref().unwrap_or(&String::new()),
+                            result.cmd_rc.unwrap_or(0))));
+                    }
+                    buffer.push_str("</failure>\n");
+                    buffer.push_str("    </testcase>\n");
+                } else if result.skipped {
+                    buffer.push_str(">\n      <skipped/>\n    </testcase>\n");
+                } else if result.ignored {
+                    buffer.push_str(">\n");
+                    buffer.push_str(&format!("      <system-out>{}", xml_escape(&format!("failed (ignored): {}", result.msg.as_ref().unwrap_or(&result.status)))));
+                    buffer.push_str("</system-out>\n");
+                    buffer.push_str("    </testcase>\n");
+                } else {
+                    buffer.push_str(">\n");
+                    buffer.push_str(&format!("      <system-out>{}", xml_escape(&result.status)));
+                    if ! result.changes.is_empty() {
+                        buffer.push_str(&xml_escape(&format!(" ({})", result.changes.join(","))));
+                    }
+                    buffer.push_str("</system-out>\n");
+                    buffer.push_str("    </testcase>\n");
+                }
+            }
+            buffer.push_str("  </testsuite>\n");
+        }
+        buffer.push_str("</testsuites>\n");
+        return buffer;
+    }
+
+}
+
+fn xml_escape(input: &String) -> String {
+    let mut output = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&'  => output.push_str("&amp;"),
+            '<'  => output.push_str("&lt;"),
+            '>'  => output.push_str("&gt;"),
+            '"'  => output.push_str("&quot;"),
+            '\'' => output.push_str("&apos;"),
+            // control characters are not allowed in XML 1.0 documents
+            c if (c as u32) < 0x20 && c != '\n' && c != '\t' && c != '\r' => {},
+            c => output.push(c)
+        }
+    }
+    return output;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_data(host: &str, task: &str, task_ct: usize, status: &str) -> LogData {
+        return LogData {
+            event: String::from("TASK_STATUS"),
+            play: Some(String::from("web")),
+            playbook_path: None,
+            role: None,
+            task: Some(String::from(task)),
+            task_ct: Some(task_ct),
+            cmd: None,
+            cmd_rc: None,
+            cmd_out: None,
+            task_status: Some(String::from(status)),
+            host: Some(String::from(host)),
+            item: None,
+            changes: None,
+            diff: None,
+            summary: None
+        };
+    }
+
+    fn response(status: TaskStatus, msg: Option<&str>) -> Arc<TaskResponse> {
+        return Arc::new(TaskResponse {
+            status: status,
+            changes: Vec::new(),
+            msg: msg.map(String::from),
+            command_result: Arc::new(None),
+            beforetask: Arc::new(None),
+            aftertask: Arc::new(None)
+        });
+    }
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(xml_escape(&String::from(r#"<a href="x">&'</a>"#)), "&lt;a href=&quot;x&quot;&gt;&amp;&apos;&lt;/a&gt;");
+        assert_eq!(xml_escape(&String::from("tab\tnew\nbell\u{7}")), "tab\tnew\nbell");
+    }
+
+    #[test]
+    fn test_junit_failed_and_skipped() {
+        let mut report = RunReport::new(&String::from("report.XML"));
+        assert_eq!(report.format, ReportFormat::JUnit);
+        report.record(&log_data("db1", "install <pkg>", 1, "Failed"), Some(&response(TaskStatus::Failed, Some("rc & \"out\""))), true, false);
+        report.record(&log_data("db1", "restart", 2, "IsSkipped"), Some(&response(TaskStatus::IsSkipped, None)), false, false);
+        let xml = report.to_junit(&String::from("run1"), &Utc::now());
+        assert!(xml.contains(r#"<testsuite name="db1" tests="2" failures="1" skipped="1""#));
+        assert!(xml.contains(r#"name="0001 install &lt;pkg&gt;""#));
+        assert!(xml.contains(r#"<failure message="rc &amp; &quot;out&quot;" type="Failed">"#));
+        assert!(xml.contains("<skipped/>"));
+    }
+
+    #[test]
+    fn test_ignored_failure_passes() {
+        let mut report = RunReport::new(&String::from("report.xml"));
+        report.record(&log_data("db1", "optional", 1, "Failed"), Some(&response(TaskStatus::Failed, Some("not there"))), false, false);
+        let xml = report.to_junit(&String::from("run1"), &Utc::now());
+        assert!(xml.contains(r#"failures="0""#));
+        assert!(! xml.contains("<failure"));
+        assert!(xml.contains("<system-out>failed (ignored): not there</system-out>"));
+        let doc = report.to_json(&String::from("run1"), &Utc::now(), false, &serde_json::map::Map::new());
+        assert_eq!(doc["hosts"]["db1"]["failed"], json!(false));
+        assert_eq!(doc["hosts"]["db1"]["tasks"][0]["ignored"], json!(true));
+    }
+
+    #[test]
+    fn test_json_report() {
+        let mut report = RunReport::new(&String::from("report.json"));
+        assert_eq!(report.format, ReportFormat::Json);
+        report.record(&log_data("web1", "ok", 1, "IsExecuted"), Some(&response(TaskStatus::IsExecuted, None)), false, false);
+        report.record(&log_data("web2", "broken", 1, "Failed"), Some(&response(TaskStatus::Failed, Some("secret stuff"))), true, true);
+        let doc = report.to_json(&String::from("run1"), &Utc::now(), false, &serde_json::map::Map::new());
+        assert_eq!(doc["hosts"]["web1"]["failed"], json!(false));
+        assert_eq!(doc["hosts"]["web2"]["failed"], json!(true));
+        assert_eq!(doc["hosts"]["web2"]["tasks"][0]["msg"], json!(NO_LOG_MESSAGE));
+    }
+
+    #[test]
+    fn test_durations_are_per_host_and_task() {
+        let mut report = RunReport::new(&String::from("report.json"));
+        report.start_host_task(&String::from("web1"), Some(1));
+        report.start_host_task(&String::from("web1"), Some(2));
+        report.record(&log_data("web1", "first", 1, "IsExecuted"), None, false, false);
+        assert_eq!(report.task_starts.len(), 1);
+        assert!(report.task_starts.contains_key(&(String::from("web1"), Some(2))));
+    }
+}
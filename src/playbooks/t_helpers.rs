@@ -14,6 +14,23 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use handlebars::{Handlebars, RenderError, HelperDef, RenderContext, ScopedJson, JsonValue, Helper, Context, handlebars_helper};
+use crate::util::secrets::add_secret;
+use aes_gcm::aead::OsRng;
+use aes_gcm::aead::rand_core::RngCore;
+use expanduser::expanduser;
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use regex::Regex;
+use sha2::{Digest, Sha256, Sha512};
+use std::os::unix::fs::OpenOptionsExt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+use std::collections::HashMap;
+use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::env;
+use std::fs;
 
 //#[allow(non_camel_case_types)]
 pub struct IsDefined;
@@ -46,6 +63,480 @@ impl HelperDef for IsDefined {
     }
 }
 
+// lookup helpers pull values from the controller (the machine running jetp) rather than from
+// variables. relative paths are resolved from the current directory, which traversal sets to
+// the directory of the playbook, or to the role directory while a role's tasks run.
+//
+//   {{ file "~/.ssh/id_ed25519.pub" }}            contents of a local file, without the trailing newline
+//   {{ env "HOME" }} or {{ env "X" "default" }}   a local environment variable
+//   {{ password "credentials/db" length=24 }}     a random password, created once and kept in that file
+//                                                 (check mode uses a throwaway one and writes nothing)
+//   {{ pipe "git rev-parse HEAD" }}               output of a local command, needs --allow-localhost-delegation
+//   {{#each (lines (file "hosts.txt"))}}          splits text into a list of lines
+
+static ALLOW_PIPE: AtomicBool = AtomicBool::new(false);
+static CREATE_PASSWORDS: AtomicBool = AtomicBool::new(true);
+// passwords handed out without creating their file, so every render in the run agrees
+static PASSWORDS_NOT_CREATED: Lazy<Mutex<HashMap<PathBuf,String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+const PASSWORD_LENGTH: u64 = 20;
+const PASSWORD_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+// the template engine is process wide, so the CLI flag is passed down this way
+
+pub fn allow_pipe_lookups(allow: bool) {
+    ALLOW_PIPE.store(allow, Ordering::Relaxed);
+}
+
+pub fn create_password_files(create: bool) {
+    CREATE_PASSWORDS.store(create, Ordering::Relaxed);
+}
+
+fn get_string_param(h: &Helper, helper_name: &str, index: usize) -> Result<String, RenderError> {
+    return match h.param(index).and_then(|x| x.value().as_str()) {
+        Some(x) => Ok(String::from(x)),
+        None => Err(RenderError::new(format!("{}: parameter {} must be a string", helper_name, index + 1)))
+    };
+}
+
+fn check_param_count(h: &Helper, helper_name: &str, min: usize, max: usize) -> Result<(), RenderError> {
+    let count = h.params().len();
+    if count < min || count > max {
+        return Err(RenderError::new(match min == max {
+            true  => format!("{}: requires {} parameter(s)", helper_name, min),
+            false => format!("{}: requires {} to {} parameters", helper_name, min, max)
+        }));
+    }
+    return Ok(());
+}
+
+fn expand_path(path: &String) -> PathBuf {
+    return match expanduser(path) {
+        Ok(x) => x,
+        Err(_) => PathBuf::from(path)
+    };
+}
+
+pub struct FileLookup;
+
+impl HelperDef for FileLookup {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        check_param_count(h, "file", 1, 1)?;
+        let path = get_string_param(h, "file", 0)?;
+        let contents = match fs::read_to_string(expand_path(&path)) {
+            Ok(x) => x,
+            Err(e) => { return Err(RenderError::new(format!("file: unable to read {}: {}", path, e))); }
+        };
+        Ok(ScopedJson::Derived(JsonValue::from(contents.trim_end_matches(['\r', '\n']))))
+    }
+}
+
+pub struct EnvLookup;
+
+impl HelperDef for EnvLookup {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        check_param_count(h, "env", 1, 2)?;
+        let name = get_string_param(h, "env", 0)?;
+        let value = match env::var(&name) {
+            Ok(x) => x,
+            Err(_) => match h.params().len() {
+                2 => get_string_param(h, "env", 1)?,
+                _ => { return Err(RenderError::new(format!("env: {} is not set", name))); }
+            }
+        };
+        Ok(ScopedJson::Derived(JsonValue::from(value)))
+    }
+}
+
+pub struct PasswordLookup;
+
+impl HelperDef for PasswordLookup {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        check_param_count(h, "password", 1, 1)?;
+        let path = expand_path(&get_string_param(h, "password", 0)?);
+        let length = match h.hash_get("length") {
+            Some(x) => match x.value().as_u64() {
+                Some(n) if n > 0 => n,
+                _ => { return Err(RenderError::new("password: length must be a positive number")); }
+            },
+            None => PASSWORD_LENGTH
+        };
+        let password = lookup_password(&path, length, CREATE_PASSWORDS.load(Ordering::Relaxed))
+            .map_err(|e| RenderError::new(format!("password: {}", e)))?;
+        add_secret(&password);
+        Ok(ScopedJson::Derived(JsonValue::from(password)))
+    }
+}
+
+fn lookup_password(path: &PathBuf, length: u64, create: bool) -> Result<String, String> {
+    // hosts render in parallel, only one of them may create the file
+    let mut not_created = PASSWORDS_NOT_CREATED.lock().unwrap();
+    if path.exists() {
+        return match fs::read_to_string(path) {
+            Ok(x) => Ok(String::from(x.trim_end_matches(['\r', '\n']))),
+            Err(e) => Err(format!("unable to read {}: {}", path.display(), e))
+        };
+    }
+    if ! create {
+        let password = not_created.entry(path.clone()).or_insert_with(|| generate_password(length));
+        return Ok(password.clone());
+    }
+    let password = generate_password(length);
+    write_password_file(path, &password)?;
+    return Ok(password);
+}
+
+fn generate_password(length: u64) -> String {
+    // rejection sampling keeps every character equally likely
+    let limit = u32::MAX - (u32::MAX % PASSWORD_CHARS.len() as u32);
+    let mut password = String::new();
+    while (password.len() as u64) < length {
+        let value = OsRng.next_u32();
+        if value < limit {
+            password.push(PASSWORD_CHARS[(value % PASSWORD_CHARS.len() as u32) as usize] as char);
+        }
+    }
+    return password;
+}
+
+fn write_password_file(path: &PathBuf, password: &String) -> Result<(), String> {
+    if path.parent().is_some() {
+        let parent = path.parent().unwrap();
+        if ! parent.as_os_str().is_empty() && ! parent.exists() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                return Err(format!("unable to create {}: {}", parent.display(), e));
+            }
+        }
+    }
+    let file = fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path);
+    let result = match file {
+        Ok(mut f) => writeln!(f, "{}", password),
+        Err(e) => Err(e)
+    };
+    return match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("unable to write {}: {}", path.display(), e))
+    };
+}
+
+pub struct PipeLookup;
+
+impl HelperDef for PipeLookup {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        check_param_count(h, "pipe", 1, 1)?;
+        if ! ALLOW_PIPE.load(Ordering::Relaxed) {
+            return Err(RenderError::new("pipe: runs commands on the local machine and requires --allow-localhost-delegation"));
+        }
+        let cmd = get_string_param(h, "pipe", 0)?;
+        let output = match Command::new("sh").arg("-c").arg(&cmd).output() {
+            Ok(x) => x,
+            Err(e) => { return Err(RenderError::new(format!("pipe: unable to run {}: {}", cmd, e))); }
+        };
+        if ! output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(RenderError::new(format!("pipe: {} failed ({}): {}", cmd, output.status, stderr.trim())));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(ScopedJson::Derived(JsonValue::from(stdout.trim_end_matches(['\r', '\n']))))
+    }
+}
+
+// data helpers shape variables for rendering config files, for instance:
+//
+//   {{ default port 8080 }}                        the second value if the first is missing, null or ""
+//   {{ join (sort (unique backends)) "," }}        lists: join, split, sort, unique, length
+//   {{#each (split "a:b:c" ":")}}{{this}}{{/each}}
+//   {{ regex_replace name "[^a-z]" "_" }}          also regex_match and plain replace
+//   {{ to_json settings }} {{ to_yaml settings }}  also to_pretty_json, from_json and from_yaml
+//   {{ b64encode text }} {{ sha256 text }}         also b64decode and sha512
+//   {{ add workers 1 }}                            also sub, mul, div and mod
+
+fn json_to_string(value: &JsonValue) -> String {
+    return match value {
+        JsonValue::String(x) => x.clone(),
+        JsonValue::Null => String::new(),
+        _ => value.to_string()
+    };
+}
+
+fn get_param<'a>(h: &'a Helper, helper_name: &str, index: usize) -> Result<&'a JsonValue, RenderError> {
+    return match h.param(index) {
+        Some(x) if ! x.is_value_missing() => Ok(x.value()),
+        _ => Err(RenderError::new(format!("{}: parameter {} is missing", helper_name, index + 1)))
+    };
+}
+
+fn sort_values(values: &[JsonValue]) -> Vec<JsonValue> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| {
+        if a.is_number() && b.is_number() {
+            return a.as_f64().unwrap().partial_cmp(&b.as_f64().unwrap()).unwrap_or(std::cmp::Ordering::Equal);
+        }
+        return json_to_string(a).cmp(&json_to_string(b));
+    });
+    return sorted;
+}
+
+fn unique_values(values: &[JsonValue]) -> Vec<JsonValue> {
+    let mut result: Vec<JsonValue> = Vec::new();
+    for value in values.iter() {
+        if ! result.contains(value) {
+            result.push(value.clone());
+        }
+    }
+    return result;
+}
+
+fn length_of(value: &JsonValue) -> Option<usize> {
+    return match value {
+        JsonValue::Array(x) => Some(x.len()),
+        JsonValue::Object(x) => Some(x.len()),
+        JsonValue::String(x) => Some(x.chars().count()),
+        _ => None
+    };
+}
+
+// unlike the other helpers, default must accept variables that do not exist
+
+pub struct DefaultValue;
+
+impl HelperDef for DefaultValue {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        check_param_count(h, "default", 2, 2)?;
+        let value = h.param(0).unwrap();
+        let use_default = value.is_value_missing() || match value.value() {
+            JsonValue::Null => true,
+            JsonValue::String(x) => x.is_empty(),
+            _ => false
+        };
+        let result = match use_default {
+            true  => get_param(h, "default", 1)?.clone(),
+            false => value.value().clone()
+        };
+        Ok(ScopedJson::Derived(result))
+    }
+}
+
+pub struct RegexHelper {
+    replace: bool
+}
+
+impl HelperDef for RegexHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let name = match self.replace { true => "regex_replace", false => "regex_match" };
+        check_param_count(h, name, if self.replace { 3 } else { 2 }, if self.replace { 3 } else { 2 })?;
+        let text = json_to_string(get_param(h, name, 0)?);
+        let pattern = get_string_param(h, name, 1)?;
+        let re = match Regex::new(&pattern) {
+            Ok(x) => x,
+            Err(e) => { return Err(RenderError::new(format!("{}: invalid regular expression {}: {}", name, pattern, e))); }
+        };
+        let result = match self.replace {
+            true  => JsonValue::from(re.replace_all(&text, get_string_param(h, name, 2)?.as_str()).into_owned()),
+            false => JsonValue::from(re.is_match(&text))
+        };
+        Ok(ScopedJson::Derived(result))
+    }
+}
+
+#[derive(Clone,Copy)]
+pub enum DataFormat {
+    Json,
+    PrettyJson,
+    Yaml
+}
+
+pub struct ToFormat {
+    format: DataFormat
+}
+
+impl HelperDef for ToFormat {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let name = match self.format {
+            DataFormat::Json => "to_json", DataFormat::PrettyJson => "to_pretty_json", DataFormat::Yaml => "to_yaml"
+        };
+        check_param_count(h, name, 1, 1)?;
+        let value = get_param(h, name, 0)?;
+        let result = match self.format {
+            DataFormat::Json       => serde_json::to_string(value).map_err(|e| e.to_string()),
+            DataFormat::PrettyJson => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+            // yaml documents end with a newline that would otherwise end up in the middle of a template
+            DataFormat::Yaml       => serde_yaml::to_string(value).map(|x| String::from(x.trim_end())).map_err(|e| e.to_string())
+        };
+        return match result {
+            Ok(x) => Ok(ScopedJson::Derived(JsonValue::from(x))),
+            Err(e) => Err(RenderError::new(format!("{}: {}", name, e)))
+        };
+    }
+}
+
+pub struct FromFormat {
+    format: DataFormat
+}
+
+impl HelperDef for FromFormat {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let name = match self.format { DataFormat::Yaml => "from_yaml", _ => "from_json" };
+        check_param_count(h, name, 1, 1)?;
+        let text = get_string_param(h, name, 0)?;
+        let result = match self.format {
+            DataFormat::Yaml => serde_yaml::from_str::<JsonValue>(&text).map_err(|e| e.to_string()),
+            _                => serde_json::from_str::<JsonValue>(&text).map_err(|e| e.to_string())
+        };
+        return match result {
+            Ok(x) => Ok(ScopedJson::Derived(x)),
+            Err(e) => Err(RenderError::new(format!("{}: {}", name, e)))
+        };
+    }
+}
+
+pub struct Base64Decode;
+
+impl HelperDef for Base64Decode {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        check_param_count(h, "b64decode", 1, 1)?;
+        let text = get_string_param(h, "b64decode", 0)?;
+        let decoded = match STANDARD.decode(text.trim()) {
+            Ok(x) => x,
+            Err(e) => { return Err(RenderError::new(format!("b64decode: {}", e))); }
+        };
+        return match String::from_utf8(decoded) {
+            Ok(x) => Ok(ScopedJson::Derived(JsonValue::from(x))),
+            Err(_) => Err(RenderError::new("b64decode: the decoded value is not text"))
+        };
+    }
+}
+
+#[derive(Clone,Copy)]
+pub enum MathOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod
+}
+
+pub struct Math {
+    op: MathOp
+}
+
+impl HelperDef for Math {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let name = match self.op {
+            MathOp::Add => "add", MathOp::Sub => "sub", MathOp::Mul => "mul", MathOp::Div => "div", MathOp::Mod => "mod"
+        };
+        check_param_count(h, name, 2, 2)?;
+        let a = get_number_param(h, name, 0)?;
+        let b = get_number_param(h, name, 1)?;
+        let is_zero = b.as_f64().unwrap() == 0.0;
+        if is_zero && matches!(self.op, MathOp::Div | MathOp::Mod) {
+            return Err(RenderError::new(format!("{}: division by zero", name)));
+        }
+
+        // whole numbers stay whole unless division leaves a remainder
+        if a.is_i64() && b.is_i64() {
+            let (x, y) = (a.as_i64().unwrap(), b.as_i64().unwrap());
+            let result = match self.op {
+                MathOp::Add => x.checked_add(y),
+                MathOp::Sub => x.checked_sub(y),
+                MathOp::Mul => x.checked_mul(y),
+                MathOp::Div => match x % y { 0 => x.checked_div(y), _ => None },
+                MathOp::Mod => x.checked_rem(y)
+            };
+            if result.is_some() {
+                return Ok(ScopedJson::Derived(JsonValue::from(result.unwrap())));
+            }
+        }
+        let (x, y) = (a.as_f64().unwrap(), b.as_f64().unwrap());
+        let result = match self.op {
+            MathOp::Add => x + y,
+            MathOp::Sub => x - y,
+            MathOp::Mul => x * y,
+            MathOp::Div => x / y,
+            MathOp::Mod => x % y
+        };
+        Ok(ScopedJson::Derived(JsonValue::from(result)))
+    }
+}
+
+// numbers that arrive as strings, like most inventory values, are accepted too
+
+fn get_number_param(h: &Helper, helper_name: &str, index: usize) -> Result<serde_json::Number, RenderError> {
+    let value = get_param(h, helper_name, index)?;
+    let number = match value {
+        JsonValue::Number(x) => Some(x.clone()),
+        JsonValue::String(x) => match x.trim().parse::<i64>() {
+            Ok(n) => Some(serde_json::Number::from(n)),
+            Err(_) => x.trim().parse::<f64>().ok().and_then(serde_json::Number::from_f64)
+        },
+        _ => None
+    };
+    return match number {
+        Some(x) => Ok(x),
+        None => Err(RenderError::new(format!("{}: parameter {} is not a number: {}", helper_name, index + 1, value)))
+    };
+}
+
 pub fn register_helpers(handlebars: &mut Handlebars) {
     {
         handlebars_helper!(to_lower_case: |v: str| v.to_lowercase());
@@ -82,6 +573,72 @@ pub fn register_helpers(handlebars: &mut Handlebars) {
     {
         handlebars.register_helper("isdefined", Box::new(IsDefined));
     }
+    {
+        handlebars_helper!(lines: |v: str| v.lines().map(String::from).collect::<Vec<String>>());
+        handlebars.register_helper("lines", Box::new(lines))
+    }
+    {
+        handlebars.register_helper("file", Box::new(FileLookup));
+        handlebars.register_helper("env", Box::new(EnvLookup));
+        handlebars.register_helper("password", Box::new(PasswordLookup));
+        handlebars.register_helper("pipe", Box::new(PipeLookup));
+    }
+    {
+        handlebars.register_helper("default", Box::new(DefaultValue));
+    }
+    {
+        handlebars_helper!(join: |v: array, sep: str| v.iter().map(json_to_string).collect::<Vec<String>>().join(sep));
+        handlebars.register_helper("join", Box::new(join))
+    }
+    {
+        handlebars_helper!(split: |v: str, sep: str| v.split(sep).map(String::from).collect::<Vec<String>>());
+        handlebars.register_helper("split", Box::new(split))
+    }
+    {
+        handlebars_helper!(replace: |v: str, from: str, to: str| v.replace(from, to));
+        handlebars.register_helper("replace", Box::new(replace))
+    }
+    {
+        handlebars.register_helper("regex_replace", Box::new(RegexHelper { replace: true }));
+        handlebars.register_helper("regex_match", Box::new(RegexHelper { replace: false }));
+    }
+    {
+        handlebars.register_helper("to_json", Box::new(ToFormat { format: DataFormat::Json }));
+        handlebars.register_helper("to_pretty_json", Box::new(ToFormat { format: DataFormat::PrettyJson }));
+        handlebars.register_helper("to_yaml", Box::new(ToFormat { format: DataFormat::Yaml }));
+        handlebars.register_helper("from_json", Box::new(FromFormat { format: DataFormat::Json }));
+        handlebars.register_helper("from_yaml", Box::new(FromFormat { format: DataFormat::Yaml }));
+    }
+    {
+        handlebars_helper!(b64encode: |v: str| STANDARD.encode(v.as_bytes()));
+        handlebars.register_helper("b64encode", Box::new(b64encode));
+        handlebars.register_helper("b64decode", Box::new(Base64Decode));
+    }
+    {
+        handlebars_helper!(sha256: |v: str| format!("{:x}", Sha256::digest(v.as_bytes())));
+        handlebars.register_helper("sha256", Box::new(sha256));
+        handlebars_helper!(sha512: |v: str| format!("{:x}", Sha512::digest(v.as_bytes())));
+        handlebars.register_helper("sha512", Box::new(sha512));
+    }
+    {
+        handlebars.register_helper("add", Box::new(Math { op: MathOp::Add }));
+        handlebars.register_helper("sub", Box::new(Math { op: MathOp::Sub }));
+        handlebars.register_helper("mul", Box::new(Math { op: MathOp::Mul }));
+        handlebars.register_helper("div", Box::new(Math { op: MathOp::Div }));
+        handlebars.register_helper("mod", Box::new(Math { op: MathOp::Mod }));
+    }
+    {
+        handlebars_helper!(sort: |v: array| sort_values(v));
+        handlebars.register_helper("sort", Box::new(sort))
+    }
+    {
+        handlebars_helper!(unique: |v: array| unique_values(v));
+        handlebars.register_helper("unique", Box::new(unique))
+    }
+    {
+        handlebars_helper!(length: |v: Json| length_of(v));
+        handlebars.register_helper("length", Box::new(length))
+    }
 }
 
 #[cfg(test)]
@@ -91,6 +648,7 @@ mod tests {
     use serde_json::json;
 
     use handlebars::{no_escape, Handlebars};
+    use std::os::unix::fs::PermissionsExt;
 
     pub fn new_handlebars<'reg>() -> Handlebars<'reg> {
         let mut handlebars = Handlebars::new();
@@ -220,4 +778,170 @@ mod tests {
         assert_eq!(result.unwrap(), "true false a ");
         Ok(())
     }
+
+    fn temp_path(name: &str) -> PathBuf {
+        return env::temp_dir().join(format!("jet-lookup-{}-{}", name, guid_create::GUID::rand()));
+    }
+
+    #[test]
+    fn test_helper_file() -> Result<(), Box<dyn Error>> {
+        let path = temp_path("file");
+        fs::write(&path, "ssh-ed25519 AAAA user@host\n")?;
+        let handlebars = new_handlebars();
+        let result = handlebars.render_template(r#"{{ file path }}"#, &json!({"path": path.display().to_string()}));
+        fs::remove_file(&path)?;
+        assert_eq!(result.unwrap(), "ssh-ed25519 AAAA user@host");
+        assert!(handlebars.render_template(r#"{{ file path }}"#, &json!({"path": path.display().to_string()})).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_helper_env() -> Result<(), Box<dyn Error>> {
+        // tests run in parallel threads, so this only reads variables and never sets them
+        let handlebars = new_handlebars();
+        assert_eq!(handlebars.render_template(r#"{{ env "PATH" }}"#, &json!({}))?, env::var("PATH")?);
+        assert_eq!(handlebars.render_template(r#"{{ env "PATH" "ipsum" }}"#, &json!({}))?, env::var("PATH")?);
+        assert!(handlebars.render_template(r#"{{ env "JET_TEST_LOOKUP_UNSET" }}"#, &json!({})).is_err());
+        assert_renders![
+            (r##"{{ env "JET_TEST_LOOKUP_UNSET" "ipsum" }}"##, r##"ipsum"##)
+        ]
+    }
+
+    #[test]
+    fn test_helper_lines() -> Result<(), Box<dyn Error>> {
+        assert_renders![
+            (r##"{{#each (lines "a\nb\nc") }}[{{this}}]{{/each}}"##, r##"[a][b][c]"##)
+        ]
+    }
+
+    #[test]
+    fn test_helper_password() -> Result<(), Box<dyn Error>> {
+        let dir = temp_path("password");
+        let path = dir.join("credentials").join("db");
+        let data = json!({"path": path.display().to_string()});
+        let handlebars = new_handlebars();
+        let first = handlebars.render_template(r#"{{ password path length=32 }}"#, &data).unwrap();
+        let second = handlebars.render_template(r#"{{ password path }}"#, &data).unwrap();
+        let mode = fs::metadata(&path)?.permissions().mode();
+        fs::remove_dir_all(&dir)?;
+        assert_eq!(first.len(), 32);
+        assert!(first.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(first, second);
+        assert_eq!(mode & 0o777, 0o600);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lookup_password_without_create() -> Result<(), Box<dyn Error>> {
+        let dir = temp_path("password-check");
+        let path = dir.join("db");
+        let first = lookup_password(&path, 16, false)?;
+        assert_eq!(first.len(), 16);
+        assert_eq!(lookup_password(&path, 16, false)?, first);
+        assert!(! dir.exists());
+        // an existing file is still read
+        fs::create_dir_all(&dir)?;
+        fs::write(&path, "kept\n")?;
+        let second = lookup_password(&path, 16, false)?;
+        fs::remove_dir_all(&dir)?;
+        assert_eq!(second, "kept");
+        Ok(())
+    }
+
+    #[test]
+    fn test_helper_pipe_requires_delegation() -> Result<(), Box<dyn Error>> {
+        let handlebars = new_handlebars();
+        let result = handlebars.render_template(r#"{{ pipe "echo hello" }}"#, &json!({}));
+        assert!(result.unwrap_err().to_string().contains("--allow-localhost-delegation"));
+        Ok(())
+    }
+
+    fn render_with(template: &str, data: &serde_json::Value) -> Result<String, handlebars::RenderError> {
+        return new_handlebars().render_template(template, data);
+    }
+
+    #[test]
+    fn test_helper_default() -> Result<(), Box<dyn Error>> {
+        let data = json!({"port": 80, "empty": "", "nothing": null});
+        assert_eq!(render_with(r#"{{ default port 8080 }}"#, &data)?, "80");
+        assert_eq!(render_with(r#"{{ default missing 8080 }}"#, &data)?, "8080");
+        assert_eq!(render_with(r#"{{ default empty "x" }}"#, &data)?, "x");
+        assert_eq!(render_with(r#"{{ default nothing "x" }}"#, &data)?, "x");
+        assert_eq!(render_with(r#"{{ default missing.port 1 }}"#, &data)?, "1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_helper_join_split_replace() -> Result<(), Box<dyn Error>> {
+        let data = json!({"hosts": ["a", "b", 3]});
+        assert_eq!(render_with(r#"{{ join hosts "," }}"#, &data)?, "a,b,3");
+        assert_renders![
+            (r##"{{#each (split "a:b:c" ":") }}[{{this}}]{{/each}}"##, r##"[a][b][c]"##),
+            (r##"{{ join (split "a b c" " ") "-" }}"##, r##"a-b-c"##),
+            (r##"{{ replace "foo.bar.baz" "." "_" }}"##, r##"foo_bar_baz"##)
+        ]
+    }
+
+    #[test]
+    fn test_helper_regex() -> Result<(), Box<dyn Error>> {
+        test_condition(r#"( regex_match "web-01" "^web-[0-9]+$" )"#, true);
+        test_condition(r#"( regex_match "db-01" "^web-" )"#, false);
+        assert!(render_with(r#"{{ regex_replace "x" "(" "y" }}"#, &json!({})).is_err());
+        assert_renders![
+            (r##"{{ regex_replace "web-01.example.com" "[.]example[.]com$" "" }}"##, r##"web-01"##),
+            (r##"{{ regex_replace "a1b22c" "([0-9]+)" "<$1>" }}"##, r##"a<1>b<22>c"##)
+        ]
+    }
+
+    #[test]
+    fn test_helper_serialization() -> Result<(), Box<dyn Error>> {
+        let data = json!({"settings": {"a": 1, "b": ["x", "y"]}});
+        assert_eq!(render_with(r#"{{ to_json settings }}"#, &data)?, r#"{"a":1,"b":["x","y"]}"#);
+        assert_eq!(render_with(r#"{{ to_pretty_json settings.b }}"#, &data)?, "[\n  \"x\",\n  \"y\"\n]");
+        assert_eq!(render_with(r#"{{ to_yaml settings }}"#, &data)?, "a: 1\nb:\n- x\n- y");
+        assert_eq!(render_with(r#"{{ lookup (from_json "{\"a\": 5}") "a" }}"#, &data)?, "5");
+        assert_eq!(render_with(r#"{{ join (from_yaml "[1, 2]") "+" }}"#, &data)?, "1+2");
+        assert!(render_with(r#"{{ from_json "{" }}"#, &data).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_helper_encoding_and_hashing() -> Result<(), Box<dyn Error>> {
+        assert!(render_with(r#"{{ b64decode "!!" }}"#, &json!({})).is_err());
+        assert_renders![
+            (r##"{{ b64encode "hello" }}"##, r##"aGVsbG8="##),
+            (r##"{{ b64decode "aGVsbG8=" }}"##, r##"hello"##),
+            (r##"{{ sha256 "hello" }}"##, r##"2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"##),
+            (r##"{{ sha512 "" }}"##, r##"cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e"##)
+        ]
+    }
+
+    #[test]
+    fn test_helper_math() -> Result<(), Box<dyn Error>> {
+        let data = json!({"workers": "4"});
+        assert_eq!(render_with(r#"{{ add workers 1 }}"#, &data)?, "5");
+        assert!(render_with(r#"{{ div 1 0 }}"#, &data).is_err());
+        assert!(render_with(r#"{{ add "x" 1 }}"#, &data).is_err());
+        assert_renders![
+            (r##"{{ add 2 3 }}"##, r##"5"##),
+            (r##"{{ sub 2 3 }}"##, r##"-1"##),
+            (r##"{{ mul 2 1.5 }}"##, r##"3.0"##),
+            (r##"{{ div 9 3 }}"##, r##"3"##),
+            (r##"{{ div 7 2 }}"##, r##"3.5"##),
+            (r##"{{ mod 7 3 }}"##, r##"1"##)
+        ]
+    }
+
+    #[test]
+    fn test_helper_lists() -> Result<(), Box<dyn Error>> {
+        let data = json!({"backends": ["web2", "web1", "web2", "web3"], "ports": [443, 80, 8080], "map": {"a": 1}});
+        assert_eq!(render_with(r#"{{ join (sort (unique backends)) "," }}"#, &data)?, "web1,web2,web3");
+        assert_eq!(render_with(r#"{{ join (sort ports) "," }}"#, &data)?, "80,443,8080");
+        assert_eq!(render_with(r#"{{ length backends }} {{ length map }} {{ length "abc" }}"#, &data)?, "4 1 3");
+        assert_eq!(
+            render_with(r#"{{#each (unique backends) }}server {{this}};{{/each}}"#, &data)?,
+            "server web2;server web1;server web3;"
+        );
+        Ok(())
+    }
 }
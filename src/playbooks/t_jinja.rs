@@ -0,0 +1,152 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use minijinja::{Environment,Error,ErrorKind,Value};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use regex::Regex;
+
+// filters for the jinja engine (see templar.rs). minijinja already provides most of the
+// jinja2 builtins such as default, join, length, sort, unique and tojson. these add the
+// common ansible filters so templates ported from ansible roles render unchanged.
+//
+//   {{ settings | to_nice_json }}   also to_json, to_yaml, from_json and from_yaml
+//   {{ text | b64encode }}          also b64decode
+//   {{ name | regex_replace('[^a-z]', '_') }}
+//   {% if enabled | bool %}
+
+// ==============================================================================================================
+// PUBLIC API
+// ==============================================================================================================
+
+pub fn register_filters(env: &mut Environment) {
+    env.add_filter("to_json", to_json);
+    env.add_filter("to_nice_json", to_nice_json);
+    env.add_filter("to_yaml", to_yaml);
+    env.add_filter("from_json", from_json);
+    env.add_filter("from_yaml", from_yaml);
+    env.add_filter("b64encode", b64encode);
+    env.add_filter("b64decode", b64decode);
+    env.add_filter("regex_replace", regex_replace);
+    env.add_filter("bool", to_bool);
+}
+
+// ==============================================================================================================
+// PRIVATE
+// ==============================================================================================================
+
+fn filter_error(filter: &str, msg: String) -> Error {
+    return Error::new(ErrorKind::InvalidOperation, format!("{}: {}", filter, msg));
+}
+
+fn to_json(value: Value) -> Result<String, Error> {
+    return serde_json::to_string(&value).map_err(|e| filter_error("to_json", e.to_string()));
+}
+
+fn to_nice_json(value: Value) -> Result<String, Error> {
+    return serde_json::to_string_pretty(&value).map_err(|e| filter_error("to_nice_json", e.to_string()));
+}
+
+fn to_yaml(value: Value) -> Result<String, Error> {
+    return serde_yaml::to_string(&value).map_err(|e| filter_error("to_yaml", e.to_string()));
+}
+
+fn from_json(text: String) -> Result<Value, Error> {
+    return match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(x) => Ok(Value::from_serialize(&x)),
+        Err(e) => Err(filter_error("from_json", e.to_string()))
+    };
+}
+
+fn from_yaml(text: String) -> Result<Value, Error> {
+    return match serde_yaml::from_str::<serde_yaml::Value>(&text) {
+        Ok(x) => Ok(Value::from_serialize(&x)),
+        Err(e) => Err(filter_error("from_yaml", e.to_string()))
+    };
+}
+
+fn b64encode(text: String) -> String {
+    return STANDARD.encode(text.as_bytes());
+}
+
+fn b64decode(text: String) -> Result<String, Error> {
+    let decoded = match STANDARD.decode(text.trim()) {
+        Ok(x) => x,
+        Err(e) => { return Err(filter_error("b64decode", e.to_string())); }
+    };
+    return String::from_utf8(decoded).map_err(|_| filter_error("b64decode", String::from("the decoded value is not text")));
+}
+
+// ansible writes backreferences as \1, the regex crate wants $1
+
+fn regex_replace(text: String, pattern: String, replacement: Option<String>) -> Result<String, Error> {
+    let re = match Regex::new(&pattern) {
+        Ok(x) => x,
+        Err(e) => { return Err(filter_error("regex_replace", format!("invalid regular expression {}: {}", pattern, e))); }
+    };
+    let backrefs = Regex::new(r"\\(\d+)").unwrap();
+    let replacement = backrefs.replace_all(&replacement.unwrap_or_default(), "$${$1}").into_owned();
+    return Ok(re.replace_all(&text, replacement.as_str()).into_owned());
+}
+
+fn to_bool(value: Value) -> bool {
+    if value.as_str().is_some() {
+        return matches!(value.as_str().unwrap().trim().to_lowercase().as_str(), "true" | "yes" | "on" | "1");
+    }
+    return value.is_true();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::playbooks::templar::{Templar,TemplateMode,TemplateEngine};
+    use std::path::Path;
+
+    fn render(template: &str, vars: &str) -> Result<String, String> {
+        let data: serde_yaml::Mapping = serde_yaml::from_str(vars).unwrap();
+        return Templar::new().render_with_engine(&String::from(template), data, TemplateMode::Strict, TemplateEngine::Jinja);
+    }
+
+    #[test]
+    fn test_jinja_variables_and_blocks() {
+        let template = "{% for b in backends | sort %}\nserver {{ b }};\n{% endfor %}\n";
+        assert_eq!(render(template, "backends: [web2, web1]").unwrap(), "server web1;\nserver web2;\n");
+        assert_eq!(render("{{ port | default(8080) }}", "{}").unwrap(), "8080");
+        assert_eq!(render("{% if x is defined %}a{% else %}b{% endif %}", "{}").unwrap(), "b");
+    }
+
+    #[test]
+    fn test_jinja_is_strict() {
+        assert!(render("{{ missing }}", "{}").is_err());
+        assert!(render("{% for x in missing %}{% endfor %}", "{}").is_err());
+    }
+
+    #[test]
+    fn test_jinja_filters() {
+        assert_eq!(render("{{ s | to_json }}", "s: {a: 1}").unwrap(), r#"{"a":1}"#);
+        assert_eq!(render("{{ s | to_yaml }}", "s: {a: 1}").unwrap(), "a: 1\n");
+        assert_eq!(render("{{ ('{\"a\": 5}' | from_json).a }}", "{}").unwrap(), "5");
+        assert_eq!(render("{{ 'hello' | b64encode }} {{ 'aGVsbG8=' | b64decode }}", "{}").unwrap(), "aGVsbG8= hello");
+        assert_eq!(render(r"{{ 'web-01.example.com' | regex_replace('^([a-z]+)-(\\d+).*$', '\\2-\\1') }}", "{}").unwrap(), "01-web");
+        assert_eq!(render("{{ 'yes' | bool }} {{ 'no' | bool }}", "{}").unwrap(), "True False");
+    }
+
+    #[test]
+    fn test_select_engine() {
+        assert_eq!(TemplateEngine::select(&None, Path::new("nginx.conf.j2")).unwrap(), TemplateEngine::Jinja);
+        assert_eq!(TemplateEngine::select(&None, Path::new("nginx.conf.hb")).unwrap(), TemplateEngine::Handlebars);
+        assert_eq!(TemplateEngine::select(&Some(String::from("handlebars")), Path::new("a.j2")).unwrap(), TemplateEngine::Handlebars);
+        assert!(TemplateEngine::select(&Some(String::from("mustache")), Path::new("a")).is_err());
+    }
+}
@@ -20,14 +20,16 @@ use crate::handle::handle::TaskHandle;
 use crate::playbooks::traversal::RunState;
 use crate::inventory::hosts::Host;
 use crate::playbooks::traversal::HandlerMode;
-use crate::playbooks::language::Play;
+use crate::playbooks::language::{Play,Block,Include,RoleInvocation};
+use crate::playbooks::traversal::{check_tags,load_tasks_file,contains_flush_handlers,Strategy,MAX_INCLUDE_DEPTH};
 use crate::tasks::request::SudoDetails;
 use crate::tasks::*;
 use crate::handle::template::BlendTarget;
 use crate::playbooks::templar::TemplateMode;
-use crate::tasks::logic::template_items;
+use crate::tasks::logic::{template_items,get_loop_var,set_loop_item,get_item_label};
 use std::sync::{Arc,RwLock,Mutex};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use rayon::prelude::*;
 use std::{thread, time};
 
@@ -39,55 +41,333 @@ use std::{thread, time};
 
 pub fn fsm_run_task(run_state: &Arc<RunState>, play: &Play, task: &Task, are_handlers: HandlerMode) -> Result<(), String> {
 
-    // if running in check mode various functions will short circuit early
-    let check =  run_state.visitor.read().unwrap().is_check_mode();
-
     // the hosts to configure are not those specified in the batch but the subset of those that have not yet failed
     let hosts : HashMap<String, Arc<RwLock<Host>>> = run_state.context.read().unwrap().get_remaining_hosts();
     let mut host_objects : Vec<Arc<RwLock<Host>>> = Vec::new();
     for (_,v) in hosts { host_objects.push(Arc::clone(&v)); }
 
+    // inside a block with rescue or always tasks, failed hosts are set aside rather than failed
+    let rescuable = run_state.context.read().unwrap().in_block();
+
     // use rayon to process hosts in different threads
     let _total : i64 = host_objects.par_iter().map(|host| {
+        run_task_and_report(run_state, play, task, are_handlers, host, rescuable);
+        // rayon needs some math to add up, hence the 1. It seems to short-circuit without some work to do.
+        return 1;
+    }).sum();
+    return fsm_check_failure_limits(run_state, play);
+}
 
-        // get the connection to each host, which should be left open until the play ends
-        let connection_result = run_state.connection_factory.read().unwrap().get_connection(&run_state.context, &host);
-        match connection_result {
-            Ok(_)  => {
-                let connection = connection_result.unwrap();
-                run_state.visitor.read().unwrap().on_host_task_start(&run_state.context, &host);
-                // the actual task is invoked here
-                let task_response = run_task_on_host(&run_state,connection,&host,play,task,are_handlers);
-
-                match task_response {
-                    Ok(x) => {
-                        match check {
-                            // output slightly differs in check vs non-check modes
-                            false => run_state.visitor.read().unwrap().on_host_task_ok(&run_state.context, &x, &host),
-                            true => run_state.visitor.read().unwrap().on_host_task_check_ok(&run_state.context, &x, &host)
-                        }
+pub fn fsm_run_tasks_free(run_state: &Arc<RunState>, play: &Play, tasks: &[Task], are_handlers: HandlerMode, role_invocation: Option<&RoleInvocation>) -> Result<(), String> {
+
+    // with the 'free' strategy each host walks the whole list of tasks on its own thread instead
+    // of waiting for every other host to finish the current task.  task numbers are reserved
+    // up front so each host can report which task it is on.
+
+    let first_task = {
+        let mut ctx = run_state.context.write().unwrap();
+        let first = ctx.get_task_count() + 1;
+        for _ in 0..count_tasks(tasks) { ctx.increment_task_count(); }
+        first
+    };
+
+    let hosts : HashMap<String, Arc<RwLock<Host>>> = run_state.context.read().unwrap().get_remaining_hosts();
+    let mut host_objects : Vec<Arc<RwLock<Host>>> = Vec::new();
+    for (_,v) in hosts { host_objects.push(Arc::clone(&v)); }
+
+    let _total : i64 = host_objects.par_iter().map(|host| {
+        let mut task_ct = first_task;
+        // a failed host stops walking, the others carry on
+        run_tasks_on_host_free(run_state, play, tasks, are_handlers, role_invocation, host, &mut task_ct, false, 0);
+        return 1;
+    }).sum();
+
+    run_state.context.write().unwrap().clear_host_tasks();
+    return fsm_check_failure_limits(run_state, play);
+}
+
+fn count_tasks(tasks: &[Task]) -> usize {
+    let mut count : usize = 0;
+    for task in tasks.iter() {
+        count = count + match task {
+            Task::Block(block) => {
+                count_tasks(&block.tasks)
+                + match &block.rescue { Some(x) => count_tasks(x), None => 0 }
+                + match &block.always { Some(x) => count_tasks(x), None => 0 }
+            },
+            Task::Flush_Handlers(_) => 0,
+            _ => 1
+        };
+    }
+    return count;
+}
+
+fn run_tasks_on_host_free(run_state: &Arc<RunState>, play: &Play, tasks: &[Task], are_handlers: HandlerMode, role_invocation: Option<&RoleInvocation>,
+    host: &Arc<RwLock<Host>>, task_ct: &mut usize, rescuable: bool, depth: usize) -> bool {
+
+    // returns false as soon as the host fails (or the batch is aborted), rescuable failures
+    // leave the host in the pool so the enclosing block can decide what to do with it.
+    // depth is how many includes deep the host is.
+
+    let host_name = host.read().unwrap().name.clone();
+    for task in tasks.iter() {
+        // another host may have pushed the batch over its failure limit
+        if fsm_check_failure_limits(run_state, play).is_err() {
+            return false;
+        }
+        match task {
+            Task::Block(block) => {
+                if ! run_block_on_host_free(run_state, play, block, are_handlers, role_invocation, host, task_ct, rescuable, depth) {
+                    return false;
+                }
+            },
+            Task::Include(include) => {
+                *task_ct = *task_ct + 1;
+                if ! check_tags(run_state, play, task, role_invocation) {
+                    continue;
+                }
+                let files = match fsm_resolve_include(run_state, host, include, are_handlers, rescuable, Strategy::Free, depth) {
+                    Some(x) => x,
+                    None => { return false; }
+                };
+                for (_, item, included) in files.iter() {
+                    set_loop_item(host, &include.get_loop_var(), item);
+                    // included tasks were not known when task numbers were handed out
+                    let mut included_ct = {
+                        let mut ctx = run_state.context.write().unwrap();
+                        let first = ctx.get_task_count() + 1;
+                        for _ in 0..count_tasks(included) { ctx.increment_task_count(); }
+                        first
+                    };
+                    if ! run_tasks_on_host_free(run_state, play, included, are_handlers, role_invocation, host, &mut included_ct, rescuable, depth + 1) {
+                        return false;
                     }
-                    Err(x) => {
-                        // hosts with task failures are removed from the pool
-                        run_state.context.write().unwrap().fail_host(&host);
-                        run_state.visitor.read().unwrap().on_host_task_failed(&run_state.context, &x, &host);
-                    },
                 }
             },
-            Err(x) => {
-                // hosts with connection failures are removed from the pool
-                run_state.visitor.read().unwrap().debug_host(&host, &x);
-                run_state.context.write().unwrap().fail_host(&host);
-                run_state.visitor.read().unwrap().on_host_connect_failed(&run_state.context, &host);
+            Task::Flush_Handlers(_) => {
+                // hosts can only be brought together to run handlers between top level tasks, process_tasks
+                // and fsm_resolve_include reject flush_handlers anywhere else
+                panic!("flush_handlers inside a block or include should have been rejected with the free strategy");
+            },
+            _ => {
+                let this_task = *task_ct;
+                *task_ct = *task_ct + 1;
+                if ! check_tags(run_state, play, task, role_invocation) {
+                    continue;
+                }
+                run_state.context.write().unwrap().set_host_task(&host_name, this_task, task);
+                run_state.visitor.read().unwrap().on_host_task_begin(&run_state.context, host, are_handlers);
+                if ! run_task_and_report(run_state, play, task, are_handlers, host, rescuable) {
+                    return false;
+                }
             }
         }
-        // rayon needs some math to add up, hence the 1. It seems to short-circuit without some work to do.
-        return 1;
+    }
+    return true;
+}
 
-    }).sum();
+fn run_block_on_host_free(run_state: &Arc<RunState>, play: &Play, block: &Block, are_handlers: HandlerMode, role_invocation: Option<&RoleInvocation>,
+    host: &Arc<RwLock<Host>>, task_ct: &mut usize, rescuable: bool, depth: usize) -> bool {
+
+    // the per-host equivalent of process_block in traversal.rs
+
+    let has_rescue = block.rescue.is_some();
+    let has_always = block.always.is_some();
+    let block_name = block.get_display_name();
+
+    let mut ok = run_tasks_on_host_free(run_state, play, &block.tasks, are_handlers, role_invocation, host, task_ct, rescuable || has_rescue || has_always, depth);
+    if has_rescue {
+        let rescue = block.rescue.as_ref().unwrap();
+        if ok {
+            *task_ct = *task_ct + count_tasks(rescue);
+        } else {
+            run_state.visitor.read().unwrap().on_host_block_section(&run_state.context, host, &block_name, &String::from("rescue"));
+            ok = run_tasks_on_host_free(run_state, play, rescue, are_handlers, role_invocation, host, task_ct, rescuable || has_always, depth);
+            if ok {
+                run_state.context.write().unwrap().rescue_host(host);
+                run_state.visitor.read().unwrap().on_host_rescued(&run_state.context, host);
+            }
+        }
+    }
+    if has_always {
+        run_state.visitor.read().unwrap().on_host_block_section(&run_state.context, host, &block_name, &String::from("always"));
+        if ! run_tasks_on_host_free(run_state, play, block.always.as_ref().unwrap(), are_handlers, role_invocation, host, task_ct, rescuable, depth) {
+            return false;
+        }
+    }
+    if ! ok && ! rescuable {
+        run_state.context.write().unwrap().fail_host(&host);
+        run_state.visitor.read().unwrap().on_host_block_failed(&run_state.context, host);
+    }
+    return ok;
+}
+
+fn report_task_failure(run_state: &Arc<RunState>, host: &Arc<RwLock<Host>>, response: &Arc<TaskResponse>, rescuable: bool) {
+    if rescuable {
+        run_state.context.write().unwrap().set_aside_host(&host);
+        run_state.visitor.read().unwrap().on_host_task_rescuable(&run_state.context, &response, &host);
+    } else {
+        // hosts with task failures are removed from the pool
+        run_state.context.write().unwrap().fail_host(&host);
+        run_state.visitor.read().unwrap().on_host_task_failed(&run_state.context, &response, &host);
+    }
+}
+
+pub fn fsm_resolve_include(run_state: &Arc<RunState>, host: &Arc<RwLock<Host>>, include: &Include, are_handlers: HandlerMode, rescuable: bool, strategy: Strategy, depth: usize) 
+    -> Option<Vec<(PathBuf, serde_yaml::Value, Vec<Task>)>> {
+
+    // works out which task files a host should include, and the value of 'item' for each. an empty
+    // list means the checkcondition was false. None means the host failed and has been reported.
+    // a missing or unparseable file, or includes nested too deeply, fail the host rather than the whole play.
+
+    let connection = match run_state.connection_factory.read().unwrap().get_connection(&run_state.context, &host) {
+        Ok(x) => x,
+        Err(x) => {
+            run_state.visitor.read().unwrap().debug_host(&host, &x);
+            run_state.context.write().unwrap().fail_host(&host);
+            run_state.visitor.read().unwrap().on_host_connect_failed(&run_state.context, &host);
+            return None;
+        }
+    };
+    let handle = Arc::new(TaskHandle::new(Arc::clone(run_state), connection, Arc::clone(host)));
+    let validate = TaskRequest::validate();
+    if depth >= MAX_INCLUDE_DEPTH {
+        let response = handle.response.is_failed(&validate, &format!("include: {}: nested too deeply, is a file including itself?", include.file));
+        report_task_failure(run_state, host, &response, rescuable);
+        return None;
+    }
+    return match resolve_include_files(run_state, &handle, &validate, host, include, are_handlers, strategy) {
+        Ok(x) => {
+            for (path, _, _) in x.iter() {
+                run_state.visitor.read().unwrap().on_host_include(&run_state.context, host, path);
+            }
+            Some(x)
+        },
+        Err(x) => {
+            report_task_failure(run_state, host, &x, rescuable);
+            None
+        }
+    };
+}
+
+fn resolve_include_files(run_state: &Arc<RunState>, handle: &Arc<TaskHandle>, validate: &Arc<TaskRequest>, host: &Arc<RwLock<Host>>, include: &Include, are_handlers: HandlerMode, strategy: Strategy)
+    -> Result<Vec<(PathBuf, serde_yaml::Value, Vec<Task>)>, Arc<TaskResponse>> {
+
+    let mut results : Vec<(PathBuf, serde_yaml::Value, Vec<Task>)> = Vec::new();
+    let (items_input, nested_input) = match &include.beforetask {
+        Some(logic) => {
+            if logic.checkcondition.is_some() {
+                let cond = handle.template.test_condition(&validate, TemplateMode::Strict, &logic.checkcondition.as_ref().unwrap())?;
+                if ! cond {
+                    return Ok(results);
+                }
+            }
+            (logic.items.clone(), logic.nested.clone())
+        },
+        None => (None, None)
+    };
+    let loop_var = include.get_loop_var();
+
+    // relative paths are from the playbook directory, or inside a role, from the role's tasks (or handlers) directory
+    let role_path = run_state.context.read().unwrap().role_path.clone();
+    let evaluated_items = template_items(&handle, &validate, TemplateMode::Strict, &items_input, &nested_input)?;
+    for item in evaluated_items.iter() {
+        set_loop_item(host, &loop_var, item);
+        let file = handle.template.path(&validate, TemplateMode::Strict, &String::from("file"), &include.file)?;
+        let mut path = PathBuf::new();
+        if role_path.is_some() {
+            path.push(role_path.as_ref().unwrap());
+            match are_handlers {
+                HandlerMode::NormalTasks => { path.push("tasks"); },
+                HandlerMode::Handlers    => { path.push("handlers"); },
+            };
+        }
+        path.push(&file);
+        if ! path.is_file() {
+            return Err(handle.response.is_failed(&validate, &format!("include: no such file: {}", path.display())));
+        }
+        let tasks = match load_tasks_file(&path) {
+            Ok(x) => x,
+            Err(y) => { return Err(handle.response.is_failed(&validate, &format!("include: {}", y))); }
+        };
+        // with the free strategy each host walks the included tasks on its own, so there is no point where all hosts can run handlers
+        if strategy == Strategy::Free && contains_flush_handlers(&tasks) {
+            return Err(handle.response.is_failed(&validate, &format!("include: {}: flush_handlers cannot be used in an included file with the free strategy", path.display())));
+        }
+        results.push((path, item.clone(), tasks));
+    }
+    return Ok(results);
+}
+
+pub fn fsm_check_failure_limits(run_state: &Arc<RunState>, play: &Play) -> Result<(), String> {
+
+    // failed hosts normally just drop out of the play, but for rolling updates it is safer
+    // to stop everything, including later batches, once too many hosts in a batch have failed
+
+    let ctx = run_state.context.read().unwrap();
+    let failed_count = ctx.get_batch_failed_count();
+    if failed_count == 0 {
+        return Ok(());
+    }
+    if play.any_errors_fatal.is_some() && play.any_errors_fatal.unwrap() {
+        return Err(format!("play aborted: any_errors_fatal is set and {} host(s) failed", failed_count));
+    }
+    if play.max_fail_percentage.is_some() {
+        let max_pct = play.max_fail_percentage.unwrap();
+        let pct = ctx.get_batch_failed_percentage();
+        if pct > max_pct as f64 {
+            return Err(format!("play aborted: {:.0}% of hosts in the batch failed, max_fail_percentage is {}%", pct, max_pct));
+        }
+    }
     return Ok(());
 }
 
+fn run_task_and_report(run_state: &Arc<RunState>, play: &Play, task: &Task, are_handlers: HandlerMode, host: &Arc<RwLock<Host>>, rescuable: bool) -> bool {
+
+    // runs one task on one host and tells the visitor how it went. returns false if the host
+    // has failed and should be removed from the pool, or, if rescuable, handed to the enclosing block.
+
+    // if running in check mode various functions will short circuit early
+    let check =  run_state.visitor.read().unwrap().is_check_mode();
+
+    // get the connection to each host, which should be left open until the play ends
+    let connection_result = run_state.connection_factory.read().unwrap().get_connection(&run_state.context, &host);
+    match connection_result {
+        Ok(_)  => {
+            let connection = connection_result.unwrap();
+            let no_log = task.get_with().map(|x| x.no_log.unwrap_or(false)).unwrap_or(false);
+            run_state.visitor.read().unwrap().set_no_log(&host, no_log);
+            run_state.visitor.read().unwrap().on_host_task_start(&run_state.context, &host);
+            // the actual task is invoked here
+            let task_response = run_task_on_host(&run_state,connection,&host,play,task,are_handlers);
+
+            let ok = match task_response {
+                Ok(x) => {
+                    match check {
+                        // output slightly differs in check vs non-check modes
+                        false => run_state.visitor.read().unwrap().on_host_task_ok(&run_state.context, &x, &host),
+                        true => run_state.visitor.read().unwrap().on_host_task_check_ok(&run_state.context, &x, &host)
+                    }
+                    true
+                }
+                Err(x) => {
+                    report_task_failure(run_state, host, &x, rescuable);
+                    false
+                },
+            };
+            run_state.visitor.read().unwrap().set_no_log(&host, false);
+            return ok;
+        },
+        Err(x) => {
+            // hosts with connection failures are removed from the pool
+            run_state.visitor.read().unwrap().debug_host(&host, &x);
+            run_state.context.write().unwrap().fail_host(&host);
+            run_state.visitor.read().unwrap().on_host_connect_failed(&run_state.context, &host);
+            return false;
+        }
+    }
+}
+
 fn get_actual_connection(run_state: &Arc<RunState>, host: &Arc<RwLock<Host>>, task: &Task, input_connection: Arc<Mutex<dyn Connection>>) -> Result<(Option<String>,Arc<Mutex<dyn Connection>>), String> {
     
     // usually the connection we already have is the one we will use, but this is not the case for using the delegate_to feature
@@ -186,6 +466,49 @@ fn run_task_on_host(
     // but allows us to get the 'items' data off the collection. 
     let evaluated = task.evaluate(&handle, &validate, TemplateMode::Off)?;
 
+    // if we are running handlers at the moment, skip any un-notified handlers. those without a
+    // subscribe run once at the end of each batch, as they always have. as they did not run,
+    // nothing is saved for them either.
+    if are_handlers == HandlerMode::Handlers && ! is_handler_notified(run_state, host, &evaluated) {
+        return Ok(handle.response.is_skipped(&Arc::clone(&validate)));
+    }
+
+    // if aftertask/save is present, store the result where later tasks can test it. this happens for
+    // every outcome, including skipped tasks and check mode, and loops save the combined result.
+    let save = match evaluated.aftertask.as_ref() {
+        Some(logic) => logic.save.clone(),
+        None => None
+    };
+
+    let result = run_task_items_on_host(run_state, connection, host, play, task, are_handlers, &handle, &validate, &evaluated);
+
+    if save.is_some() {
+        let response = match &result { Ok(x) => x, Err(y) => y };
+        save_task_result(host, save.as_ref().unwrap(), response);
+    }
+    return result;
+}
+
+fn is_handler_notified(run_state: &Arc<RunState>, host: &Arc<RwLock<Host>>, evaluated: &EvaluatedTask) -> bool {
+    let play_count = run_state.context.read().unwrap().play_count;
+    let subscribe = evaluated.beforetask.as_ref().as_ref().and_then(|x| x.subscribe.as_ref());
+    return match subscribe {
+        Some(x) => x.iter().any(|topic| host.read().unwrap().is_notified(play_count, topic)),
+        None => run_state.context.read().unwrap().get_unsubscribed_handlers()
+    };
+}
+
+fn run_task_items_on_host(
+    run_state: &Arc<RunState>,
+    connection: &Arc<Mutex<dyn Connection>>,
+    host: &Arc<RwLock<Host>>,
+    play: &Play, 
+    task: &Task,
+    are_handlers: HandlerMode,
+    handle: &Arc<TaskHandle>,
+    validate: &Arc<TaskRequest>,
+    evaluated: &EvaluatedTask) -> Result<Arc<TaskResponse>,Arc<TaskResponse>> {
+
     if evaluated.beforetask.is_some() {
         let checkcondition = &evaluated.beforetask.as_ref().as_ref().unwrap().checkcondition; // lol rust
         if checkcondition.is_some() {
@@ -197,27 +520,35 @@ fn run_task_on_host(
     }
 
     // see if we are iterating over a list of items or not
-    let items_input = match evaluated.beforetask.is_some() {
-        true => &evaluated.beforetask.as_ref().as_ref().unwrap().items,
-        false => &None
+    let (items_input, nested_input, loop_var, item_label) = match evaluated.beforetask.is_some() {
+        true => {
+            let logic = evaluated.beforetask.as_ref().as_ref().unwrap();
+            (&logic.items, &logic.nested, get_loop_var(&logic.loop_var), &logic.item_label)
+        },
+        false => (&None, &None, String::from("item"), &None)
     };
+    let is_loop = items_input.is_some() || nested_input.is_some();
 
-    // mapping to store the 'item' variable when using 'with_items'
-    let mut mapping = serde_yaml::Mapping::new();
-
-    // storing the last result of the items loop so we always have something to return
+    // storing the result of each item so they can be combined into one result for the task
     // if a failure occurs it will be returned immediately
-    let mut last : Option<Result<Arc<TaskResponse>,Arc<TaskResponse>>> = None;
+    let mut results : Vec<Arc<TaskResponse>> = Vec::new();
 
     // even if we are not iterating over a list of items, make a list of one item to simplify the logic
-    let evaluated_items = template_items(&handle, &validate, TemplateMode::Strict, &items_input)?;
+    let evaluated_items = template_items(&handle, &validate, TemplateMode::Strict, &items_input, &nested_input)?;
 
     // walking over each item or just the single task if 'with_items' was not used
     for item in evaluated_items.iter() {
             
-        // store the 'items' variable for use in module parameters
-        mapping.insert(serde_yaml::Value::String(String::from("item")), item.clone());
-        host.write().unwrap().update_facts2(mapping.clone());
+        // store the 'items' variable (or the loop_var) for use in module parameters. tasks that do not
+        // loop leave it alone so tasks in an included file can still see the include's item.
+        let mut label = String::new();
+        if is_loop {
+            set_loop_item(host, &loop_var, item);
+            label = match item_label {
+                Some(x) => handle.template.string(&validate, TemplateMode::Strict, &String::from("item_label"), x)?,
+                None => get_item_label(item)
+            };
+        }
 
         // re-evaluate the task, allowing the 'items' to be plugged in.
         let evaluated = task.evaluate(&handle, &validate, TemplateMode::Strict)?;
@@ -235,10 +566,15 @@ fn run_task_on_host(
             
             // here we finally call the actual task, everything around this is just support
             // for delegation, loops, and retries!
-            match run_task_on_host_inner(run_state, &connection, host, play, task, are_handlers, &handle, &validate, &evaluated) {
+            match run_task_on_host_inner(run_state, connection, host, play, task, are_handlers, handle, validate, &evaluated) {
                 Err(e) => match retries {
                     // retries are used up
-                    0 => { return Err(e); },
+                    0 => { 
+                        if is_loop {
+                            run_state.visitor.read().unwrap().on_host_task_item_result(&run_state.context, host, &label, &e);
+                        }
+                        return Err(e); 
+                    },
                     // we have retries left
                     _ => { 
                         retries = retries - 1;
@@ -249,7 +585,13 @@ fn run_task_on_host(
                         }
                     }
                 },
-                Ok(x) => { last = Some(Ok(x)); break }
+                Ok(x) => { 
+                    if is_loop {
+                        run_state.visitor.read().unwrap().on_host_task_item_result(&run_state.context, host, &label, &x);
+                    }
+                    results.push(x); 
+                    break 
+                }
             }
         }
     
@@ -257,13 +599,48 @@ fn run_task_on_host(
 
     // looping over a list of no items should be impossible unless someone passed in a variable that was
     // an empty list
-    if last.is_some() {
-        return last.unwrap();
-    }
-    else {
-        return Err(handle.response.is_failed(&validate, &String::from("with/items contained no entries")));    
-    }
+    return match results.len() {
+        0 => Err(handle.response.is_failed(&validate, &String::from("with/items contained no entries"))),
+        1 => Ok(results.pop().unwrap()),
+        _ => Ok(combine_item_results(&results))
+    };
+
+}
+
+fn combine_item_results(results: &Vec<Arc<TaskResponse>>) -> Arc<TaskResponse> {
+
+    // a loop is reported as a single task result. if any item made a change the task has too,
+    // taking the status of the last item that did, with the changes from every item.
 
+    let is_change = |x: &TaskStatus| match x {
+        TaskStatus::IsCreated | TaskStatus::IsRemoved | TaskStatus::IsModified | TaskStatus::IsExecuted => true,
+        TaskStatus::NeedsCreation | TaskStatus::NeedsRemoval | TaskStatus::NeedsModification | TaskStatus::NeedsExecution => true,
+        _ => false
+    };
+    let last = results.last().unwrap();
+    let representative = match results.iter().rev().find(|x| is_change(&x.status)) {
+        Some(x) => x,
+        None => match results.iter().rev().find(|x| x.status == TaskStatus::Failed) {
+            Some(x) => x,
+            None => last
+        }
+    };
+    let mut changes : Vec<Field> = Vec::new();
+    for result in results.iter() {
+        for change in result.changes.iter() {
+            if ! changes.contains(change) {
+                changes.push(change.clone());
+            }
+        }
+    }
+    return Arc::new(TaskResponse {
+        status: representative.status.clone(),
+        changes: changes,
+        msg: last.msg.clone(),
+        command_result: Arc::clone(&last.command_result),
+        beforetask: Arc::clone(&last.beforetask),
+        aftertask: Arc::clone(&last.aftertask)
+    });
 }
 
 // the "on this host" method body from _task
@@ -273,9 +650,9 @@ fn run_task_on_host_inner(
     host: &Arc<RwLock<Host>>,
     play: &Play, 
     _task: &Task,
-    are_handlers: HandlerMode, 
+    _are_handlers: HandlerMode, 
     handle: &Arc<TaskHandle>,
-    validate: &Arc<TaskRequest>,
+    _validate: &Arc<TaskRequest>,
     evaluated: &EvaluatedTask) -> Result<Arc<TaskResponse>,Arc<TaskResponse>> {
 
     let play_count = run_state.context.read().unwrap().play_count;
@@ -301,13 +678,6 @@ fn run_task_on_host_inner(
     // is 'with' provided?
     if pre_logic.is_some() {
         let logic = pre_logic.as_ref().as_ref().unwrap();
-        let my_host = host.read().unwrap();
-        if are_handlers == HandlerMode::Handlers  {
-            // if we are running handlers at the moment, skip any un-notified handlers
-            if ! my_host.is_notified(play_count, &logic.subscribe.as_ref().unwrap().clone()) {
-                return Ok(handle.response.is_skipped(&Arc::clone(&validate))); 
-            }
-        }
         
         // if sudo was requested on the specific task override any sudo computations above
         if logic.sudo.is_some() {
@@ -476,17 +846,19 @@ fn run_task_on_host_inner(
         }
     };
 
-    // if and/notify is present, notify handlers when changed actions are seen
+    // if and/notify is present, notify handlers when changed actions are seen. handlers may
+    // notify other handlers, which run in the next pass over the handlers.
 
     if result.is_ok() && post_logic.is_some() {
         let logic = post_logic.as_ref().as_ref().unwrap();
-        if are_handlers == HandlerMode::NormalTasks && result.is_ok() && logic.notify.is_some() {
-            let notify = logic.notify.as_ref().unwrap().clone();
+        if result.is_ok() && logic.notify.is_some() {
             let status = &result.as_ref().unwrap().status;
             match status {
                 TaskStatus::IsCreated | TaskStatus::IsModified | TaskStatus::IsRemoved | TaskStatus::IsExecuted => {
-                    run_state.visitor.read().unwrap().on_notify_handler(host, &notify.clone());
-                    host.write().unwrap().notify(play_count, &notify.clone());
+                    for notify in logic.notify.as_ref().unwrap().iter() {
+                        run_state.visitor.read().unwrap().on_notify_handler(host, &notify.clone());
+                        host.write().unwrap().notify(play_count, &notify.clone());
+                    }
                 },
                 _ => { }
             }
@@ -497,3 +869,113 @@ fn run_task_on_host_inner(
 
     return result;
 }
+
+fn save_task_result(host: &Arc<RwLock<Host>>, key: &String, response: &Arc<TaskResponse>) {
+
+    // aftertask/save stores a summary of any module's result in the host facts, for instance
+    // so a later checkcondition can test whether a package was upgraded. a previous result saved
+    // under the same name is replaced, not merged.
+
+    let status = match response.status {
+        TaskStatus::IsCreated  => "created",
+        TaskStatus::IsRemoved  => "removed",
+        TaskStatus::IsModified => "modified",
+        TaskStatus::IsExecuted => "executed",
+        TaskStatus::IsPassive  => "passive",
+        TaskStatus::IsMatched  => "matched",
+        TaskStatus::IsSkipped  => "skipped",
+        TaskStatus::Failed     => "failed",
+        // check mode reports the changes that would have been made
+        TaskStatus::NeedsCreation     => "created",
+        TaskStatus::NeedsRemoval      => "removed",
+        TaskStatus::NeedsModification => "modified",
+        TaskStatus::NeedsExecution    => "executed",
+        _ => "unknown"
+    };
+    let changed = match response.status {
+        TaskStatus::IsCreated | TaskStatus::IsRemoved | TaskStatus::IsModified | TaskStatus::IsExecuted => true,
+        TaskStatus::NeedsCreation | TaskStatus::NeedsRemoval | TaskStatus::NeedsModification | TaskStatus::NeedsExecution => true,
+        _ => false
+    };
+    let changes : Vec<serde_yaml::Value> = response.changes.iter().map(|x| serde_yaml::Value::String(format!("{:?}", x).to_lowercase())).collect();
+
+    let mut data = serde_yaml::Mapping::new();
+    data.insert(serde_yaml::Value::String(String::from("status")), serde_yaml::Value::String(String::from(status)));
+    data.insert(serde_yaml::Value::String(String::from("changed")), serde_yaml::Value::Bool(changed));
+    data.insert(serde_yaml::Value::String(String::from("failed")), serde_yaml::Value::Bool(response.status == TaskStatus::Failed));
+    data.insert(serde_yaml::Value::String(String::from("changes")), serde_yaml::Value::Sequence(changes));
+    data.insert(serde_yaml::Value::String(String::from("msg")), match &response.msg {
+        Some(x) => serde_yaml::Value::String(x.clone()),
+        None => serde_yaml::Value::Null
+    });
+    match response.command_result.as_ref() {
+        Some(cmd_result) => {
+            data.insert(serde_yaml::Value::String(String::from("rc")), serde_yaml::Value::Number(serde_yaml::Number::from(cmd_result.rc)));
+            data.insert(serde_yaml::Value::String(String::from("out")), serde_yaml::Value::String(cmd_result.out.clone()));
+        },
+        None => {
+            data.insert(serde_yaml::Value::String(String::from("rc")), serde_yaml::Value::Null);
+            data.insert(serde_yaml::Value::String(String::from("out")), serde_yaml::Value::Null);
+        }
+    };
+
+    let mut mapping = serde_yaml::Mapping::new();
+    mapping.insert(serde_yaml::Value::String(key.clone()), serde_yaml::Value::Mapping(data));
+    let mut host2 = host.write().unwrap();
+    host2.clear_fact(key);
+    host2.update_facts2(mapping);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasks::fields::Field;
+
+    fn response(status: TaskStatus, changes: Vec<Field>, msg: &str) -> Arc<TaskResponse> {
+        return Arc::new(TaskResponse {
+            status: status,
+            changes: changes,
+            msg: Some(String::from(msg)),
+            command_result: Arc::new(None),
+            beforetask: Arc::new(None),
+            aftertask: Arc::new(None)
+        });
+    }
+
+    #[test]
+    fn test_combine_item_results() {
+        // failed items only get this far when errors are ignored, an item that is not ignored ends the loop
+        // (item results, expected status)
+        let cases = vec![
+            (vec![TaskStatus::IsMatched, TaskStatus::IsMatched], TaskStatus::IsMatched),
+            (vec![TaskStatus::IsMatched, TaskStatus::IsModified, TaskStatus::IsMatched], TaskStatus::IsModified),
+            (vec![TaskStatus::IsCreated, TaskStatus::IsExecuted], TaskStatus::IsExecuted),
+            (vec![TaskStatus::NeedsCreation, TaskStatus::IsMatched], TaskStatus::NeedsCreation),
+            (vec![TaskStatus::IsMatched, TaskStatus::Failed, TaskStatus::IsMatched], TaskStatus::Failed),
+            (vec![TaskStatus::Failed, TaskStatus::IsModified], TaskStatus::IsModified),
+            (vec![TaskStatus::IsModified, TaskStatus::Failed], TaskStatus::IsModified),
+            (vec![TaskStatus::IsSkipped, TaskStatus::IsSkipped], TaskStatus::IsSkipped),
+        ];
+        for (statuses, expected) in cases.iter() {
+            let results : Vec<Arc<TaskResponse>> = statuses.iter().enumerate().map(
+                |(i, x)| response(x.clone(), Vec::new(), &format!("item {}", i))
+            ).collect();
+            let combined = combine_item_results(&results);
+            assert_eq!(combined.status, *expected, "items: {:?}", statuses);
+            // the message is the one from the last item, whatever the status
+            assert_eq!(combined.msg, Some(format!("item {}", statuses.len() - 1)));
+        }
+    }
+
+    #[test]
+    fn test_combine_item_changes() {
+        let results = vec![
+            response(TaskStatus::IsModified, vec![Field::Content, Field::Mode], "a"),
+            response(TaskStatus::Failed, Vec::new(), "b"),
+            response(TaskStatus::IsModified, vec![Field::Mode, Field::Owner], "c"),
+        ];
+        let combined = combine_item_results(&results);
+        assert_eq!(combined.status, TaskStatus::IsModified);
+        assert_eq!(combined.changes, vec![Field::Content, Field::Mode, Field::Owner]);
+    }
+}
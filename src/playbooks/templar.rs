@@ -15,10 +15,13 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use serde_yaml;
+use std::path::Path;
 use once_cell::sync::Lazy;
 use handlebars::{Handlebars,RenderError};
+use minijinja::{Environment,UndefinedBehavior};
 
 use crate::playbooks::t_helpers::register_helpers;
+use crate::playbooks::t_jinja::register_filters;
 
 // templar contains low-level wrapping around handlebars.
 // this is not used directly when evaluating templates and template
@@ -33,6 +36,20 @@ static HANDLEBARS: Lazy<Handlebars> = Lazy::new(|| {
     return hb;
 });
 
+// the jinja engine is only used by the template module, for files ending in .j2 or when
+// asked for with 'engine: jinja', which makes templates from ansible roles usable as is.
+// undefined variables are errors just like with handlebars. blocks are trimmed and the
+// final newline kept, as ansible does.
+
+static JINJA: Lazy<Environment<'static>> = Lazy::new(|| {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_trim_blocks(true);
+    env.set_keep_trailing_newline(true);
+    register_filters(&mut env);
+    return env;
+});
+
 // 'off' mode is used in a bit of a weird traversal/engine
 // situation where we need to get access to some task parameters
 // before templates are evaluated. You will notice there is no way
@@ -44,6 +61,32 @@ pub enum TemplateMode {
     Off
 }
 
+#[derive(PartialEq,Copy,Clone,Debug)]
+pub enum TemplateEngine {
+    Handlebars,
+    Jinja
+}
+
+impl TemplateEngine {
+
+    // picks the engine for a template file, an explicit choice wins over the file extension
+
+    pub fn select(engine: &Option<String>, src: &Path) -> Result<Self, String> {
+        if engine.is_some() {
+            return match engine.as_ref().unwrap().as_str() {
+                "handlebars" => Ok(TemplateEngine::Handlebars),
+                "jinja"      => Ok(TemplateEngine::Jinja),
+                x            => Err(format!("expecting 'handlebars' or 'jinja', got '{}'", x))
+            };
+        }
+        return match src.extension().and_then(|x| x.to_str()) {
+            Some("j2") => Ok(TemplateEngine::Jinja),
+            _          => Ok(TemplateEngine::Handlebars)
+        };
+    }
+
+}
+
 pub struct Templar {
 }
 
@@ -57,6 +100,15 @@ impl Templar {
     // evaluate a string
 
     pub fn render(&self, template: &String, data: serde_yaml::Mapping, template_mode: TemplateMode) -> Result<String, String> {
+        return self.render_with_engine(template, data, template_mode, TemplateEngine::Handlebars);
+    }
+
+    // the template module may ask for a different engine, everything else is handlebars
+
+    pub fn render_with_engine(&self, template: &String, data: serde_yaml::Mapping, template_mode: TemplateMode, engine: TemplateEngine) -> Result<String, String> {
+        if engine == TemplateEngine::Jinja {
+            return self.render_jinja(template, data, template_mode);
+        }
         let result : Result<String, RenderError> = match template_mode {
             TemplateMode::Strict => HANDLEBARS.render_template(template, &data),
             /* this is only used to get back the raw 'items' collection inside the task FSM */
@@ -72,6 +124,16 @@ impl Templar {
         }
     }
     
+    fn render_jinja(&self, template: &String, data: serde_yaml::Mapping, template_mode: TemplateMode) -> Result<String, String> {
+        if template_mode == TemplateMode::Off {
+            return Ok(String::from("empty"));
+        }
+        return match JINJA.render_str(template, &data) {
+            Ok(x) => Ok(x),
+            Err(y) => Err(format!("Template error: {}", y))
+        };
+    }
+
     // used for with/cond and also in the shell module
 
     pub fn test_condition(&self, expr: &String, data: serde_yaml::Mapping, template_mode: TemplateMode) -> Result<bool, String> {
@@ -17,19 +17,26 @@
 use crate::playbooks::language::Play;
 use crate::playbooks::visitor::PlaybookVisitor;
 use crate::playbooks::context::PlaybookContext;
-use crate::playbooks::language::{Role,RoleInvocation};
+use crate::playbooks::language::{Role,RoleInvocation,RoleArgument,Block,Include,PlaybookEntry,ImportPlaybook};
 use crate::connection::factory::ConnectionFactory;
 use crate::registry::list::Task;
-use crate::playbooks::task_fsm::fsm_run_task;
+use crate::playbooks::task_fsm::{fsm_run_task,fsm_run_tasks_free,fsm_resolve_include,fsm_check_failure_limits};
 use crate::inventory::inventory::Inventory;
 use crate::inventory::hosts::Host;
-use crate::util::io::{jet_file_open,directory_as_string};
+use crate::util::io::{jet_file_open,directory_as_string,path_as_string,path_walk,path_basename_as_string};
+use crate::tasks::logic::set_loop_item;
 use crate::util::yaml::{blend_variables,show_yaml_error_in_context};
+use crate::util::vault::load_vars_file;
+use crate::util::secrets::add_secret_values;
+use crate::util::terminal::{prompt_choice,markdown_print};
+use crate::handle::template::BlendTarget;
+use crate::playbooks::templar::TemplateMode;
 use std::path::PathBuf;
 use std::collections::HashMap;
 use std::sync::{Arc,RwLock};
 use std::path::Path;
 use std::env;
+use std::fs;
 
 // this module contains the start of everything related to playbook evaluation
 
@@ -40,6 +47,26 @@ pub enum HandlerMode {
     Handlers
 }
 
+// with the linear strategy every remaining host finishes a task before any host
+// starts the next one. with the free strategy each host walks the task list on its
+// own, so fast hosts are not held up by slow ones.  roles and handlers are still
+// processed in order since the role directory and variables are shared, and hosts
+// only wait for each other at a top level flush_handlers.
+#[derive(PartialEq,Copy,Debug,Clone)]
+pub enum Strategy {
+    Linear,
+    Free
+}
+
+// handlers notifying handlers could otherwise go on forever
+const MAX_HANDLER_PASSES : usize = 32;
+
+// files importing or including themselves could otherwise go on forever
+pub const MAX_INCLUDE_DEPTH : usize = 32;
+
+// the types that can be used in a role's argument_spec
+const ROLE_ARGUMENT_TYPES : [&str; 7] = ["any", "str", "int", "float", "bool", "list", "dict"];
+
 // the run state is a quasi-global that can be used to access all
 // import 'objects' related to playbook evaluation
 
@@ -55,7 +82,12 @@ pub struct RunState {
     pub visitor: Arc<RwLock<PlaybookVisitor>>,
     pub connection_factory: Arc<RwLock<dyn ConnectionFactory>>,
     pub tags: Option<Vec<String>>,
-    pub allow_localhost_delegation: bool
+    pub skip_tags: Option<Vec<String>>,
+    pub start_at_task: Arc<RwLock<Option<String>>>,
+    pub step: Arc<RwLock<bool>>,
+    pub allow_localhost_delegation: bool,
+    pub vault_password: Option<String>,
+    pub diff: bool
 }
 
 // this is the top end traversal function that is called from cli/playbooks.rs
@@ -65,56 +97,163 @@ pub fn playbook_traversal(run_state: &Arc<RunState>) -> Result<(), String> {
     // it's possible to specify multiple playbooks seperated by colons on the command line
 
     for playbook_path in run_state.playbook_paths.read().unwrap().iter() {
+        let mut importers : Vec<PathBuf> = Vec::new();
+        process_playbook(run_state, playbook_path, &mut importers)?;
+    }
+    // disconnect from all hosts and exit. 
+    run_state.context.read().unwrap().connection_cache.write().unwrap().clear();
+    if run_state.start_at_task.read().unwrap().is_some() {
+        return Err(format!("--start-at-task: no task named '{}' was found", run_state.start_at_task.read().unwrap().as_ref().unwrap()));
+    }
+    return Ok(())
+}
+
+fn process_playbook(run_state: &Arc<RunState>, playbook_path: &PathBuf, importers: &mut Vec<PathBuf>) -> Result<(), String> {
+
+    // importers is the chain of playbooks that imported this one, used to catch loops
+
+    if importers.contains(playbook_path) {
+        return Err(format!("playbook import loop detected: {} imports itself", playbook_path.display()));
+    }
+
+    { 
+        // let the context object know what playbook we're currently running
+        // braces are to avoid a deadlock
+        let mut ctx = run_state.context.write().unwrap(); 
+        ctx.set_playbook_path(playbook_path); 
+    }
+
+    run_state.visitor.read().unwrap().on_playbook_start(&run_state.context);
+
+    // parse the playbook file
+    let entries = load_playbook(run_state, playbook_path)?;
+
+    // chdir in the playbook directory
+    let p1 = env::current_dir().expect("could not get current directory");
+    let previous = p1.as_path();
+    let pbdirname = directory_as_string(playbook_path);
+    let pbdir = Path::new(&pbdirname);
+    if pbdirname.eq(&String::from("")) {
+    } else {
+        env::set_current_dir(&pbdir).expect("could not chdir into playbook directory");
+    }
 
-        { 
-            // let the context object know what playbook we're currently running
-            // braces are to avoid a deadlock
-            let mut ctx = run_state.context.write().unwrap(); 
-            ctx.set_playbook_path(playbook_path); 
+    // walk each play in the playbook
+    let pb_base = playbook_path.parent().unwrap().to_path_buf();
+    for entry in entries.iter() {
+        match entry {
+            PlaybookEntry::Play(play) => {
+                match handle_play(&run_state, play) {
+                    Ok(_) => {},
+                    Err(s) => { return Err(s); }
+                }
+                // disconnect from all hosts between plays
+                run_state.context.read().unwrap().connection_cache.write().unwrap().clear();
+            },
+            PlaybookEntry::ImportPlaybook(import) => {
+                let imported = get_imported_playbook_path(run_state, &pb_base, &import.import_playbook)?;
+                importers.push(playbook_path.clone());
+                process_playbook(run_state, &imported, importers)?;
+                importers.pop();
+                let mut ctx = run_state.context.write().unwrap(); 
+                ctx.set_playbook_path(playbook_path); 
+            }
         }
+    }
+    // disconnect from all hosts between playbooks
+    run_state.context.read().unwrap().connection_cache.write().unwrap().clear();
 
-        run_state.visitor.read().unwrap().on_playbook_start(&run_state.context);
+    // switch back to the original directory
+    env::set_current_dir(&previous).expect("could not restore previous directory");
+    return Ok(());
+}
 
-        // parse the playbook file
-        let playbook_file = jet_file_open(&playbook_path)?;
-        let parsed: Result<Vec<Play>, serde_yaml::Error> = serde_yaml::from_reader(playbook_file);
-        if parsed.is_err() {
-            show_yaml_error_in_context(&parsed.unwrap_err(), &playbook_path);
-            return Err(format!("edit the file and try again?"));
-        }   
+pub fn load_playbook(run_state: &Arc<RunState>, playbook_path: &PathBuf) -> Result<Vec<PlaybookEntry>, String> {
 
-        // chdir in the playbook directory
-        let p1 = env::current_dir().expect("could not get current directory");
-        let previous = p1.as_path();
-        let pbdirname = directory_as_string(playbook_path);
-        let pbdir = Path::new(&pbdirname);
-        if pbdirname.eq(&String::from("")) {
-        } else {
-            env::set_current_dir(&pbdir).expect("could not chdir into playbook directory");
-        }
-
-        // walk each play in the playbook
-        let plays: Vec<Play> = parsed.unwrap();
-        for play in plays.iter() {
-            match handle_play(&run_state, play) {
-                Ok(_) => {},
-                Err(s) => { return Err(s); }
+    // import_tasks statements and role dependencies are resolved before anything runs, 
+    // import_tasks relative to the playbook
+
+    let mut entries = parse_playbook(playbook_path)?;
+    let pb_base = playbook_path.parent().unwrap().to_path_buf();
+    for entry in entries.iter_mut() {
+        if let PlaybookEntry::Play(play) = entry {
+            if play.tasks.is_some() {
+                play.tasks = Some(expand_imports(play.tasks.take().unwrap(), &pb_base, 0)?);
+            }
+            if play.handlers.is_some() {
+                play.handlers = Some(expand_imports(play.handlers.take().unwrap(), &pb_base, 0)?);
+            }
+            if play.roles.is_some() {
+                let roles = expand_role_dependencies(run_state, play)?;
+                play.roles = Some(roles);
             }
-            // disconnect from all hosts between plays
-            run_state.context.read().unwrap().connection_cache.write().unwrap().clear();
         }
-        // disconnect from all hosts between playbooks
-        run_state.context.read().unwrap().connection_cache.write().unwrap().clear();
+    }
+    return Ok(entries);
+}
 
-        // switch back to the original directory
-        env::set_current_dir(&previous).expect("could not restore previous directory");
+fn parse_playbook(playbook_path: &PathBuf) -> Result<Vec<PlaybookEntry>, String> {
 
+    // most playbooks are only plays, which gives the best error messages when they are wrong
+    let playbook_file = jet_file_open(&playbook_path)?;
+    let parsed: Result<Vec<Play>, serde_yaml::Error> = serde_yaml::from_reader(playbook_file);
+    let plays_error = match parsed {
+        Ok(plays) => { return Ok(plays.into_iter().map(|x| PlaybookEntry::Play(x)).collect()); },
+        Err(y) => y
+    };
 
+    // otherwise look for import_playbook lines and parse each entry on its own. serde's
+    // untagged enums can't be used here as they lose the YAML tags used by tasks.
+    let playbook_file = jet_file_open(&playbook_path)?;
+    let values: Vec<serde_yaml::Value> = match serde_yaml::from_reader(playbook_file) {
+        Ok(x) => x,
+        Err(_) => {
+            show_yaml_error_in_context(&plays_error, &playbook_path);
+            return Err(format!("edit the file and try again?"));
+        }
+    };
+    let has_imports = values.iter().any(|x| x.get("import_playbook").is_some());
+    if ! has_imports {
+        show_yaml_error_in_context(&plays_error, &playbook_path);
+        return Err(format!("edit the file and try again?"));
     }
-    // disconnect from all hosts and exit. 
-    run_state.context.read().unwrap().connection_cache.write().unwrap().clear();
-    run_state.visitor.read().unwrap().on_exit(&run_state.context);
-    return Ok(())
+    let mut entries : Vec<PlaybookEntry> = Vec::new();
+    for (index, value) in values.into_iter().enumerate() {
+        let entry = match value.get("import_playbook").is_some() {
+            true  => serde_yaml::from_value::<ImportPlaybook>(value).map(|x| PlaybookEntry::ImportPlaybook(x)),
+            false => serde_yaml::from_value::<Play>(value).map(|x| PlaybookEntry::Play(x))
+        };
+        match entry {
+            Ok(x) => entries.push(x),
+            Err(y) => { return Err(format!("error reading YAML file: {}, entry {}: {}", playbook_path.display(), index, y)); }
+        }
+    }
+    return Ok(entries);
+}
+
+pub fn get_imported_playbook_path(run_state: &Arc<RunState>, pb_base: &PathBuf, file: &String) -> Result<PathBuf, String> {
+
+    // imported playbooks are found relative to the importing playbook, and like playbooks given
+    // on the command line, a roles/ directory alongside them is added to the role search path
+
+    let mut path = pb_base.clone();
+    path.push(file);
+    if ! path.is_file() {
+        return Err(format!("import_playbook: no such file: {}", path.display()));
+    }
+    let full = match fs::canonicalize(path.as_path()) {
+        Ok(x) => x,
+        Err(y) => { return Err(format!("import_playbook: {}: {}", path.display(), y)); }
+    };
+    let mut roles = full.parent().unwrap().to_path_buf();
+    roles.push("roles");
+    if roles.is_dir() {
+        let mut role_paths = run_state.role_paths.write().unwrap();
+        if ! role_paths.contains(&roles) {
+            role_paths.push(roles);
+        }
+    }
+    return Ok(full);
 }
 
 fn handle_play(run_state: &Arc<RunState>, play: &Play) -> Result<(), String> {
@@ -137,6 +276,12 @@ fn handle_play(run_state: &Arc<RunState>, play: &Play) -> Result<(), String> {
     }
     run_state.visitor.read().unwrap().on_play_start(&run_state.context);
 
+    // fail early on a typo rather than silently running linearly
+    get_strategy(play)?;
+    if play.max_fail_percentage.is_some() && play.max_fail_percentage.unwrap() > 100 {
+        return Err(format!("play {}: max_fail_percentage must be between 0 and 100", play.name));
+    }
+
     // make sure all host and groups used to limit exists
     validate_limit_groups(run_state, play)?;
     validate_limit_hosts(run_state, play)?;
@@ -146,6 +291,7 @@ fn handle_play(run_state: &Arc<RunState>, play: &Play) -> Result<(), String> {
     let hosts = get_play_hosts(run_state, play);
     validate_hosts(run_state, play, &hosts)?;
     load_vars_into_context(run_state, play)?;
+    register_secret_vars(run_state, play, &hosts);
 
     // support for serialization if using push configuration
     // means we may not configure hosts all at once but may take
@@ -200,64 +346,224 @@ fn handle_batch(run_state: &Arc<RunState>, play: &Play, hosts: &Vec<Arc<RwLock<H
     // handle loose play tasks
     if play.tasks.is_some() {
         let tasks = play.tasks.as_ref().unwrap();
-        for task in tasks.iter() { process_task(run_state, &play, &task, HandlerMode::NormalTasks, None)?; }
+        process_tasks(run_state, &play, &tasks, HandlerMode::NormalTasks, None)?;
     }
 
-    // handle role handlers
-    if play.roles.is_some() {
-        let roles = play.roles.as_ref().unwrap();
-        for invocation in roles.iter() { process_role(run_state, &play, &invocation, HandlerMode::Handlers)?; }
-    }   
-    { let mut ctx = run_state.context.write().unwrap(); ctx.unset_role(); }  
+    // handle role and loose play handlers
+    process_handlers(run_state, &play, true)?;
+    return Ok(())
 
-    // handle loose play handlers
-    if play.handlers.is_some() {
-        let handlers = play.handlers.as_ref().unwrap();
-        for handler in handlers { process_task(run_state, &play, &handler, HandlerMode::Handlers, None)?;  }
+}
+
+fn process_handlers(run_state: &Arc<RunState>, play: &Play, end_of_batch: bool) -> Result<(), String> {
+
+    // handlers run in passes until no host has any notifications left, so that a handler
+    // notifying another handler sees it run. this happens at the end of each batch and
+    // wherever flush_handlers is used. handlers without a subscribe have always run at the
+    // end of each batch, notified or not, so the first pass there runs them once.
+
+    let play_count = run_state.context.read().unwrap().play_count;
+    let mut passes : usize = 0;
+    loop {
+        let hosts : HashMap<String, Arc<RwLock<Host>>> = run_state.context.read().unwrap().get_remaining_hosts();
+        let mut pending = false;
+        for (_, host) in hosts.iter() {
+            if host.write().unwrap().start_handlers(play_count) {
+                pending = true;
+            }
+        }
+        let unsubscribed = end_of_batch && passes == 0;
+        if ! pending && ! unsubscribed {
+            for (_, host) in hosts.iter() { host.write().unwrap().finish_handlers(play_count); }
+            return Ok(());
+        }
+        passes = passes + 1;
+        if passes > MAX_HANDLER_PASSES {
+            return Err(format!("handlers were still being notified after {} passes, do some handlers notify each other?", MAX_HANDLER_PASSES));
+        }
+
+        run_state.context.write().unwrap().set_unsubscribed_handlers(unsubscribed);
+
+        // handle role handlers
+        if play.roles.is_some() {
+            let roles = play.roles.as_ref().unwrap();
+            for invocation in roles.iter() { process_role(run_state, &play, &invocation, HandlerMode::Handlers)?; }
+        }   
+        { let mut ctx = run_state.context.write().unwrap(); ctx.unset_role(); }  
+
+        // handle loose play handlers
+        if play.handlers.is_some() {
+            let handlers = play.handlers.as_ref().unwrap();
+            process_tasks(run_state, &play, &handlers, HandlerMode::Handlers, None)?;
+        }
+
+        run_state.context.write().unwrap().set_unsubscribed_handlers(false);
+
+        for (_, host) in hosts.iter() { host.write().unwrap().finish_handlers(play_count); }
     }
-    return Ok(())
+}
+
+fn flush_handlers(run_state: &Arc<RunState>, play: &Play, are_handlers: HandlerMode, role_invocation: Option<&RoleInvocation>) -> Result<(), String> {
 
+    // handlers already run until nothing is left to run, so flushing from a handler does nothing
+    if are_handlers == HandlerMode::Handlers {
+        return Ok(());
+    }
+    run_state.visitor.read().unwrap().on_flush_handlers(&run_state.context);
+
+    // the handlers may be from any role, so when flushing from inside a role put it back afterwards
+    let previous = env::current_dir().expect("could not get current directory");
+    process_handlers(run_state, play, false)?;
+    if role_invocation.is_some() {
+        let invocation = role_invocation.unwrap();
+        let (role, role_path) = find_role(run_state, &play, invocation.role.clone())?;
+        let mut ctx = run_state.context.write().unwrap();
+        ctx.set_role(&role, invocation, &path_as_string(&role_path));
+    }
+    match env::set_current_dir(&previous) {
+        Ok(_) => {}, Err(s) => { return Err(format!("could not restore previous directory after flushing handlers: {:?}, {}", previous, s)) }
+    }
+    return Ok(());
 }
 
-fn check_tags(run_state: &Arc<RunState>, task: &Task, role_invocation: Option<&RoleInvocation>) -> bool {
+pub fn check_tags(run_state: &Arc<RunState>, play: &Play, task: &Task, role_invocation: Option<&RoleInvocation>) -> bool {
 
-    // a given task may have tags associated from either the current role or directly on the task
+    // a given task may have tags associated from the play, the current role or directly on the task.
     // if the CLI --tags argument was used, we will skip the task if those tags don't match or
-    // if the tags are ommitted
+    // if the tags are ommitted, and --skip-tags skips any task with a matching tag
+
+    let task_tags = get_task_tags(play, task, role_invocation);
+    return tags_selected(&run_state.tags, &run_state.skip_tags, &task_tags);
+}
 
-    match &run_state.tags {
+pub fn get_task_tags(play: &Play, task: &Task, role_invocation: Option<&RoleInvocation>) -> Vec<String> {
+
+    // tags on the play apply to everything in it, and tags on a role invocation apply to all
+    // of the role's tasks and handlers
+
+    let mut tags : Vec<String> = Vec::new();
+    let mut add = |more: &Vec<String>| {
+        for x in more.iter() { if ! tags.contains(x) { tags.push(x.clone()); } }
+    };
+    match task.get_with() {
+        Some(task_with) => match &task_with.tags {
+            Some(task_tags) => add(task_tags),
+            None => {}
+        },
+        None => {}
+    };
+    match role_invocation {
+        Some(role_invoke) => match &role_invoke.tags {
+            Some(role_tags) => add(role_tags),
+            None => {}
+        },
+        None => {}
+    };
+    match &play.tags {
+        Some(play_tags) => add(play_tags),
+        None => {}
+    };
+    return tags;
+}
+
+pub fn tags_selected(cli_tags: &Option<Vec<String>>, cli_skip_tags: &Option<Vec<String>>, task_tags: &Vec<String>) -> bool {
+
+    // two tags are special. 'always' tasks run even when --tags does not mention them, and 'never'
+    // tasks only run when --tags asks for them, either by one of their other tags or by 'never'.
+    // --tags all selects everything except 'never' tasks. --skip-tags always wins.
+
+    let always = String::from("always");
+    let never = String::from("never");
+
+    match cli_skip_tags {
+        Some(skip_tags) => {
+            for x in task_tags.iter() { if skip_tags.contains(x) { return false; } }
+        },
+        None => {}
+    }
+    let is_never = task_tags.contains(&never);
+    match cli_tags {
+        // no CLI tags so run the task
+        None => { return ! is_never; },
         Some(cli_tags) => {
-            // CLI tags were specified
-            match task.get_with() {
-                // a with section was present
-                Some(task_with) => match task_with.tags {
-                    // tags are applied to the task
-                    Some(task_tags) => {
-                        for x in task_tags.iter() {  if cli_tags.contains(&x) { return true; } }
-                    },
-                    // no tags
-                    None => {}
-                },
-                None => {}
-            };
-            match role_invocation {
-                // the role invocation has tags applied
-                Some(role_invoke) => match &role_invoke.tags {
-                    Some(role_tags) => {
-                        for x in role_tags.iter() { if cli_tags.contains(&x) { return true; } }
-                    },
-                    None => {}
-                },
-                None => {}
-            };
+            if task_tags.contains(&always) {
+                return true;
+            }
+            if cli_tags.contains(&String::from("all")) && ! is_never {
+                return true;
+            }
+            for x in task_tags.iter() {
+                if cli_tags.contains(x) && ! x.eq(&always) { return true; }
+            }
         }
-        // no CLI tags so run the task
-        None => { return true; }
     }
     // we didn't match any tags, so don't run the task
     return false;
 }
 
+pub fn contains_flush_handlers(tasks: &[Task]) -> bool {
+    // looks through blocks, but not includes, as those are only loaded when they run
+    return tasks.iter().any(|task| match task {
+        Task::Flush_Handlers(_) => true,
+        Task::Block(block) => {
+            contains_flush_handlers(&block.tasks)
+            || block.rescue.as_ref().map(|x| contains_flush_handlers(x)).unwrap_or(false)
+            || block.always.as_ref().map(|x| contains_flush_handlers(x)).unwrap_or(false)
+        },
+        _ => false
+    });
+}
+
+fn get_strategy(play: &Play) -> Result<Strategy, String> {
+    return match &play.strategy {
+        None => Ok(Strategy::Linear),
+        Some(x) => match x.as_str() {
+            "linear" => Ok(Strategy::Linear),
+            "free"   => Ok(Strategy::Free),
+            _        => Err(format!("play {}: strategy must be 'linear' or 'free', got: {}", play.name, x))
+        }
+    };
+}
+
+fn process_tasks(run_state: &Arc<RunState>, play: &Play, tasks: &Vec<Task>, are_handlers: HandlerMode, role_invocation: Option<&RoleInvocation>) -> Result<(), String> {
+
+    // runs a list of tasks from the play or from a role task file, according to the play strategy.
+    // --step and --start-at-task look at one task at a time for all hosts, so until they are done
+    // with the free strategy is run as linear
+
+    let mut strategy = get_strategy(play)?;
+    if *run_state.step.read().unwrap() || run_state.start_at_task.read().unwrap().is_some() {
+        strategy = Strategy::Linear;
+    }
+    match strategy {
+        Strategy::Linear => {
+            for task in tasks.iter() { process_task(run_state, &play, &task, are_handlers, role_invocation)?; }
+        },
+        Strategy::Free => {
+            // hosts walk the tasks on their own, except that they all wait for each other at flush_handlers.
+            // that only works between top level tasks, so anywhere else it is an error rather than skipped.
+            for task in tasks.iter() {
+                if matches!(task, Task::Block(_)) && contains_flush_handlers(std::slice::from_ref(task)) {
+                    return Err(format!("play {}: flush_handlers cannot be used inside a block with the free strategy", play.name));
+                }
+            }
+            let segments : Vec<&[Task]> = tasks.split(|x| matches!(x, Task::Flush_Handlers(_))).collect();
+            for (index, segment) in segments.iter().enumerate() {
+                if index > 0 {
+                    flush_handlers(run_state, play, are_handlers, role_invocation)?;
+                }
+                if segment.is_empty() {
+                    continue;
+                }
+                let hosts : HashMap<String, Arc<RwLock<Host>>> = run_state.context.read().unwrap().get_remaining_hosts();
+                if hosts.len() == 0 { return Err(String::from("no hosts remaining")) }
+                fsm_run_tasks_free(run_state, play, segment, are_handlers, role_invocation)?;
+            }
+        }
+    }
+    return Ok(());
+}
+
 fn process_task(run_state: &Arc<RunState>, play: &Play, task: &Task, are_handlers: HandlerMode, role_invocation: Option<&RoleInvocation>) -> Result<(), String> {
 
     // this function is the final wrapper before fsm_run_task, the low-level finite state machine around task execution that is wrapped
@@ -266,8 +572,34 @@ fn process_task(run_state: &Arc<RunState>, play: &Play, task: &Task, are_handler
     let hosts : HashMap<String, Arc<RwLock<Host>>> = run_state.context.read().unwrap().get_remaining_hosts();
     if hosts.len() == 0 { return Err(String::from("no hosts remaining")) }
 
-    // we will run tasks with the FSM only if not skipped by tags
-    let should_run = check_tags(run_state, task, role_invocation);
+    // blocks are containers of other tasks and never reach the FSM themselves. while looking for
+    // the --start-at-task task they are only entered if that task is inside of them.
+    // includes are not, as working out what they include means contacting every host, so
+    // --start-at-task can name an include but not a task inside the included file.
+    match task {
+        Task::Block(block) => { 
+            if is_before_start_task(run_state, task, are_handlers) && ! block_has_start_task(run_state, block) {
+                return Ok(());
+            }
+            return process_block(run_state, play, block, are_handlers, role_invocation); 
+        },
+        Task::Include(include) => { 
+            if is_before_start_task(run_state, task, are_handlers) {
+                return Ok(());
+            }
+            return process_include(run_state, play, task, include, are_handlers, role_invocation); 
+        },
+        // imports are replaced by their tasks as files are loaded, see expand_imports
+        Task::Import_Tasks(import) => { return Err(format!("import_tasks was not expanded: {}", import.file)); },
+        Task::Flush_Handlers(_) => { return flush_handlers(run_state, play, are_handlers, role_invocation); },
+        _ => {}
+    }
+
+    // we will run tasks with the FSM only if not skipped by tags, --start-at-task or --step
+    if is_before_start_task(run_state, task, are_handlers) {
+        return Ok(());
+    }
+    let should_run = check_tags(run_state, play, task, role_invocation) && confirm_step(run_state, task);
     if should_run {
         run_state.context.write().unwrap().set_task(&task);
         run_state.visitor.read().unwrap().on_task_start(&run_state.context, are_handlers);
@@ -278,6 +610,316 @@ fn process_task(run_state: &Arc<RunState>, play: &Play, task: &Task, are_handler
     return Ok(());
 }
 
+fn is_before_start_task(run_state: &Arc<RunState>, task: &Task, are_handlers: HandlerMode) -> bool {
+
+    // with --start-at-task, tasks are skipped until one has the requested name
+
+    let mut start_at_task = run_state.start_at_task.write().unwrap();
+    if start_at_task.is_none() || are_handlers == HandlerMode::Handlers {
+        return false;
+    }
+    if task.get_display_name().eq(start_at_task.as_ref().unwrap()) {
+        *start_at_task = None;
+        return false;
+    }
+    return true;
+}
+
+fn block_has_start_task(run_state: &Arc<RunState>, block: &Block) -> bool {
+    let start_at_task = run_state.start_at_task.read().unwrap();
+    if start_at_task.is_none() {
+        return false;
+    }
+    return block_has_task_named(block, start_at_task.as_ref().unwrap());
+}
+
+fn block_has_task_named(block: &Block, name: &String) -> bool {
+    let sections = [Some(&block.tasks), block.rescue.as_ref(), block.always.as_ref()];
+    return sections.iter().flatten().any(|tasks| tasks.iter().any(|task| match task {
+        Task::Block(inner) => task.get_display_name().eq(name) || block_has_task_named(inner, name),
+        _ => task.get_display_name().eq(name)
+    }));
+}
+
+fn confirm_step(run_state: &Arc<RunState>, task: &Task) -> bool {
+
+    // with --step, asks before each task. continuing runs the rest of the tasks without asking
+
+    if ! *run_state.step.read().unwrap() {
+        return true;
+    }
+    let choices = vec![("y","es"), ("n","o"), ("c","ontinue")];
+    let answer = prompt_choice(&format!("run task: {}?", task.get_display_name()), &choices);
+    return match answer.as_str() {
+        "n" => false,
+        "c" => { *run_state.step.write().unwrap() = false; true },
+        _ => true
+    };
+}
+
+fn process_block(run_state: &Arc<RunState>, play: &Play, block: &Block, are_handlers: HandlerMode, role_invocation: Option<&RoleInvocation>) -> Result<(), String> {
+
+    // hosts that fail in the block tasks are set aside by the context rather than failed. those hosts
+    // then run the rescue tasks, and if that works out they rejoin the others. every host that entered
+    // the block runs the always tasks, after which hosts that were not rescued are failed for real.
+
+    let has_rescue = block.rescue.is_some();
+    let has_always = block.always.is_some();
+    let block_name = block.get_display_name();
+    let protected = has_rescue || has_always;
+
+    run_state.visitor.read().unwrap().on_block_section(&run_state.context, &block_name, &String::from("tasks"));
+    if protected { run_state.context.write().unwrap().push_block(); }
+    let result = process_nested_tasks(run_state, play, &block.tasks, are_handlers, role_invocation);
+    let mut unresolved = match protected {
+        true => run_state.context.write().unwrap().pop_block(),
+        false => HashMap::new()
+    };
+    result?;
+
+    if has_rescue && ! unresolved.is_empty() {
+        let others = run_state.context.read().unwrap().get_remaining_hosts();
+        run_state.context.write().unwrap().set_remaining_hosts(&unresolved);
+        run_state.visitor.read().unwrap().on_block_section(&run_state.context, &block_name, &String::from("rescue"));
+        if has_always { run_state.context.write().unwrap().push_block(); }
+        let result = process_nested_tasks(run_state, play, block.rescue.as_ref().unwrap(), are_handlers, role_invocation);
+        unresolved = match has_always {
+            true => run_state.context.write().unwrap().pop_block(),
+            false => HashMap::new()
+        };
+        result?;
+        let mut rejoined = run_state.context.read().unwrap().get_remaining_hosts();
+        for (_, host) in rejoined.iter() {
+            run_state.context.write().unwrap().rescue_host(host);
+            run_state.visitor.read().unwrap().on_host_rescued(&run_state.context, host);
+        }
+        rejoined.extend(others);
+        run_state.context.write().unwrap().set_remaining_hosts(&rejoined);
+    }
+
+    if has_always {
+        let mut everyone = run_state.context.read().unwrap().get_remaining_hosts();
+        for (k, v) in unresolved.iter() { everyone.insert(k.clone(), Arc::clone(&v)); }
+        run_state.context.write().unwrap().set_remaining_hosts(&everyone);
+        run_state.visitor.read().unwrap().on_block_section(&run_state.context, &block_name, &String::from("always"));
+        process_nested_tasks(run_state, play, block.always.as_ref().unwrap(), are_handlers, role_invocation)?;
+    }
+
+    // anything still unresolved failed in the block and was not rescued. if this block is
+    // nested inside another protected block, that block gets to deal with it.
+    let remaining = run_state.context.read().unwrap().get_remaining_hosts();
+    for (k, host) in unresolved.iter() {
+        if ! has_always || remaining.contains_key(k) {
+            let nested = run_state.context.read().unwrap().in_block();
+            match nested {
+                true => { run_state.context.write().unwrap().set_aside_host(&host); },
+                false => {
+                    run_state.context.write().unwrap().fail_host(&host);
+                    run_state.visitor.read().unwrap().on_host_block_failed(&run_state.context, &host);
+                }
+            }
+        }
+    }
+    // those hosts count towards any_errors_fatal and max_fail_percentage like any task failure
+    return fsm_check_failure_limits(run_state, play);
+}
+
+fn process_nested_tasks(run_state: &Arc<RunState>, play: &Play, tasks: &Vec<Task>, are_handlers: HandlerMode, role_invocation: Option<&RoleInvocation>) -> Result<(), String> {
+
+    // used for the sections of blocks and for included files. unlike the top level of a play, 
+    // running out of hosts here is expected, for instance when they have all been set aside
+    // for the rescue or always tasks
+
+    for task in tasks.iter() {
+        if run_state.context.read().unwrap().get_remaining_hosts().is_empty() {
+            break;
+        }
+        process_task(run_state, play, task, are_handlers, role_invocation)?;
+    }
+    return Ok(());
+}
+
+fn process_include(run_state: &Arc<RunState>, play: &Play, task: &Task, include: &Include, are_handlers: HandlerMode, role_invocation: Option<&RoleInvocation>) -> Result<(), String> {
+
+    // every host works out which file(s) it is including, which may differ per host and per item. 
+    // the files are then run in rounds, one per item, with the hosts that picked the same file
+    // in that round running it together.
+
+    if ! check_tags(run_state, play, task, role_invocation) || ! confirm_step(run_state, task) {
+        return Ok(());
+    }
+    run_state.context.write().unwrap().set_task(&task);
+    run_state.visitor.read().unwrap().on_task_start(&run_state.context, are_handlers);
+    run_state.context.write().unwrap().increment_task_count();
+
+    let rescuable = run_state.context.read().unwrap().in_block();
+    let depth = run_state.context.read().unwrap().get_include_depth();
+    let hosts : HashMap<String, Arc<RwLock<Host>>> = run_state.context.read().unwrap().get_remaining_hosts();
+    let mut host_names : Vec<String> = hosts.keys().cloned().collect();
+    host_names.sort();
+
+    let mut plans : Vec<(Arc<RwLock<Host>>, Vec<(PathBuf, serde_yaml::Value)>)> = Vec::new();
+    let mut rounds : usize = 0;
+    for host_name in host_names.iter() {
+        let host = hosts.get(host_name).unwrap();
+        match fsm_resolve_include(run_state, host, include, are_handlers, rescuable, Strategy::Linear, depth) {
+            Some(files) => {
+                if files.len() > rounds { rounds = files.len(); }
+                plans.push((Arc::clone(&host), files.into_iter().map(|(path, item, _)| (path, item)).collect()));
+            },
+            None => {}
+        }
+    }
+
+    for round in 0..rounds {
+        let remaining = run_state.context.read().unwrap().get_remaining_hosts();
+        let mut groups : Vec<(PathBuf, HashMap<String, Arc<RwLock<Host>>>)> = Vec::new();
+        for (host, files) in plans.iter() {
+            let host_name = host.read().unwrap().name.clone();
+            if round >= files.len() || ! remaining.contains_key(&host_name) {
+                continue;
+            }
+            let (path, item) = &files[round];
+            set_loop_item(host, &include.get_loop_var(), item);
+            match groups.iter_mut().find(|(p, _)| p.eq(path)) {
+                Some((_, group)) => { group.insert(host_name, Arc::clone(&host)); },
+                None => {
+                    let mut group = HashMap::new();
+                    group.insert(host_name, Arc::clone(&host));
+                    groups.push((path.clone(), group));
+                }
+            }
+        }
+        for (path, group) in groups.iter() {
+            let tasks = load_tasks_file(&path)?;
+            let mut others = run_state.context.read().unwrap().get_remaining_hosts();
+            others.retain(|k, _| ! group.contains_key(k));
+            run_state.context.write().unwrap().set_remaining_hosts(&group);
+            run_state.context.write().unwrap().enter_include();
+            let result = process_nested_tasks(run_state, play, &tasks, are_handlers, role_invocation);
+            run_state.context.write().unwrap().leave_include();
+            let mut everyone = run_state.context.read().unwrap().get_remaining_hosts();
+            everyone.extend(others);
+            run_state.context.write().unwrap().set_remaining_hosts(&everyone);
+            result?;
+        }
+    }
+    return Ok(());
+}
+
+pub fn load_tasks_file(path: &Path) -> Result<Vec<Task>, String> {
+
+    // used for role task files and included task files, any import_tasks inside are
+    // resolved relative to the directory of the file itself
+
+    return load_tasks_file_at_depth(path, 0);
+}
+
+fn load_tasks_file_at_depth(path: &Path, depth: usize) -> Result<Vec<Task>, String> {
+    let task_fh = jet_file_open(&path)?;
+    let parsed: Result<Vec<Task>, serde_yaml::Error> = serde_yaml::from_reader(task_fh);
+    if parsed.is_err() {
+        show_yaml_error_in_context(&parsed.unwrap_err(), &path);
+        return Err(format!("edit the file and try again?"));
+    }
+    let base = match path.parent() {
+        Some(x) => x.to_path_buf(),
+        None => PathBuf::from(".")
+    };
+    return expand_imports(parsed.unwrap(), &base, depth);
+}
+
+fn expand_imports(tasks: Vec<Task>, base: &Path, depth: usize) -> Result<Vec<Task>, String> {
+
+    // replaces each import_tasks with the contents of the file, recursively
+
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(format!("import_tasks nested too deeply, is a file importing itself? (in {})", base.display()));
+    }
+    let mut results : Vec<Task> = Vec::new();
+    for task in tasks.into_iter() {
+        match task {
+            Task::Import_Tasks(import) => {
+                let mut path = PathBuf::new();
+                path.push(base);
+                path.push(&import.file);
+                results.extend(load_tasks_file_at_depth(&path.as_path(), depth + 1)?);
+            },
+            Task::Block(block) => {
+                results.push(Task::Block(Block {
+                    name: block.name,
+                    tasks: expand_imports(block.tasks, base, depth)?,
+                    rescue: match block.rescue {
+                        Some(x) => Some(expand_imports(x, base, depth)?),
+                        None => None
+                    },
+                    always: match block.always {
+                        Some(x) => Some(expand_imports(x, base, depth)?),
+                        None => None
+                    }
+                }));
+            },
+            _ => { results.push(task); }
+        }
+    }
+    return Ok(results);
+}
+
+fn expand_role_dependencies(run_state: &Arc<RunState>, play: &Play) -> Result<Vec<RoleInvocation>, String> {
+
+    // replaces the roles list of the play with one where each role is preceded by the roles it
+    // depends on, recursively. a dependency already running with the same vars is not run again,
+    // but roles listed in the play always run, even when listed more than once.
+
+    let mut results : Vec<RoleInvocation> = Vec::new();
+    for invocation in play.roles.as_ref().unwrap().iter() {
+        let mut chain : Vec<String> = Vec::new();
+        add_role_invocation(run_state, play, invocation.clone(), &mut chain, &mut results, false)?;
+    }
+    return Ok(results);
+}
+
+fn add_role_invocation(run_state: &Arc<RunState>, play: &Play, invocation: RoleInvocation, chain: &mut Vec<String>, results: &mut Vec<RoleInvocation>, is_dependency: bool) -> Result<(), String> {
+
+    // chain is the list of roles depending on this one, used to catch loops
+
+    if chain.contains(&invocation.role) {
+        return Err(format!("role dependency loop detected: {} -> {}", chain.join(" -> "), invocation.role));
+    }
+    let (role, _role_path) = find_role(run_state, play, invocation.role.clone())?;
+    chain.push(invocation.role.clone());
+    if role.dependencies.is_some() {
+        for dependency in role.dependencies.as_ref().unwrap().iter() {
+            // tags on a role apply to the roles it depends on
+            let mut dependency = dependency.clone();
+            dependency.tags = merge_tags(&dependency.tags, &invocation.tags);
+            add_role_invocation(run_state, play, dependency, chain, results, true)?;
+        }
+    }
+    chain.pop();
+    if ! is_dependency {
+        results.push(invocation);
+        return Ok(());
+    }
+    match results.iter_mut().find(|x| x.role.eq(&invocation.role) && x.vars.eq(&invocation.vars)) {
+        // already running, but it should still run for the tags of this invocation
+        Some(existing) => { existing.tags = merge_tags(&existing.tags, &invocation.tags); },
+        None => { results.push(invocation); }
+    }
+    return Ok(());
+}
+
+fn merge_tags(a: &Option<Vec<String>>, b: &Option<Vec<String>>) -> Option<Vec<String>> {
+    if a.is_none() { return b.clone(); }
+    let mut results = a.clone().unwrap();
+    if b.is_some() {
+        for x in b.as_ref().unwrap().iter() {
+            if ! results.contains(x) { results.push(x.clone()); }
+        }
+    }
+    return Some(results);
+}
+
 fn process_role(run_state: &Arc<RunState>, play: &Play, invocation: &RoleInvocation, are_handlers: HandlerMode) -> Result<(), String> {
 
     // traversal code for roles.  This is called twice, once for normal tasks and again when processing handler tasks.
@@ -291,12 +933,18 @@ fn process_role(run_state: &Arc<RunState>, play: &Play, invocation: &RoleInvocat
     {
         // we're good.
         let mut ctx = run_state.context.write().unwrap();
-        let str_path = directory_as_string(&role_path);
+        let str_path = path_as_string(&role_path);
         ctx.set_role(&role, invocation, &str_path);
         if are_handlers == HandlerMode::NormalTasks {
             ctx.increment_role_count();
         }
     }
+    if are_handlers == HandlerMode::NormalTasks {
+        validate_role_arguments(run_state, &role, &role_path)?;
+    }
+    // role vars may define more values for the play's secret variables
+    let remaining : Vec<Arc<RwLock<Host>>> = run_state.context.read().unwrap().get_remaining_hosts().values().cloned().collect();
+    register_secret_vars(run_state, play, &remaining);
     run_state.visitor.read().unwrap().on_role_start(&run_state.context);
 
     // roles contain two list of files to include, which one we're processing now
@@ -325,38 +973,16 @@ fn process_role(run_state: &Arc<RunState>, play: &Play, invocation: &RoleInvocat
 
             // find the likely path location, which is organized into subdirectories for relative paths
 
-            let task_buf = match task_file.starts_with("/") {
-                true => {
-                    Path::new(task_file).to_path_buf()
-                }
-                false => {
-                    let mut pb = PathBuf::new();
-                    pb.push(role_path.clone());
-                    match are_handlers {
-                        HandlerMode::NormalTasks => { pb.push("tasks"); },
-                        HandlerMode::Handlers    => { pb.push("handlers"); },
-                    };
-                    pb.push(task_file);
-                    pb
-                }
-            };
+            let task_buf = get_role_task_path(&role_path, task_file, are_handlers);
 
             // parse the YAML file
 
-            let task_fh = jet_file_open(&task_buf.as_path())?;
-            let parsed: Result<Vec<Task>, serde_yaml::Error> = serde_yaml::from_reader(task_fh);
-            if parsed.is_err() {
-                show_yaml_error_in_context(&parsed.unwrap_err(), &task_buf.as_path());
-                return Err(format!("edit the file and try again?"));
-            }   
-            let tasks = parsed.unwrap();
-            for task in tasks.iter() {
+            let tasks = load_tasks_file(&task_buf.as_path())?;
 
-                // process all tasks in the YAML file, this is the same function used
-                // for processing loose tasks outside of roles
+            // process all tasks in the YAML file, this is the same function used
+            // for processing loose tasks outside of roles
 
-                process_task(run_state, &play, &task, are_handlers, Some(invocation))?;
-            }
+            process_tasks(run_state, &play, &tasks, are_handlers, Some(invocation))?;
         }
 
         // we're done with the role so flip back to the previous directory
@@ -372,6 +998,24 @@ fn process_role(run_state: &Arc<RunState>, play: &Play, invocation: &RoleInvocat
 
 }
 
+pub fn get_role_task_path(role_path: &PathBuf, task_file: &String, are_handlers: HandlerMode) -> PathBuf {
+    return match task_file.starts_with("/") {
+        true => {
+            Path::new(task_file).to_path_buf()
+        }
+        false => {
+            let mut pb = PathBuf::new();
+            pb.push(role_path.clone());
+            match are_handlers {
+                HandlerMode::NormalTasks => { pb.push("tasks"); },
+                HandlerMode::Handlers    => { pb.push("handlers"); },
+            };
+            pb.push(task_file);
+            pb
+        }
+    };
+}
+
 fn get_host_batches(run_state: &Arc<RunState>, play: &Play, hosts: Vec<Arc<RwLock<Host>>>) 
     -> (usize, usize, HashMap<usize, Vec<Arc<RwLock<Host>>>>) {
 
@@ -425,7 +1069,7 @@ fn get_host_batches(run_state: &Arc<RunState>, play: &Play, hosts: Vec<Arc<RwLoc
 
 }
 
-fn get_play_hosts(run_state: &Arc<RunState>,play: &Play) -> Vec<Arc<RwLock<Host>>> {
+pub fn get_play_hosts(run_state: &Arc<RunState>,play: &Play) -> Vec<Arc<RwLock<Host>>> {
 
     // the hosts we want to talk to are the ones specified in the play but may
     // be further constrained by the parameters --limit-hosts and limit--groups
@@ -481,7 +1125,7 @@ fn get_play_hosts(run_state: &Arc<RunState>,play: &Play) -> Vec<Arc<RwLock<Host>
     return results.iter().map(|(_k,v)| Arc::clone(&v)).collect();
 }
 
-fn validate_limit_groups(run_state: &Arc<RunState>, _play: &Play) -> Result<(), String> {
+pub fn validate_limit_groups(run_state: &Arc<RunState>, _play: &Play) -> Result<(), String> {
 
     // limit groups on the command line can't mention any groups that aren't in inventory
 
@@ -495,7 +1139,7 @@ fn validate_limit_groups(run_state: &Arc<RunState>, _play: &Play) -> Result<(),
     return Ok(());
 }
 
-fn validate_limit_hosts(run_state: &Arc<RunState>, _play: &Play) -> Result<(), String> {
+pub fn validate_limit_hosts(run_state: &Arc<RunState>, _play: &Play) -> Result<(), String> {
 
     // limit hosts on the command line can't mention any hosts that aren't in inventory
 
@@ -509,7 +1153,7 @@ fn validate_limit_hosts(run_state: &Arc<RunState>, _play: &Play) -> Result<(), S
     return Ok(());
 }
 
-fn validate_groups(run_state: &Arc<RunState>, play: &Play) -> Result<(), String> {
+pub fn validate_groups(run_state: &Arc<RunState>, play: &Play) -> Result<(), String> {
 
     // groups on the play can't mention any groups that aren't in inventory
 
@@ -556,13 +1200,9 @@ fn load_vars_into_context(run_state: &Arc<RunState>, play: &Play) -> Result<(),
         let vars_files = play.vars_files.as_ref().unwrap();
         for pathname in vars_files {
             let path = Path::new(&pathname);
-            let vars_file = jet_file_open(&path)?;
-            let parsed: Result<serde_yaml::Mapping, serde_yaml::Error> = serde_yaml::from_reader(vars_file);
-            if parsed.is_err() {
-                show_yaml_error_in_context(&parsed.unwrap_err(), &path);
-                return Err(format!("edit the file and try again?"));
-            }
-            blend_variables(&mut ctx_vars_storage, serde_yaml::Value::Mapping(parsed.unwrap()));
+            // vars_files may be vault encrypted, see 'jetp vault'
+            let parsed = load_vars_file(&path, &run_state.vault_password)?;
+            blend_variables(&mut ctx_vars_storage, serde_yaml::Value::Mapping(parsed));
         }
     }
 
@@ -585,7 +1225,7 @@ fn load_vars_into_context(run_state: &Arc<RunState>, play: &Play) -> Result<(),
     return Ok(());
 }
 
-fn find_role(run_state: &Arc<RunState>, _play: &Play, role_name: String) -> Result<(Role,PathBuf), String> {
+pub fn find_role(run_state: &Arc<RunState>, _play: &Play, role_name: String) -> Result<(Role,PathBuf), String> {
 
     // when we need to find a role we look for it in the configured role paths
 
@@ -610,12 +1250,390 @@ fn find_role(run_state: &Arc<RunState>, _play: &Play, role_name: String) -> Resu
                 show_yaml_error_in_context(&parsed.unwrap_err(), &path);
                 return Err(format!("edit the file and try again?"));
             }   
-            let role = parsed.unwrap();
+            let mut role = parsed.unwrap();
+
+            // the defaults/ and vars/ directories add to the defaults and vars in role.yml
             
+            role.defaults = load_role_vars_directory(run_state, &pb, "defaults", role.defaults)?;
+            role.vars = load_role_vars_directory(run_state, &pb, "vars", role.vars)?;
+            role.defaults = add_argument_defaults(&role);
             return Ok((role,pb));
         }
     }
     return Err(format!("role not found: {}", role_name));
-}  
+}
 
+fn add_argument_defaults(role: &Role) -> Option<serde_yaml::Mapping> {
 
+    // defaults from the argument_spec only apply when the role does not set the variable itself
+
+    if role.argument_spec.is_none() {
+        return role.defaults.clone();
+    }
+    let mut defaults = role.defaults.clone().unwrap_or_default();
+    for argument in role.argument_spec.as_ref().unwrap().iter() {
+        let key = serde_yaml::Value::String(argument.name.clone());
+        if argument.default.is_some() && ! defaults.contains_key(&key) {
+            defaults.insert(key, argument.default.as_ref().unwrap().clone());
+        }
+    }
+    return Some(defaults);
+}
+
+fn register_secret_vars(run_state: &Arc<RunState>, play: &Play, hosts: &Vec<Arc<RwLock<Host>>>) {
+
+    // the variables named in 'secrets' are looked up as each host sees them, and their
+    // values are masked from all output. see util/secrets.rs
+
+    if play.secrets.is_none() {
+        return;
+    }
+    for host in hosts.iter() {
+        let vars = run_state.context.read().unwrap().get_complete_blended_variables(host, BlendTarget::NotTemplateModule);
+        for name in play.secrets.as_ref().unwrap().iter() {
+            let value = vars.get(&serde_yaml::Value::String(name.clone()));
+            if value.is_some() {
+                add_secret_values(value.unwrap());
+            }
+        }
+    }
+}
+
+fn validate_role_arguments(run_state: &Arc<RunState>, role: &Role, role_path: &PathBuf) -> Result<(), String> {
+
+    // arguments are checked against the variables each host will see in the role, as they may come
+    // from the invocation, the play or the inventory. the problems found are shown all at once,
+    // along with the hosts they apply to. templated values are checked once rendered, see
+    // render_role_argument.
+
+    if role.argument_spec.is_none() {
+        return Ok(());
+    }
+    let hosts = run_state.context.read().unwrap().get_remaining_hosts();
+    let mut host_names : Vec<String> = hosts.keys().cloned().collect();
+    host_names.sort();
+
+    let mut problems : Vec<(String, Vec<String>)> = Vec::new();
+    for host_name in host_names.iter() {
+        let host = hosts.get(host_name).unwrap();
+        let vars = run_state.context.read().unwrap().get_complete_blended_variables(host, BlendTarget::NotTemplateModule);
+        for argument in role.argument_spec.as_ref().unwrap().iter() {
+            let value = match vars.get(&serde_yaml::Value::String(argument.name.clone())) {
+                Some(serde_yaml::Value::String(x)) if x.contains("{{") => Some(render_role_argument(run_state, host, argument, x)),
+                x => x.cloned()
+            };
+            let problem = match check_role_argument(argument, value.as_ref()) {
+                Ok(_) => { continue; },
+                Err(y) => y
+            };
+            match problems.iter_mut().find(|(p, _)| p.eq(&problem)) {
+                Some((_, names)) => { names.push(host_name.clone()); },
+                None => { problems.push((problem, vec![host_name.clone()])); }
+            }
+        }
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    let mut role_file = role_path.clone();
+    role_file.push("role.yml");
+    let mut markdown_table = format!("|:-|\n|Invalid arguments for role {}, see argument_spec in {}|\n", role.name, role_file.display());
+    for (problem, names) in problems.iter() {
+        markdown_table.push_str(&format!("|-\n|{} (hosts: {})|\n", problem, names.join(", ")));
+    }
+    markdown_table.push_str("|-");
+    println!("");
+    markdown_print(&markdown_table);
+    return Err(format!("edit the role arguments and try again?"));
+}
+
+fn render_role_argument(run_state: &Arc<RunState>, host: &Arc<RwLock<Host>>, argument: &RoleArgument, template: &String) -> serde_yaml::Value {
+
+    // a templated value is rendered with what the host knows so far. if that is not enough, as when
+    // it uses a variable set later on, it is left unchecked and the task using it will report it.
+    // lists and mappings do not survive rendering into a string, so those are never checked.
+
+    let rendered = match run_state.context.read().unwrap().render_template(template, host, BlendTarget::NotTemplateModule, TemplateMode::Strict) {
+        Ok(x) => x,
+        Err(_) => { return serde_yaml::Value::String(template.clone()); }
+    };
+    return rendered_role_argument(argument, template, rendered);
+}
+
+fn rendered_role_argument(argument: &RoleArgument, template: &String, rendered: String) -> serde_yaml::Value {
+    return match argument.argument_type.as_deref() {
+        // numbers and booleans are read the way they would be if written in YAML
+        None | Some("int") | Some("float") | Some("bool") => match serde_yaml::from_str(&rendered) {
+            Ok(serde_yaml::Value::Number(x)) => serde_yaml::Value::Number(x),
+            Ok(serde_yaml::Value::Bool(x)) => serde_yaml::Value::Bool(x),
+            _ => serde_yaml::Value::String(rendered)
+        },
+        Some("list") | Some("dict") | Some("any") => serde_yaml::Value::String(template.clone()),
+        _ => serde_yaml::Value::String(rendered)
+    };
+}
+
+fn check_role_argument(argument: &RoleArgument, value: Option<&serde_yaml::Value>) -> Result<(), String> {
+    if argument.argument_type.is_some() && ! ROLE_ARGUMENT_TYPES.contains(&argument.argument_type.as_ref().unwrap().as_str()) {
+        return Err(format!("{} has an unknown type: {}, expected one of: {}", argument.name, argument.argument_type.as_ref().unwrap(), ROLE_ARGUMENT_TYPES.join(", ")));
+    }
+    if value.is_none() || value.unwrap().is_null() {
+        return match argument.required.is_some() && argument.required.unwrap() {
+            true => Err(format!("{} is required", argument.name)),
+            false => Ok(())
+        };
+    }
+    let value = value.unwrap();
+    if argument.argument_type.is_some() {
+        let expected = argument.argument_type.as_ref().unwrap();
+        let ok = match (expected.as_str(), value) {
+            // templated values that could not be rendered yet, see render_role_argument
+            (_, serde_yaml::Value::String(x)) if x.contains("{{") => true,
+            ("any", _)                               => true,
+            ("str", serde_yaml::Value::String(_))    => true,
+            ("int", serde_yaml::Value::Number(x))    => x.is_i64() || x.is_u64(),
+            ("float", serde_yaml::Value::Number(_))  => true,
+            ("bool", serde_yaml::Value::Bool(_))     => true,
+            ("list", serde_yaml::Value::Sequence(_)) => true,
+            ("dict", serde_yaml::Value::Mapping(_))  => true,
+            _ => false
+        };
+        if ! ok {
+            return Err(format!("{} must be of type {}, got: {}", argument.name, expected, show_value(value)));
+        }
+    }
+    if argument.choices.is_some() && ! argument.choices.as_ref().unwrap().contains(value) {
+        let choices : Vec<String> = argument.choices.as_ref().unwrap().iter().map(|x| show_value(x)).collect();
+        return Err(format!("{} must be one of: {}, got: {}", argument.name, choices.join(", "), show_value(value)));
+    }
+    return Ok(());
+}
+
+fn show_value(value: &serde_yaml::Value) -> String {
+    return serde_json::to_string(value).unwrap_or_default();
+}
+
+fn load_role_vars_directory(run_state: &Arc<RunState>, role_path: &PathBuf, subdir: &str, inline: Option<serde_yaml::Mapping>) -> Result<Option<serde_yaml::Mapping>, String> {
+
+    // every YAML file in the directory is loaded in name order, later files winning
+
+    let mut dir = role_path.clone();
+    dir.push(subdir);
+    if ! dir.is_dir() {
+        return Ok(inline);
+    }
+    let mut paths : Vec<PathBuf> = Vec::new();
+    path_walk(&dir, |vars_path| {
+        let name = path_basename_as_string(&vars_path);
+        // skip dot files and backup files
+        if name.starts_with(".") || ! (name.ends_with(".yml") || name.ends_with(".yaml")) {
+            return Ok(());
+        }
+        paths.push(vars_path.to_path_buf());
+        return Ok(());
+    })?;
+    paths.sort();
+
+    let mut blended = serde_yaml::Value::from(inline.unwrap_or_default());
+    for path in paths.iter() {
+        let parsed = load_vars_file(&path, &run_state.vault_password)?;
+        blend_variables(&mut blended, serde_yaml::Value::Mapping(parsed));
+    }
+    return match blended {
+        serde_yaml::Value::Mapping(x) => Ok(Some(x)),
+        _ => panic!("unexpected, get_blended_variables produced a non-mapping (5)")
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(x: &[&str]) -> Vec<String> {
+        return x.iter().map(|y| String::from(*y)).collect();
+    }
+
+    fn cli(x: Option<&[&str]>) -> Option<Vec<String>> {
+        return x.map(|y| tags(y));
+    }
+
+    #[test]
+    fn test_tags_selected() {
+        // (--tags, --skip-tags, task tags, selected)
+        let cases : Vec<(Option<&[&str]>, Option<&[&str]>, &[&str], bool)> = vec![
+            // no tags on the command line runs everything but 'never'
+            (None,                     None,                  &[],                  true),
+            (None,                     None,                  &["web"],             true),
+            (None,                     None,                  &["always"],          true),
+            (None,                     None,                  &["never"],           false),
+            (None,                     None,                  &["never", "debug"],  false),
+            // --tags selects tasks by any of their tags
+            (Some(&["web"]),           None,                  &["web"],             true),
+            (Some(&["web"]),           None,                  &["db", "web"],       true),
+            (Some(&["web"]),           None,                  &["db"],              false),
+            (Some(&["web"]),           None,                  &[],                  false),
+            // 'always' runs whatever --tags says
+            (Some(&["web"]),           None,                  &["always"],          true),
+            (Some(&["always"]),        None,                  &["web"],             false),
+            // 'never' runs only when asked for
+            (Some(&["never"]),         None,                  &["never"],           true),
+            (Some(&["debug"]),         None,                  &["never", "debug"],  true),
+            (Some(&["web"]),           None,                  &["never", "debug"],  false),
+            // 'all' selects everything except 'never'
+            (Some(&["all"]),           None,                  &[],                  true),
+            (Some(&["all"]),           None,                  &["db"],              true),
+            (Some(&["all"]),           None,                  &["never"],           false),
+            (Some(&["all", "debug"]),  None,                  &["never", "debug"],  true),
+            // --skip-tags wins over everything
+            (None,                     Some(&["db"]),         &["db"],              false),
+            (None,                     Some(&["db"]),         &["web"],             true),
+            (None,                     Some(&["db"]),         &[],                  true),
+            (Some(&["web"]),           Some(&["web"]),        &["web"],             false),
+            (Some(&["web"]),           Some(&["db"]),         &["db", "web"],       false),
+            (Some(&["web"]),           Some(&["always"]),     &["always"],          false),
+            (Some(&["all"]),           Some(&["db"]),         &["db"],              false),
+            (Some(&["never"]),         Some(&["never"]),      &["never"],           false),
+        ];
+        for (cli_tags, cli_skip_tags, task_tags, expected) in cases.iter() {
+            assert_eq!(
+                tags_selected(&cli(*cli_tags), &cli(*cli_skip_tags), &tags(task_tags)),
+                *expected,
+                "--tags {:?} --skip-tags {:?} task tags {:?}", cli_tags, cli_skip_tags, task_tags
+            );
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        return env::temp_dir().join(format!("jet-traversal-{}-{}", name, guid_create::GUID::rand()));
+    }
+
+    fn task_names(tasks: &Vec<Task>) -> Vec<String> {
+        return tasks.iter().map(|x| x.get_display_name()).collect();
+    }
+
+    #[test]
+    fn test_import_tasks_nested() {
+        // each file's imports are relative to that file, including inside blocks
+        let dir = temp_path("imports");
+        fs::create_dir_all(dir.join("tasks/more")).unwrap();
+        fs::write(dir.join("main.yml"), "- !echo\n  name: one\n  msg: x\n- !import_tasks\n  file: tasks/outer.yml\n").unwrap();
+        fs::write(dir.join("tasks/outer.yml"), "- !echo\n  name: two\n  msg: x\n- !block\n  tasks:\n    - !import_tasks\n      file: more/inner.yml\n").unwrap();
+        fs::write(dir.join("tasks/more/inner.yml"), "- !echo\n  name: three\n  msg: x\n").unwrap();
+        let tasks = load_tasks_file(&dir.join("main.yml"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let tasks = tasks.unwrap();
+        assert_eq!(task_names(&tasks), vec!["one", "two", "block"]);
+        match &tasks[2] {
+            Task::Block(block) => { assert_eq!(task_names(&block.tasks), vec!["three"]); },
+            _ => panic!("expected a block")
+        }
+    }
+
+    #[test]
+    fn test_import_tasks_loop() {
+        let dir = temp_path("import-loop");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.yml"), "- !import_tasks\n  file: b.yml\n").unwrap();
+        fs::write(dir.join("b.yml"), "- !import_tasks\n  file: a.yml\n").unwrap();
+        let result = load_tasks_file(&dir.join("a.yml"));
+        let missing = load_tasks_file(&dir.join("missing.yml"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.unwrap_err().contains("import_tasks nested too deeply"));
+        assert!(missing.is_err());
+    }
+
+    fn argument(spec: &str) -> RoleArgument {
+        return serde_yaml::from_str(spec).unwrap();
+    }
+
+    fn value(yaml: &str) -> serde_yaml::Value {
+        return serde_yaml::from_str(yaml).unwrap();
+    }
+
+    #[test]
+    fn test_check_role_argument() {
+        // (argument spec, value or None if unset, expected problem or "" if valid)
+        let cases : Vec<(&str, Option<&str>, &str)> = vec![
+            ("{ name: a }",                        Some("x"),          ""),
+            ("{ name: a }",                        None,               ""),
+            ("{ name: a, type: any }",             Some("[1, x]"),     ""),
+            ("{ name: a, type: str }",             Some("x"),          ""),
+            ("{ name: a, type: str }",             Some("'80'"),       ""),
+            ("{ name: a, type: str }",             Some("80"),         "a must be of type str, got: 80"),
+            ("{ name: a, type: int }",             Some("80"),         ""),
+            ("{ name: a, type: int }",             Some("-1"),         ""),
+            ("{ name: a, type: int }",             Some("1.5"),        "a must be of type int, got: 1.5"),
+            ("{ name: a, type: int }",             Some("'80'"),       "a must be of type int, got: \"80\""),
+            ("{ name: a, type: float }",           Some("1.5"),        ""),
+            ("{ name: a, type: float }",           Some("2"),          ""),
+            ("{ name: a, type: float }",           Some("x"),          "a must be of type float, got: \"x\""),
+            ("{ name: a, type: bool }",            Some("true"),       ""),
+            ("{ name: a, type: bool }",            Some("'yes'"),      "a must be of type bool, got: \"yes\""),
+            ("{ name: a, type: list }",            Some("[1, 2]"),     ""),
+            ("{ name: a, type: list }",            Some("{ b: 1 }"),   "a must be of type list, got: {\"b\":1}"),
+            ("{ name: a, type: dict }",            Some("{ b: 1 }"),   ""),
+            ("{ name: a, type: dict }",            Some("[1, 2]"),     "a must be of type dict, got: [1,2]"),
+            ("{ name: a, type: integer }",         Some("80"),         "a has an unknown type: integer"),
+            ("{ name: a, type: integer }",         None,               "a has an unknown type: integer"),
+            // unset and null are the same, and only a problem if required
+            ("{ name: a, required: true }",        None,               "a is required"),
+            ("{ name: a, required: true }",        Some("null"),       "a is required"),
+            ("{ name: a, required: false }",       None,               ""),
+            ("{ name: a, required: true, type: int }", Some("0"),      ""),
+            // choices compare the values as written, types included
+            ("{ name: a, choices: [x, y] }",       Some("y"),          ""),
+            ("{ name: a, choices: [x, y] }",       Some("z"),          "a must be one of: \"x\", \"y\", got: \"z\""),
+            ("{ name: a, choices: [80, 443] }",    Some("'80'"),       "a must be one of: 80, 443, got: \"80\""),
+            ("{ name: a, type: int, choices: [80, 443] }", Some("x"),  "a must be of type int"),
+            ("{ name: a, choices: [x] }",          None,               ""),
+            // templates that could not be rendered are left to the task using them
+            ("{ name: a, type: int }",             Some("'{{ x }}'"),  ""),
+            ("{ name: a, type: int, choices: [1] }", Some("'{{ x }}'"), "a must be one of: 1, got: \"{{ x }}\""),
+        ];
+        for (spec, input, expected) in cases.iter() {
+            let input = input.map(|x| value(x));
+            let result = check_role_argument(&argument(spec), input.as_ref());
+            match *expected {
+                "" => assert!(result.is_ok(), "{} {:?}: {}", spec, input, result.unwrap_err()),
+                _ => {
+                    let err = result.expect_err(&format!("{} {:?} should not be valid", spec, input));
+                    assert!(err.starts_with(expected), "{} {:?}: {}", spec, input, err);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rendered_role_argument() {
+        // (argument spec, rendered template, value checked)
+        let cases = vec![
+            ("{ name: a }",                "80",      "80"),
+            ("{ name: a }",                "x",       "x"),
+            ("{ name: a, type: str }",     "80",      "'80'"),
+            ("{ name: a, type: int }",     "80",      "80"),
+            ("{ name: a, type: int }",     "x",       "x"),
+            ("{ name: a, type: int }",     "[1]",     "'[1]'"),
+            ("{ name: a, type: float }",   "1.5",     "1.5"),
+            ("{ name: a, type: bool }",    "false",   "false"),
+            ("{ name: a, type: bool }",    "",        "''"),
+            ("{ name: a, type: list }",    "[1]",     "'{{ a }}'"),
+            ("{ name: a, type: dict }",    "x",       "'{{ a }}'"),
+            ("{ name: a, type: any }",     "x",       "'{{ a }}'"),
+        ];
+        for (spec, rendered, expected) in cases.iter() {
+            let result = rendered_role_argument(&argument(spec), &String::from("{{ a }}"), String::from(*rendered));
+            assert_eq!(result, value(expected), "{} {}", spec, rendered);
+        }
+    }
+
+    #[test]
+    fn test_role_argument_unknown_keys() {
+        let result : Result<RoleArgument, serde_yaml::Error> = serde_yaml::from_str("{ name: a, typ: int }");
+        assert!(result.unwrap_err().to_string().contains("unknown field `typ`"));
+        let result : Result<RoleArgument, serde_yaml::Error> = serde_yaml::from_str("{ type: int }");
+        assert!(result.unwrap_err().to_string().contains("missing field `name`"));
+    }
+}
@@ -30,6 +30,11 @@ use serde_json::json;
 use guid_create::GUID;
 use chrono::prelude::*;
 use std::env;
+use std::path::PathBuf;
+use similar::TextDiff;
+use crate::playbooks::report::RunReport;
+use crate::util::secrets::{redact,NO_LOG_MESSAGE};
+use std::collections::HashSet;
 
 // visitor contains various functions that are called from all over the program
 // to send feedback to the user and logs
@@ -44,7 +49,9 @@ pub struct PlaybookVisitor {
     pub check_mode: CheckMode,
     pub logfile: Option<Arc<RwLock<File>>>,
     pub run_id: String,
-    pub utc_start: DateTime<Utc>
+    pub utc_start: DateTime<Utc>,
+    pub report: Option<RwLock<RunReport>>,
+    no_log_hosts: RwLock<HashSet<String>>
 }
 
 pub struct LogData {
@@ -59,12 +66,15 @@ pub struct LogData {
     pub cmd_out: Option<String>,
     pub task_status: Option<String>,
     pub host: Option<String>,
+    pub item: Option<String>,
+    pub changes: Option<Vec<String>>,
+    pub diff: Option<String>,
     pub summary: Option<serde_json::map::Map<String,serde_json::Value>>
 }
 
 impl PlaybookVisitor {
 
-    pub fn new(check_mode: CheckMode) -> Self {
+    pub fn new(check_mode: CheckMode, report_path: &Option<String>) -> Self {
 
         let logpath : String = match env::var("JET_LOG") {
             Ok(x) => {
@@ -83,7 +93,12 @@ impl PlaybookVisitor {
             check_mode: check_mode,
             logfile: logfile,
             utc_start: Utc::now(),
-            run_id: GUID::rand().to_string()
+            run_id: GUID::rand().to_string(),
+            report: match report_path {
+                Some(x) => Some(RwLock::new(RunReport::new(x))),
+                None => None
+            },
+            no_log_hosts: RwLock::new(HashSet::new())
         };
         s
     }
@@ -111,16 +126,39 @@ impl PlaybookVisitor {
             cmd_out: None,
             task_status: None,
             host: None,
+            item: None,
+            changes: None,
+            diff: None,
             summary: None
         }
     }
 
+    // events about a specific host, which under the 'free' strategy may not be on the
+    // same task as the rest of the play
+
+    pub fn host_log_entry(&self, event: &String, context: &Arc<RwLock<PlaybookContext>>, host_name: &String) -> LogData {
+        let mut log_entry = self.log_entry(event, Arc::clone(context));
+        log_entry.host = Some(host_name.clone());
+        let host_task = context.read().unwrap().get_host_task(host_name);
+        if host_task.is_some() {
+            let (task_ct, task) = host_task.unwrap();
+            log_entry.task = Some(task);
+            log_entry.task_ct = Some(task_ct);
+        }
+        return log_entry;
+    }
+
     pub fn log(&self, log: &LogData) {
 
         if self.logfile.is_none() {
             return;
         }
 
+        let scrub = |x: &String| match &log.host {
+            Some(host_name) => self.scrub(host_name, x),
+            None => redact(x)
+        };
+
         let now = Utc::now();
 
         let mut obj =  serde_json::map::Map::new();
@@ -136,11 +174,14 @@ impl PlaybookVisitor {
         if log.role.is_some()        { obj.insert(String::from("role"),        json!(log.role.clone().unwrap()));          }
         if log.task.is_some()        { obj.insert(String::from("task"),        json!(log.task.clone().unwrap()));          }
         if log.task.is_some()        { obj.insert(String::from("task_ct"),     json!(log.task_ct.clone().unwrap()));       }
-        if log.cmd.is_some()         { obj.insert(String::from("cmd"),         json!(log.cmd.clone().unwrap()));           }
+        if log.cmd.is_some()         { obj.insert(String::from("cmd"),         json!(scrub(log.cmd.as_ref().unwrap())));   }
         if log.cmd_rc.is_some()      { obj.insert(String::from("cmd_rc"),      json!(log.cmd_rc.clone().unwrap()));        }
-        if log.cmd_out.is_some()     { obj.insert(String::from("cmd_out"),     json!(log.cmd_out.clone().unwrap()));       }
+        if log.cmd_out.is_some()     { obj.insert(String::from("cmd_out"),     json!(scrub(log.cmd_out.as_ref().unwrap()))); }
         if log.task_status.is_some() { obj.insert(String::from("task_status"), json!(log.task_status.clone().unwrap()));   }
         if log.host.is_some()        { obj.insert(String::from("host"),        json!(log.host.clone().unwrap()));          }
+        if log.item.is_some()        { obj.insert(String::from("item"),        json!(scrub(log.item.as_ref().unwrap())));  }
+        if log.changes.is_some()     { obj.insert(String::from("changes"),     json!(log.changes.clone().unwrap()));       }
+        if log.diff.is_some()        { obj.insert(String::from("diff"),        json!(scrub(log.diff.as_ref().unwrap())));  }
         
         if log.summary.is_some()     { obj.insert(String::from("summary"),     json!(log.summary.clone().unwrap()));       }
 
@@ -159,6 +200,38 @@ impl PlaybookVisitor {
 
     }
 
+    // in addition to the JET_LOG file, per-host task results are kept for --report
+
+    fn record(&self, log: &LogData, task_response: Option<&Arc<TaskResponse>>, failed: bool) {
+        if self.report.is_some() {
+            let hidden = log.host.as_ref().map(|x| self.is_no_log(x)).unwrap_or(false);
+            self.report.as_ref().unwrap().write().unwrap().record(log, task_response, failed, hidden);
+        }
+    }
+
+    // a task with beforetask/no_log hides its commands and output while the host runs it.
+    // secret variable values are masked everywhere, see util/secrets.rs
+
+    pub fn set_no_log(&self, host: &Arc<RwLock<Host>>, no_log: bool) {
+        let host_name = host.read().unwrap().name.clone();
+        let mut no_log_hosts = self.no_log_hosts.write().unwrap();
+        match no_log {
+            true  => { no_log_hosts.insert(host_name); },
+            false => { no_log_hosts.remove(&host_name); }
+        }
+    }
+
+    fn is_no_log(&self, host_name: &String) -> bool {
+        return self.no_log_hosts.read().unwrap().contains(host_name);
+    }
+
+    fn scrub(&self, host_name: &String, text: &String) -> String {
+        if self.is_no_log(host_name) {
+            return String::from(NO_LOG_MESSAGE);
+        }
+        return redact(text);
+    }
+
     pub fn is_check_mode(&self) -> bool { 
         return self.check_mode == CheckMode::Yes; 
     }
@@ -169,7 +242,8 @@ impl PlaybookVisitor {
 
     // used by the echo module
     pub fn debug_host(&self, host: &Arc<RwLock<Host>>, message: &String) {
-        println!("{color_cyan}  ..... {} : {}{color_reset}", host.read().unwrap().name, message);
+        let host_name = host.read().unwrap().name.clone();
+        println!("{color_cyan}  ..... {} : {}{color_reset}", host_name, self.scrub(&host_name, message));
     }
 
     pub fn on_playbook_start(&self, context: &Arc<RwLock<PlaybookContext>>) {
@@ -218,6 +292,19 @@ impl PlaybookVisitor {
         println!("----------------------------------------------------------");
         println!("");
         self.show_playbook_summary(context);
+        self.write_report(context);
+    }
+
+    fn write_report(&self, context: &Arc<RwLock<PlaybookContext>>) {
+        if self.report.is_none() {
+            return;
+        }
+        let summary = self.get_summary_map(context);
+        let report = self.report.as_ref().unwrap().read().unwrap();
+        match report.write(&self.run_id, &self.utc_start, self.is_check_mode(), &summary) {
+            Ok(_) => {},
+            Err(y) => { println!("{color_red}! {}{color_reset}", y); }
+        }
     }
 
     pub fn on_task_start(&self, context: &Arc<RwLock<PlaybookContext>>, is_handler: HandlerMode) {
@@ -242,14 +329,34 @@ impl PlaybookVisitor {
         self.log(&log_entry);
     }
 
+    // with the 'free' strategy there is no play-wide task banner, each host announces its own tasks
+
+    pub fn on_host_task_begin(&self, context: &Arc<RwLock<PlaybookContext>>, host: &Arc<RwLock<Host>>, is_handler: HandlerMode) {
+        let host2 = host.read().unwrap();
+        let log_entry = self.host_log_entry(&String::from("TASK_START"), context, &host2.name);
+        let what = match is_handler {
+            HandlerMode::NormalTasks => String::from("task"),
+            HandlerMode::Handlers    => String::from("handler")
+        };
+        match &log_entry.role {
+            None    => println!("> {} => begin {}: {}", host2.name, what, log_entry.task.as_ref().unwrap()),
+            Some(x) => println!("> ({}) {} => begin {}: {}", x, host2.name, what, log_entry.task.as_ref().unwrap())
+        }
+        self.log(&log_entry);
+    }
+
     pub fn on_batch(&self, batch_num: usize, batch_count: usize, batch_size: usize) {
         self.banner();
         println!("> batch {}/{}, {} hosts", batch_num+1, batch_count, batch_size);
     }
 
-    pub fn on_host_task_start(&self, _context: &Arc<RwLock<PlaybookContext>>, host: &Arc<RwLock<Host>>) {
+    pub fn on_host_task_start(&self, context: &Arc<RwLock<PlaybookContext>>, host: &Arc<RwLock<Host>>) {
         let host2 = host.read().unwrap();
         println!("… {} => running", host2.name);
+        if self.report.is_some() {
+            let log_entry = self.host_log_entry(&String::from("TASK_START"), context, &host2.name);
+            self.report.as_ref().unwrap().write().unwrap().start_host_task(&host2.name, log_entry.task_ct);
+        }
     }
 
     pub fn on_notify_handler(&self, host: &Arc<RwLock<Host>>, which_handler: &String) {
@@ -307,10 +414,12 @@ impl PlaybookVisitor {
             }
         }
 
-        let mut log_entry = self.log_entry(&String::from("TASK_STATUS"), Arc::clone(context));
-        log_entry.host = Some(host2.name.clone());
+        let mut log_entry = self.host_log_entry(&String::from("TASK_STATUS"), context, &host2.name);
         log_entry.task_status = Some(format!("{:?}", &task_response.status));
+        log_entry.changes = Some(task_response.changes.iter().map(|x| { format!("{:?}", x) }).collect());
         self.log(&log_entry);
+        // a failure reaching here was ignored with ignore_errors, the report counts it as passed
+        self.record(&log_entry, Some(task_response), false);
 
     }
 
@@ -360,10 +469,12 @@ impl PlaybookVisitor {
             }
         }
 
-        let mut log_entry = self.log_entry(&String::from("TASK_CHECK_STATUS"), Arc::clone(context));
-        log_entry.host = Some(host2.name.clone());
+        let mut log_entry = self.host_log_entry(&String::from("TASK_CHECK_STATUS"), context, &host2.name);
         log_entry.task_status = Some(format!("{:?}", &task_response.status));
+        log_entry.changes = Some(task_response.changes.iter().map(|x| { format!("{:?}", x) }).collect());
         self.log(&log_entry);
+        // a failure reaching here was ignored with ignore_errors, the report counts it as passed
+        self.record(&log_entry, Some(task_response), false);
     }
 
     pub fn on_host_task_retry(&self, _context: &Arc<RwLock<PlaybookContext>>,host: &Arc<RwLock<Host>>, retries: u64, delay: u64) {
@@ -372,42 +483,135 @@ impl PlaybookVisitor {
     }
 
     pub fn on_host_task_failed(&self, context: &Arc<RwLock<PlaybookContext>>, task_response: &Arc<TaskResponse>, host: &Arc<RwLock<Host>>) {
-        let mut log_entry = self.log_entry(&String::from("TASK_FAILED"), Arc::clone(context));
         let host2 = host.read().unwrap();
+        let mut log_entry = self.host_log_entry(&String::from("TASK_FAILED"), context, &host2.name);
+        self.show_task_failure(context, task_response, &host2.name, &mut log_entry, color_red);
+        context.write().unwrap().increment_failed_for_host(&host2.name);
+        log_entry.task_status = Some(format!("{:?}", &task_response.status));
+        self.log(&log_entry);
+        self.record(&log_entry, Some(task_response), true);
+    }
+
+    // a failure inside a block with rescue or always tasks, the host does not fail (yet)
+
+    pub fn on_host_task_rescuable(&self, context: &Arc<RwLock<PlaybookContext>>, task_response: &Arc<TaskResponse>, host: &Arc<RwLock<Host>>) {
+        let host2 = host.read().unwrap();
+        let mut log_entry = self.host_log_entry(&String::from("TASK_FAILED_IN_BLOCK"), context, &host2.name);
+        self.show_task_failure(context, task_response, &host2.name, &mut log_entry, color_yellow);
+        log_entry.task_status = Some(format!("{:?}", &task_response.status));
+        self.log(&log_entry);
+        self.record(&log_entry, Some(task_response), false);
+    }
+
+    fn show_task_failure(&self, context: &Arc<RwLock<PlaybookContext>>, task_response: &Arc<TaskResponse>, host_name: &String, log_entry: &mut LogData, color: &str) {
         if task_response.msg.is_some() {
             let msg = &task_response.msg;
             if task_response.command_result.is_some() {
                 {
                     let cmd_result = task_response.command_result.as_ref().as_ref().unwrap();
                     let _lock = context.write().unwrap();
-                    println!("{color_red}! {} => failed", host2.name);
-                    println!("    cmd: {}", cmd_result.cmd);
-                    println!("    out: {}", cmd_result.out);
+                    println!("{}! {} => failed", color, host_name);
+                    println!("    cmd: {}", self.scrub(host_name, &cmd_result.cmd));
+                    println!("    out: {}", self.scrub(host_name, &cmd_result.out));
                     println!("    rc: {}{color_reset}", cmd_result.rc);
                     log_entry.cmd     = Some(cmd_result.cmd.clone());
                     log_entry.cmd_out = Some(cmd_result.out.clone());
                     log_entry.cmd_rc  = Some(cmd_result.rc.clone());
                 }
             } else {
-                println!("{color_red}! error: {}: {}{color_reset}", host2.name, msg.as_ref().unwrap());
+                println!("{}! error: {}: {}{color_reset}", color, host_name, self.scrub(host_name, msg.as_ref().unwrap()));
             }
         } else {
-            println!("{color_red}! host failed: {}, {color_reset}", host2.name);
+            println!("{}! host failed: {}, {color_reset}", color, host_name);
         }
+    }
 
-        context.write().unwrap().increment_failed_for_host(&host2.name);
-        log_entry.host = Some(host2.name.clone());
+    // blocks print which section is running, in the free strategy this is per host
+
+    pub fn on_block_section(&self, context: &Arc<RwLock<PlaybookContext>>, block_name: &String, section: &String) {
+        self.banner();
+        let ctx = context.read().unwrap();
+        match &ctx.role {
+            None    => println!("> block: {} ({})", block_name, section),
+            Some(x) => println!("> ({}) block: {} ({})", x.name, block_name, section)
+        }
+    }
+
+    pub fn on_host_block_section(&self, _context: &Arc<RwLock<PlaybookContext>>, host: &Arc<RwLock<Host>>, block_name: &String, section: &String) {
+        let host2 = host.read().unwrap();
+        println!("> {} => block: {} ({})", host2.name, block_name, section);
+    }
+
+    // each item of a loop is shown as it finishes, the task as a whole is then reported as usual
+    // with the combined result
+
+    pub fn on_host_task_item_result(&self, context: &Arc<RwLock<PlaybookContext>>, host: &Arc<RwLock<Host>>, label: &String, task_response: &Arc<TaskResponse>) {
+        let host2 = host.read().unwrap();
+        let changes : Vec<String> = task_response.changes.iter().map(|x| { format!("{:?}", x) }).collect();
+        let status = match &task_response.status {
+            TaskStatus::IsCreated         => "created",
+            TaskStatus::IsRemoved         => "removed",
+            TaskStatus::IsModified        => "modified",
+            TaskStatus::IsExecuted        => "complete",
+            TaskStatus::IsPassive         => "ok",
+            TaskStatus::IsMatched         => "matched",
+            TaskStatus::IsSkipped         => "skipped",
+            TaskStatus::NeedsCreation     => "would create",
+            TaskStatus::NeedsRemoval      => "would remove",
+            TaskStatus::NeedsModification => "would modify",
+            TaskStatus::NeedsExecution    => "would run",
+            TaskStatus::NeedsPassive      => "ok",
+            TaskStatus::Failed            => "failed"
+        };
+        let label2 = self.scrub(&host2.name, label);
+        match changes.is_empty() {
+            true  => println!("… {} => item: {} => {}", host2.name, label2, status),
+            false => println!("… {} => item: {} => {} ({})", host2.name, label2, status, changes.join(","))
+        };
+        let mut log_entry = self.host_log_entry(&String::from("TASK_ITEM_STATUS"), context, &host2.name);
+        log_entry.item = Some(label.clone());
         log_entry.task_status = Some(format!("{:?}", &task_response.status));
+        log_entry.changes = Some(changes);
+        self.log(&log_entry);
+    }
+
+    pub fn on_flush_handlers(&self, context: &Arc<RwLock<PlaybookContext>>) {
+        self.banner();
+        println!("> flushing handlers");
+        let log_entry = self.log_entry(&String::from("FLUSH_HANDLERS"), context.clone());
+        self.log(&log_entry);
+    }
+
+    pub fn on_host_include(&self, context: &Arc<RwLock<PlaybookContext>>, host: &Arc<RwLock<Host>>, path: &PathBuf) {
+        let host2 = host.read().unwrap();
+        println!("… {} => including: {}", host2.name, path.display());
+        let log_entry = self.host_log_entry(&String::from("HOST_INCLUDE"), context, &host2.name);
+        self.log(&log_entry);
+    }
+
+    pub fn on_host_rescued(&self, context: &Arc<RwLock<PlaybookContext>>, host: &Arc<RwLock<Host>>) {
+        let host2 = host.read().unwrap();
+        println!("{color_green}✓ {} => rescued{color_reset}", host2.name);
+        let log_entry = self.host_log_entry(&String::from("HOST_RESCUED"), context, &host2.name);
         self.log(&log_entry);
     }
 
+    pub fn on_host_block_failed(&self, context: &Arc<RwLock<PlaybookContext>>, host: &Arc<RwLock<Host>>) {
+        let host2 = host.read().unwrap();
+        context.write().unwrap().increment_failed_for_host(&host2.name);
+        println!("{color_red}! {} => block failed{color_reset}", host2.name);
+        let log_entry = self.host_log_entry(&String::from("BLOCK_FAILED"), context, &host2.name);
+        self.log(&log_entry);
+        self.record(&log_entry, None, true);
+    }
+
     pub fn on_host_connect_failed(&self, context: &Arc<RwLock<PlaybookContext>>, host: &Arc<RwLock<Host>>) {
         let host2 = host.read().unwrap();
         context.write().unwrap().increment_failed_for_host(&host2.name);
         println!("{color_red}! connection failed to host: {}{color_reset}", host2.name);
-        let mut log_entry = self.log_entry(&String::from("HOST_CONNECT_FAILED"), Arc::clone(context));
-        log_entry.host = Some(host2.name.clone());
+        let log_entry = self.host_log_entry(&String::from("HOST_CONNECT_FAILED"), context, &host2.name);
         self.log(&log_entry);
+        self.record(&log_entry, None, true);
     }
 
     pub fn get_exit_status(&self, context: &Arc<RwLock<PlaybookContext>>) -> i32 {
@@ -425,10 +629,39 @@ impl PlaybookVisitor {
         }
     }
 
+    // used by the template and copy modules when --diff is given
+
+    pub fn on_file_diff(&self, context: &Arc<RwLock<PlaybookContext>>, host: &Arc<RwLock<Host>>, path: &String, before: &String, after: &String) {
+        let host2 = host.read().unwrap();
+        if self.is_no_log(&host2.name) {
+            println!("{color_cyan}! {} => diff: {} {}{color_reset}", host2.name, path, NO_LOG_MESSAGE);
+            return;
+        }
+        let unified = render_diff(path, before, after);
+        {
+            let _ctx2 = context.write().unwrap(); // lock for multi-line output
+            println!("{color_cyan}! {} => diff: {}{color_reset}", host2.name, path);
+            for line in unified.lines() {
+                if line.starts_with("+++") || line.starts_with("---") || line.starts_with("@@") {
+                    println!("{color_cyan}    {}{color_reset}", line);
+                } else if line.starts_with("+") {
+                    println!("{color_green}    {}{color_reset}", line);
+                } else if line.starts_with("-") {
+                    println!("{color_red}    {}{color_reset}", line);
+                } else {
+                    println!("    {}", line);
+                }
+            }
+        }
+        let mut log_entry = self.host_log_entry(&String::from("FILE_DIFF"), context, &host2.name);
+        log_entry.diff = Some(unified);
+        self.log(&log_entry);
+    }
+
     pub fn on_command_run(&self, context: &Arc<RwLock<PlaybookContext>>, host: &Arc<RwLock<Host>>, cmd: &String) {
         let host2 = host.read().unwrap();
         if context.read().unwrap().verbosity > 0 {
-            println!("{color_blue}! {} => exec: {}", host2.name, &cmd.clone());
+            println!("{color_blue}! {} => exec: {}", host2.name, self.scrub(&host2.name, cmd));
         }
     }
 
@@ -438,8 +671,8 @@ impl PlaybookVisitor {
         if context.read().unwrap().verbosity > 2 {
             let _ctx2 = context.write().unwrap(); // lock for multi-line output
             println!("{color_blue}! {} ... command ok", host2.name);
-            println!("    cmd: {}", cmd_result.cmd);           
-            println!("    out: {}", cmd_result.out.clone());
+            println!("    cmd: {}", self.scrub(&host2.name, &cmd_result.cmd));
+            println!("    out: {}", self.scrub(&host2.name, &cmd_result.out));
             println!("    rc: {}{color_reset}", cmd_result.rc);
         }
     }
@@ -450,8 +683,8 @@ impl PlaybookVisitor {
         if context.read().unwrap().verbosity > 2 {
             let _ctx2 = context.write().unwrap(); // lock for multi-line output
             println!("{color_red}! {} ... command failed", host2.name);
-            println!("    cmd: {}", cmd_result.cmd);
-            println!("    out: {}", cmd_result.out.clone());
+            println!("    cmd: {}", self.scrub(&host2.name, &cmd_result.cmd));
+            println!("    out: {}", self.scrub(&host2.name, &cmd_result.out));
             println!("    rc: {}{color_reset}", cmd_result.rc);
         }
     }
@@ -515,30 +748,109 @@ impl PlaybookVisitor {
         crate::util::terminal::markdown_print(&mode_table);
         println!("{}", format!("\n{summary}"));
         println!("");
+        drop(ctx);
 
         let mut log_entry = self.log_entry(&String::from("SUMMARY"), Arc::clone(context));
+        log_entry.summary = Some(self.get_summary_map(context));
+        self.log(&log_entry);
+
+    }
+
+    pub fn get_summary_map(&self, context: &Arc<RwLock<PlaybookContext>>) -> serde_json::map::Map<String,serde_json::Value> {
+
+        let ctx = context.read().unwrap();
+        let seen_hosts = ctx.get_hosts_seen_count();
+        let action_ct = ctx.get_total_attempted_count();
+        let adjusted_ct = ctx.get_total_adjusted_count();
+        let adjusted_hosts = ctx.get_hosts_adjusted_count();
+
         let mut map : serde_json::map::Map<String,serde_json::Value> = serde_json::map::Map::new();
-        map.insert(String::from("matched_ct"),      json!(matched_ct));
-        map.insert(String::from("matched_hosts"),   json!(matched_hosts));
-        map.insert(String::from("created_ct"),      json!(created_ct));
-        map.insert(String::from("created_hosts"),   json!(created_hosts));
-        map.insert(String::from("modified_ct"),     json!(modified_ct));
-        map.insert(String::from("modified_hosts"),  json!(modified_hosts));
-        map.insert(String::from("executed_ct"),     json!(executed_ct));
-        map.insert(String::from("executed_hosts"),  json!(executed_hosts));
-        map.insert(String::from("passive_ct"),      json!(passive_ct));
-        map.insert(String::from("passive_hosts"),   json!(passive_hosts));
-        map.insert(String::from("skipped_ct"),      json!(skipped_ct));
-        map.insert(String::from("skipped_hosts"),   json!(skipped_hosts));
-        map.insert(String::from("unchanged_ct"),    json!(unchanged_ct));
-        map.insert(String::from("unchanged_hosts"), json!(unchanged_hosts));
+        map.insert(String::from("matched_ct"),      json!(ctx.get_total_matched_count()));
+        map.insert(String::from("matched_hosts"),   json!(ctx.get_hosts_matched_count()));
+        map.insert(String::from("created_ct"),      json!(ctx.get_total_creation_count()));
+        map.insert(String::from("created_hosts"),   json!(ctx.get_hosts_creation_count()));
+        map.insert(String::from("modified_ct"),     json!(ctx.get_total_modified_count()));
+        map.insert(String::from("modified_hosts"),  json!(ctx.get_hosts_modified_count()));
+        map.insert(String::from("executed_ct"),     json!(ctx.get_total_executions_count()));
+        map.insert(String::from("executed_hosts"),  json!(ctx.get_hosts_executions_count()));
+        map.insert(String::from("passive_ct"),      json!(ctx.get_total_passive_count()));
+        map.insert(String::from("passive_hosts"),   json!(ctx.get_hosts_passive_count()));
+        map.insert(String::from("skipped_ct"),      json!(ctx.get_total_skipped_count()));
+        map.insert(String::from("skipped_hosts"),   json!(ctx.get_hosts_skipped_count()));
+        map.insert(String::from("unchanged_ct"),    json!(action_ct - adjusted_ct));
+        map.insert(String::from("unchanged_hosts"), json!(seen_hosts - adjusted_hosts));
         map.insert(String::from("adjusted_ct"),     json!(adjusted_ct));
         map.insert(String::from("adjusted_hosts"),  json!(adjusted_hosts));
-        map.insert(String::from("failed_ct"),       json!(failed_ct));
-        map.insert(String::from("failed_hosts"),    json!(failed_hosts));
-        log_entry.summary = Some(map.clone());
-        self.log(&log_entry);
+        map.insert(String::from("failed_ct"),       json!(ctx.get_total_failed_count()));
+        map.insert(String::from("failed_hosts"),    json!(ctx.get_hosts_failed_count()));
+        return map;
+    }
 
+}
+
+fn render_diff(path: &String, before: &String, after: &String) -> String {
+    // command output has trailing newlines removed, so compare on the same footing
+    let before2 = normalize_for_diff(before);
+    let after2 = normalize_for_diff(after);
+    let diff = TextDiff::from_lines(&before2, &after2);
+    return redact(&diff.unified_diff().context_radius(3).header(&format!("{} (remote)", path), &format!("{} (desired)", path)).to_string());
+}
+
+fn normalize_for_diff(input: &String) -> String {
+    let trimmed = input.trim_end_matches('\n');
+    return match trimmed.is_empty() {
+        true => String::new(),
+        false => format!("{}\n", trimmed)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::secrets::add_secret;
+
+    fn diff(before: &str, after: &str) -> String {
+        return render_diff(&String::from("/etc/motd"), &String::from(before), &String::from(after));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_render_diff() {
+        // (remote contents, desired contents, expected diff)
+        let cases = vec![
+            // same contents, or only differing in trailing newlines, show nothing
+            ("a\nb\n", "a\nb\n", ""),
+            ("a\nb", "a\nb\n\n", ""),
+            ("", "", ""),
+            ("", "\n", ""),
+            // a new file is compared against an empty one
+            ("", "a\nb\n",
+                "--- /etc/motd (remote)\n+++ /etc/motd (desired)\n@@ -0,0 +1,2 @@\n+a\n+b\n"),
+            ("a\n", "",
+                "--- /etc/motd (remote)\n+++ /etc/motd (desired)\n@@ -1 +0,0 @@\n-a\n"),
+            ("a\nb\nc", "a\nB\nc\n",
+                "--- /etc/motd (remote)\n+++ /etc/motd (desired)\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n"),
+        ];
+        for (before, after, expected) in cases.iter() {
+            assert_eq!(diff(before, after), *expected, "{:?} -> {:?}", before, after);
+        }
+    }
+
+    #[test]
+    fn test_render_diff_context() {
+        // only three lines around each change are shown
+        let before : String = (1..=20).map(|x| format!("line {}\n", x)).collect();
+        let after = before.replace("line 10\n", "line ten\n");
+        let result = diff(&before, &after);
+        assert!(result.contains("@@ -7,7 +7,7 @@\n line 7\n line 8\n line 9\n-line 10\n+line ten\n line 11\n"));
+        assert!(!result.contains("line 6\n"));
+        assert!(!result.contains("line 14\n"));
+    }
+
+    #[test]
+    fn test_render_diff_redacts_secrets() {
+        add_secret(&String::from("diff-hunter2"));
+        let result = diff("password = old\n", "password = diff-hunter2\n");
+        assert!(!result.contains("diff-hunter2"));
+        assert!(result.contains("+password = "));
+    }
+}
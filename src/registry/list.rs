@@ -17,6 +17,7 @@
 use serde::Deserialize;
 use crate::tasks::*;
 use std::sync::Arc;
+use crate::playbooks::language::{Block,FlushHandlers,ImportTasks,Include};
 
 // note: there is some repetition in this module that we would rather not have
 // however, it comes from a conflict between polymorphic dispatch macros + traits
@@ -68,6 +69,8 @@ pub enum Task {
     // ADD NEW MODULES HERE, KEEP ALPHABETIZED BY NAME
     Apt(AptTask),
     Assert(AssertTask),
+    // not a module, blocks are unrolled by traversal and never reach the FSM
+    Block(Block),
     Copy(CopyTask),
     Debug(DebugTask),
     Directory(DirectoryTask),
@@ -78,9 +81,14 @@ pub enum Task {
     Fail(FailTask),
     Fetch(FetchTask),
     File(FileTask),
+    // not a module, runs notified handlers early
+    Flush_Handlers(FlushHandlers),
     Git(GitTask),
     Group(GroupTask),
     Homebrew(HomebrewTask),
+    // not modules, task files are loaded by traversal
+    Import_Tasks(ImportTasks),
+    Include(Include),
     Pacman(PacmanTask),
     Sd_Service(SystemdServiceTask),
     Set(SetTask),
@@ -99,6 +107,7 @@ impl Task {
         return match self {
             Task::Apt(x)        => x.get_module(),
             Task::Assert(x)     => x.get_module(),
+            Task::Block(_)      => String::from("block"),
             Task::Copy(x)       => x.get_module(),
             Task::Debug(x)      => x.get_module(),
             Task::Directory(x)  => x.get_module(),
@@ -112,6 +121,9 @@ impl Task {
             Task::Git(x)        => x.get_module(), 
             Task::Group(x)      => x.get_module(),
             Task::Homebrew(x)   => x.get_module(),
+            Task::Flush_Handlers(_) => String::from("flush_handlers"),
+            Task::Import_Tasks(_) => String::from("import_tasks"),
+            Task::Include(_)    => String::from("include"),
             Task::Pacman(x)     => x.get_module(),
             Task::Sd_Service(x) => x.get_module(),
             Task::Set(x)        => x.get_module(), 
@@ -129,6 +141,7 @@ impl Task {
         return match self {
             Task::Apt(x)        => x.get_name(),
             Task::Assert(x)     => x.get_name(),
+            Task::Block(x)      => x.name.clone(),
             Task::Copy(x)       => x.get_name(),
             Task::Debug(x)      => x.get_name(), 
             Task::Directory(x)  => x.get_name(),
@@ -142,6 +155,9 @@ impl Task {
             Task::Git(x)        => x.get_name(),
             Task::Group(x)      => x.get_name(),
             Task::Homebrew(x)   => x.get_name(),
+            Task::Flush_Handlers(x) => x.name.clone(),
+            Task::Import_Tasks(x) => x.name.clone(),
+            Task::Include(x)    => x.name.clone(),
             Task::Pacman(x)     => x.get_name(),
             Task::Sd_Service(x) => x.get_name(),
             Task::Set(x)        => x.get_name(),
@@ -159,6 +175,7 @@ impl Task {
         return match self {
             Task::Apt(x)        => x.get_with(),
             Task::Assert(x)     => x.get_with(),
+            Task::Block(_)      => None,
             Task::Copy(x)       => x.get_with(),
             Task::Debug(x)      => x.get_with(), 
             Task::Directory(x)  => x.get_with(),
@@ -172,6 +189,9 @@ impl Task {
             Task::Git(x)        => x.get_with(), 
             Task::Group(x)      => x.get_with(),
             Task::Homebrew(x)   => x.get_with(),
+            Task::Flush_Handlers(_) => None,
+            Task::Import_Tasks(_) => None,
+            Task::Include(x)    => x.beforetask.clone(),
             Task::Pacman(x)     => x.get_with(),
             Task::Sd_Service(x) => x.get_with(),
             Task::Set(x)        => x.get_with(),
@@ -189,6 +209,7 @@ impl Task {
         return match self {
             Task::Apt(x)        => x.evaluate(handle, request, tm),
             Task::Assert(x)     => x.evaluate(handle, request, tm),
+            Task::Block(_)      => Err(handle.response.is_failed(request, &String::from("blocks cannot be evaluated as a module"))),
             Task::Copy(x)       => x.evaluate(handle, request, tm),
             Task::Debug(x)      => x.evaluate(handle, request, tm), 
             Task::Directory(x)  => x.evaluate(handle, request, tm), 
@@ -202,6 +223,9 @@ impl Task {
             Task::Git(x)        => x.evaluate(handle, request, tm),
             Task::Group(x)      => x.evaluate(handle, request, tm),
             Task::Homebrew(x)   => x.evaluate(handle, request, tm),
+            Task::Flush_Handlers(_) => Err(handle.response.is_failed(request, &String::from("flush_handlers cannot be evaluated as a module"))),
+            Task::Import_Tasks(_) => Err(handle.response.is_failed(request, &String::from("import_tasks cannot be evaluated as a module"))),
+            Task::Include(_)    => Err(handle.response.is_failed(request, &String::from("include cannot be evaluated as a module"))),
             Task::Pacman(x)     => x.evaluate(handle, request, tm),
             Task::Sd_Service(x) => x.evaluate(handle, request, tm),
             Task::Set(x)        => x.evaluate(handle, request, tm),
@@ -99,6 +99,11 @@ pub fn get_sha512_command(os_type: HostOSType, untrusted_path: &String) -> Resul
     }
 }
 
+pub fn get_read_file_command(_os_type: HostOSType, untrusted_path: &String) -> Result<String,String>  {
+    let path = screen_path(untrusted_path)?;
+    return Ok(format!("cat '{}'", path));
+}
+
 pub fn get_ownership_command(_os_type: HostOSType, untrusted_path: &String) -> Result<String,String>  {
     let path = screen_path(untrusted_path)?;
     return Ok(format!("ls -ld '{}'", path));
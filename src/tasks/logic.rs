@@ -21,6 +21,8 @@ use crate::tasks::response::TaskResponse;
 use serde::Deserialize;
 use crate::handle::template::BlendTarget;
 use crate::playbooks::templar::TemplateMode;
+use crate::inventory::hosts::Host;
+use std::sync::RwLock;
 
 // this is storage behind all 'and' and 'with' statements in the program, which
 // are mostly implemented in task_fsm
@@ -29,44 +31,77 @@ use crate::playbooks::templar::TemplateMode;
 #[serde(deny_unknown_fields)]
 pub struct PreLogicInput {
     pub checkcondition: Option<String>,
-    pub subscribe: Option<String>,
+    pub subscribe: Option<NamesInput>,
     pub sudo: Option<String>,
     pub items: Option<ItemsInput>,
+    pub nested: Option<Vec<ItemsInput>>,
+    pub loop_var: Option<String>,
+    pub item_label: Option<String>,
     pub tags: Option<Vec<String>>,
-    pub delegate_to: Option<String>
+    pub delegate_to: Option<String>,
+    pub no_log: Option<bool>
 }
 
+// items may name a variable, or be a list of values (strings or mappings), or a mapping,
+// in which case each item has a 'key' and a 'value'. a variable may hold a list or a mapping.
+// nested takes a list of these and loops over every combination, each item being a list.
+
 #[derive(Deserialize,Debug,Clone)]
 #[serde(untagged)]
 pub enum ItemsInput {
     ItemsString(String),
-    ItemsList(Vec<String>),
+    ItemsList(Vec<serde_yaml::Value>),
+    ItemsMapping(serde_yaml::Mapping),
+}
+
+// notify and subscribe take either one name or a list of them, so one change can notify
+// several handlers and a handler can listen for several topics
+
+#[derive(Deserialize,Debug,Clone)]
+#[serde(untagged)]
+pub enum NamesInput {
+    Name(String),
+    Names(Vec<String>),
+}
+
+impl NamesInput {
+    pub fn to_vec(&self) -> Vec<String> {
+        return match self {
+            NamesInput::Name(x) => vec![x.clone()],
+            NamesInput::Names(x) => x.clone()
+        };
+    }
 }
 
 #[derive(Debug)]
 pub struct PreLogicEvaluated {
     pub checkcondition: Option<String>, // this is not evaluated here
-    pub subscribe: Option<String>,
+    pub subscribe: Option<Vec<String>>,
     pub sudo: Option<String>,
     pub items: Option<ItemsInput>,
+    pub nested: Option<Vec<ItemsInput>>,
+    pub loop_var: Option<String>,
+    pub item_label: Option<String>, // this is evaluated per item
     pub tags: Option<Vec<String>>
 }
 
 #[derive(Deserialize,Debug)]
 #[serde(deny_unknown_fields)]
 pub struct PostLogicInput {
-    pub notify: Option<String>,
+    pub notify: Option<NamesInput>,
     pub ignore_errors: Option<String>,
     pub retry: Option<String>,
-    pub delay: Option<String>
+    pub delay: Option<String>,
+    pub save: Option<String>
 }
 
 #[derive(Debug)]
 pub struct PostLogicEvaluated {
-    pub notify: Option<String>,
+    pub notify: Option<Vec<String>>,
     pub ignore_errors: bool,
     pub retry: u64,
     pub delay: u64,
+    pub save: Option<String>
 }
 
 
@@ -80,8 +115,14 @@ impl PreLogicInput {
         return Ok(Some(PreLogicEvaluated {
             checkcondition: input2.checkcondition.clone(),
             sudo: handle.template.string_option_no_spaces(request, tm, &String::from("sudo"), &input2.sudo)?,
-            subscribe: handle.template.no_template_string_option_trim(&input2.subscribe),
+            subscribe: match &input2.subscribe {
+                Some(x) => Some(x.to_vec().iter().map(|y| y.trim().to_string()).collect()),
+                None => None
+            },
             items: input2.items.clone(),
+            nested: input2.nested.clone(),
+            loop_var: handle.template.no_template_string_option_trim(&input2.loop_var),
+            item_label: input2.item_label.clone(),
             tags: input2.tags.clone()
         }));
     }
@@ -96,25 +137,68 @@ impl PostLogicInput {
         }
         let input2 = input.as_ref().unwrap();
         return Ok(Some(PostLogicEvaluated {
-            notify: handle.template.string_option_trim(request, tm, &String::from("notify"), &input2.notify)?,
+            notify: match &input2.notify {
+                Some(x) => {
+                    let mut names : Vec<String> = Vec::new();
+                    for name in x.to_vec().iter() {
+                        names.push(handle.template.string(request, tm, &String::from("notify"), name)?.trim().to_string());
+                    }
+                    Some(names)
+                },
+                None => None
+            },
             // unsafe here means the options cannot be sent to the shell, which they are not.
             delay:         handle.template.integer_option_to_integer(request, tm, &String::from("delay"), &input2.delay, 1)?,
             ignore_errors: handle.template.boolean_option_default_false(request, tm, &String::from("ignore_errors"), &input2.ignore_errors)?,
             retry:         handle.template.integer_option_to_integer(request, tm, &String::from("retry"), &input2.retry, 0)?,
+            // save is used verbatim so the name is known before the task is templated
+            save:          handle.template.no_template_string_option_trim(&input2.save),
         }));
     }
 }
 
 /* this is called from the task_fsm, not above */
-pub fn template_items(handle: &Arc<TaskHandle>, request: &Arc<TaskRequest>, tm: TemplateMode, items_input: &Option<ItemsInput>) 
-    -> Result<Vec<serde_yaml::Value>, Arc<TaskResponse>> {
+pub fn template_items(handle: &Arc<TaskHandle>, request: &Arc<TaskRequest>, tm: TemplateMode, items_input: &Option<ItemsInput>, nested_input: &Option<Vec<ItemsInput>>) 
+    -> Result<Vec<serde_yaml::Value>,Arc<TaskResponse>> {
+
+    return match (items_input, nested_input) {
+
+        (None, None) => Ok(empty_items_vector()),
+
+        (Some(_), Some(_)) => Err(handle.response.is_failed(request, &String::from("items and nested cannot be used together"))),
+
+        // with/items: varname, a list, or a mapping
+        (Some(x), None) => template_items_input(handle, request, tm, x),
+
+        // with/nested: a list of the above, looping over every combination
+        (None, Some(x)) => {
+            if x.is_empty() {
+                return Err(handle.response.is_failed(request, &String::from("with/nested contained no entries")));
+            }
+            let mut combinations : Vec<Vec<serde_yaml::Value>> = vec![Vec::new()];
+            for input in x.iter() {
+                let values = template_items_input(handle, request, tm, input)?;
+                let mut next : Vec<Vec<serde_yaml::Value>> = Vec::new();
+                for prefix in combinations.iter() {
+                    for value in values.iter() {
+                        let mut combination = prefix.clone();
+                        combination.push(value.clone());
+                        next.push(combination);
+                    }
+                }
+                combinations = next;
+            }
+            Ok(combinations.into_iter().map(|x| serde_yaml::Value::Sequence(x)).collect())
+        }
+    }
+}
+
+fn template_items_input(handle: &Arc<TaskHandle>, request: &Arc<TaskRequest>, tm: TemplateMode, items_input: &ItemsInput) 
+    -> Result<Vec<serde_yaml::Value>,Arc<TaskResponse>> {
 
     return match items_input {
 
-        None => Ok(empty_items_vector()),
-        
-        // with/items: varname
-        Some(ItemsInput::ItemsString(x)) => {
+        ItemsInput::ItemsString(x) => {
             let blended = handle.run_state.context.read().unwrap().get_complete_blended_variables(
                 &handle.host, 
                 BlendTarget::NotTemplateModule
@@ -124,8 +208,9 @@ pub fn template_items(handle: &Arc<TaskHandle>, request: &Arc<TaskRequest>, tm:
                     let value : serde_yaml::Value = blended.get(&x).unwrap().clone();
                     match value {
                         serde_yaml::Value::Sequence(vs) => template_serde_sequence(handle, request, tm, vs),
+                        serde_yaml::Value::Mapping(vm) => template_serde_mapping_items(handle, request, tm, vm),
                         _ => {
-                            return Err(handle.response.is_failed(request, &format!("with/items variable did not resolve to a list")));
+                            return Err(handle.response.is_failed(request, &format!("with/items variable did not resolve to a list or mapping")));
                         }
                     }
                 }, 
@@ -134,13 +219,8 @@ pub fn template_items(handle: &Arc<TaskHandle>, request: &Arc<TaskRequest>, tm:
                 }
             }
         },
-        Some(ItemsInput::ItemsList(x)) => {
-            let mut output : Vec<serde_yaml::Value> = Vec::new();
-            for item in x.iter() {
-                output.push(serde_yaml::Value::String(handle.template.string(request, tm, &String::from("items"), item)?));
-            }
-            Ok(output)
-        }
+        ItemsInput::ItemsList(x) => template_serde_sequence(handle, request, tm, x.clone()),
+        ItemsInput::ItemsMapping(x) => template_serde_mapping_items(handle, request, tm, x.clone())
     }
 }
 
@@ -156,15 +236,217 @@ pub fn template_serde_sequence(
     -> Result<Vec<serde_yaml::Value>,Arc<TaskResponse>> {
 
     let mut output : Vec<serde_yaml::Value> = Vec::new();
-
     for seq_item in vs.iter() {
+        output.push(template_serde_value(handle, request, tm, seq_item)?);
+    }
+    return Ok(output);
+}
 
-        match seq_item {   
-            serde_yaml::Value::String(x) => {
-                output.push(serde_yaml::Value::String(handle.template.string(request, tm, &String::from("items"), x)?))
-            },
-            x => { output.push(x.clone()) }
-        }
+fn template_serde_mapping_items(
+    handle: &TaskHandle, 
+    request: &Arc<TaskRequest>, 
+    tm: TemplateMode,
+    vm: serde_yaml::Mapping) 
+    -> Result<Vec<serde_yaml::Value>,Arc<TaskResponse>> {
+
+    // looping over a mapping gives one item per entry, with 'key' and 'value' fields
+
+    let mut output : Vec<serde_yaml::Value> = Vec::new();
+    for (k, v) in vm.iter() {
+        let mut item = serde_yaml::Mapping::new();
+        item.insert(serde_yaml::Value::String(String::from("key")), k.clone());
+        item.insert(serde_yaml::Value::String(String::from("value")), template_serde_value(handle, request, tm, v)?);
+        output.push(serde_yaml::Value::Mapping(item));
     }
     return Ok(output);
 }
+
+fn template_serde_value(
+    handle: &TaskHandle, 
+    request: &Arc<TaskRequest>, 
+    tm: TemplateMode,
+    value: &serde_yaml::Value) 
+    -> Result<serde_yaml::Value,Arc<TaskResponse>> {
+
+    // strings are templated wherever they appear inside an item, keys are left alone
+
+    return match value {
+        serde_yaml::Value::String(x) => {
+            Ok(serde_yaml::Value::String(handle.template.string(request, tm, &String::from("items"), x)?))
+        },
+        serde_yaml::Value::Sequence(x) => {
+            Ok(serde_yaml::Value::Sequence(template_serde_sequence(handle, request, tm, x.clone())?))
+        },
+        serde_yaml::Value::Mapping(x) => {
+            let mut output = serde_yaml::Mapping::new();
+            for (k, v) in x.iter() {
+                output.insert(k.clone(), template_serde_value(handle, request, tm, v)?);
+            }
+            Ok(serde_yaml::Value::Mapping(output))
+        },
+        x => Ok(x.clone())
+    };
+}
+
+pub fn get_loop_var(loop_var: &Option<String>) -> String {
+    return match loop_var {
+        Some(x) => x.clone(),
+        None => String::from("item")
+    };
+}
+
+pub fn set_loop_item(host: &Arc<RwLock<Host>>, loop_var: &String, item: &serde_yaml::Value) {
+    // the previous item is removed first, as facts are blended and mapping or list items would merge
+    let mut mapping = serde_yaml::Mapping::new();
+    mapping.insert(serde_yaml::Value::String(loop_var.clone()), item.clone());
+    let mut host2 = host.write().unwrap();
+    host2.clear_fact(loop_var);
+    host2.update_facts2(mapping);
+}
+
+pub fn get_item_label(item: &serde_yaml::Value) -> String {
+    // without an item_label, strings are shown as-is and anything else in compact JSON
+    return match item {
+        serde_yaml::Value::String(x) => x.clone(),
+        x => match serde_json::to_string(x) {
+            Ok(y) => y,
+            Err(_) => format!("{:?}", x)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::parser::CliParser;
+    use crate::connection::no::NoFactory;
+    use crate::connection::factory::ConnectionFactory;
+    use crate::connection::connection::Connection;
+    use crate::inventory::inventory::Inventory;
+    use crate::playbooks::context::PlaybookContext;
+    use crate::playbooks::traversal::RunState;
+    use crate::playbooks::visitor::{PlaybookVisitor,CheckMode};
+    use std::sync::Mutex;
+    use std::path::PathBuf;
+
+    // a handle on a simulated host, with facts standing in for variables the items may name
+
+    fn handle(facts: &str) -> (Arc<TaskHandle>, Arc<TaskRequest>) {
+        let parser = CliParser::new();
+        let factory = NoFactory::new();
+        let context = Arc::new(RwLock::new(PlaybookContext::new(&parser)));
+        let host = Arc::new(RwLock::new(Host::new(&String::from("web1"))));
+        host.write().unwrap().update_facts2(serde_yaml::from_str(facts).unwrap());
+        let connection : Arc<Mutex<dyn Connection>> = factory.get_connection(&context, &host).unwrap();
+        let run_state = Arc::new(RunState {
+            inventory: Arc::new(RwLock::new(Inventory::new())),
+            playbook_paths: Arc::new(RwLock::new(Vec::<PathBuf>::new())),
+            role_paths: Arc::new(RwLock::new(Vec::<PathBuf>::new())),
+            module_paths: Arc::new(RwLock::new(Vec::<PathBuf>::new())),
+            limit_hosts: Vec::new(),
+            limit_groups: Vec::new(),
+            batch_size: None,
+            context: context,
+            visitor: Arc::new(RwLock::new(PlaybookVisitor::new(CheckMode::No, &None))),
+            connection_factory: Arc::new(RwLock::new(factory)),
+            tags: None,
+            skip_tags: None,
+            start_at_task: Arc::new(RwLock::new(None)),
+            step: Arc::new(RwLock::new(false)),
+            allow_localhost_delegation: false,
+            vault_password: None,
+            diff: false
+        });
+        let handle = Arc::new(TaskHandle::new(run_state, connection, host));
+        return (handle, TaskRequest::validate());
+    }
+
+    fn items(input: &str) -> Option<ItemsInput> {
+        return Some(serde_yaml::from_str(input).unwrap());
+    }
+
+    fn nested(input: &str) -> Option<Vec<ItemsInput>> {
+        return Some(serde_yaml::from_str(input).unwrap());
+    }
+
+    fn values(input: &str) -> Vec<serde_yaml::Value> {
+        return serde_yaml::from_str(input).unwrap();
+    }
+
+    const FACTS : &str = "{ ports: [80, 443], users: { alice: admin, bob: '{{ role }}' }, role: guest }";
+
+    #[test]
+    fn test_template_items() {
+        // (items, nested, expected items in order)
+        let cases : Vec<(Option<ItemsInput>, Option<Vec<ItemsInput>>, &str)> = vec![
+            (None, None, "[true]"),
+            (items("[a, '{{ role }}']"), None, "[a, guest]"),
+            (items("ports"), None, "[80, 443]"),
+            (items("{ x: 1, y: '{{ role }}' }"), None, "[{ key: x, value: 1 }, { key: y, value: guest }]"),
+            (items("users"), None, "[{ key: alice, value: admin }, { key: bob, value: guest }]"),
+            (None, nested("[[a, b]]"), "[[a], [b]]"),
+            (None, nested("[[a, b], ports]"), "[[a, 80], [a, 443], [b, 80], [b, 443]]"),
+            (None, nested("[[a, b], [1, 2], [x]]"), "[[a, 1, x], [a, 2, x], [b, 1, x], [b, 2, x]]"),
+            (None, nested("[[a, b], []]"), "[]"),
+            (None, nested("[[a], { k: v }]"), "[[a, { key: k, value: v }]]"),
+        ];
+        let (handle, request) = handle(FACTS);
+        for (items_input, nested_input, expected) in cases.iter() {
+            let result = template_items(&handle, &request, TemplateMode::Strict, items_input, nested_input);
+            assert_eq!(result.unwrap(), values(expected), "items: {:?}, nested: {:?}", items_input, nested_input);
+        }
+    }
+
+    #[test]
+    fn test_template_items_errors() {
+        // (items, nested, expected message)
+        let cases : Vec<(Option<ItemsInput>, Option<Vec<ItemsInput>>, &str)> = vec![
+            (items("[a]"), nested("[[b]]"), "items and nested cannot be used together"),
+            (None, nested("[]"), "with/nested contained no entries"),
+            (items("missing"), None, "variable not found for items: missing"),
+            (items("role"), None, "with/items variable did not resolve to a list or mapping"),
+            (None, nested("[[a], missing]"), "variable not found for items: missing"),
+        ];
+        let (handle, request) = handle(FACTS);
+        for (items_input, nested_input, expected) in cases.iter() {
+            let result = template_items(&handle, &request, TemplateMode::Strict, items_input, nested_input);
+            let msg = result.unwrap_err().msg.clone();
+            assert_eq!(msg, Some(String::from(*expected)), "items: {:?}, nested: {:?}", items_input, nested_input);
+        }
+    }
+
+    #[test]
+    fn test_loop_var() {
+        let (handle, request) = handle(FACTS);
+        let cases = vec![
+            (None, "item"),
+            (Some(String::from("port")), "port"),
+        ];
+        for (loop_var, expected) in cases.iter() {
+            let name = get_loop_var(loop_var);
+            assert_eq!(name, *expected);
+            // a mapping item replaces the previous one instead of being merged into it
+            set_loop_item(&handle.host, &name, &values("[{ key: a, value: 1 }]")[0]);
+            set_loop_item(&handle.host, &name, &values("[{ key: b }]")[0]);
+            let template = format!("{{{{ {}.key }}}}/{{{{ {}.value }}}}", name, name);
+            let result = handle.template.string(&request, TemplateMode::Strict, &String::from("test"), &template);
+            assert!(result.is_err(), "{} still has the value from the previous item", name);
+            let template = format!("{{{{ {}.key }}}}", name);
+            let result = handle.template.string(&request, TemplateMode::Strict, &String::from("test"), &template);
+            assert_eq!(result.unwrap(), "b");
+        }
+    }
+
+    #[test]
+    fn test_get_item_label() {
+        let cases = vec![
+            ("[a]", "a"),
+            ("[80]", "80"),
+            ("[[a, 80]]", "[\"a\",80]"),
+            ("[{ key: k, value: v }]", "{\"key\":\"k\",\"value\":\"v\"}"),
+        ];
+        for (item, expected) in cases.iter() {
+            assert_eq!(get_item_label(&values(item)[0]), *expected);
+        }
+    }
+}
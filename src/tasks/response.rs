@@ -25,7 +25,7 @@ use std::vec::Vec;
 // created directly but by helper functions in handle.rs, see
 // the various modules for examples/usage
 
-#[derive(Debug,PartialEq)]
+#[derive(Debug,PartialEq,Clone)]
 pub enum TaskStatus {
     IsCreated,
     IsRemoved,
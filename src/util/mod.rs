@@ -17,3 +17,5 @@
 pub mod io;
 pub mod yaml;
 pub mod terminal;
+pub mod vault;
+pub mod secrets;
@@ -0,0 +1,126 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+
+// the secret registry holds the values of variables that must never be shown, such as
+// everything loaded from a vault file and the variables a play lists under 'secrets'.
+// values are registered as they are loaded and masked out of anything the visitor prints,
+// logs or reports. it is process wide because inventory is loaded before any playbook
+// state exists.
+
+pub const REDACTED: &str = "********";
+
+// shown instead of commands and output for tasks with beforetask/no_log
+
+pub const NO_LOG_MESSAGE: &str = "(hidden by no_log)";
+
+// shorter values are not masked when found inside a variable, as every '1' or 'yes' in the
+// output would be replaced
+
+pub const MIN_SECRET_LENGTH: usize = 4;
+
+static SECRETS: Lazy<RwLock<Vec<String>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+// ==============================================================================================================
+// PUBLIC API
+// ==============================================================================================================
+
+pub fn add_secret(value: &String) {
+    if value.is_empty() {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap();
+    if secrets.contains(value) {
+        return;
+    }
+    secrets.push(value.clone());
+    // longest first, so a secret containing another secret is masked whole
+    secrets.sort_by_key(|x| std::cmp::Reverse(x.len()));
+}
+
+// strings anywhere in the value are registered. numbers, booleans, nulls and short strings
+// are not, as masking every 'true' or port number would make the output useless
+
+pub fn add_secret_values(value: &serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::String(x) => {
+            if x.chars().count() >= MIN_SECRET_LENGTH { add_secret(x); }
+        },
+        serde_yaml::Value::Sequence(x) => {
+            for item in x.iter() { add_secret_values(item); }
+        },
+        serde_yaml::Value::Mapping(x) => {
+            for (_, item) in x.iter() { add_secret_values(item); }
+        },
+        serde_yaml::Value::Tagged(x) => add_secret_values(&x.value),
+        _ => {}
+    }
+}
+
+pub fn redact(text: &String) -> String {
+    let secrets = SECRETS.read().unwrap();
+    let mut result = text.clone();
+    for secret in secrets.iter() {
+        if result.contains(secret.as_str()) {
+            result = result.replace(secret.as_str(), REDACTED);
+        }
+    }
+    return result;
+}
+
+pub fn redact_option(text: &Option<String>) -> Option<String> {
+    return text.as_ref().map(|x| redact(x));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the registry is process wide, so each test uses values no other test registers
+
+    #[test]
+    fn test_redact() {
+        add_secret(&String::from("redact-secret"));
+        add_secret(&String::from("redact-secret-longer"));
+        add_secret(&String::from(""));
+        assert_eq!(redact(&String::from("pw=redact-secret, again redact-secret")), format!("pw={}, again {}", REDACTED, REDACTED));
+        // the longer secret is masked whole rather than leaving its tail behind
+        assert_eq!(redact(&String::from("redact-secret-longer")), REDACTED);
+        assert_eq!(redact(&String::from("nothing to hide")), "nothing to hide");
+        assert_eq!(redact_option(&Some(String::from("redact-secret"))), Some(String::from(REDACTED)));
+        assert_eq!(redact_option(&None), None);
+    }
+
+    #[test]
+    fn test_add_secret_values() {
+        let value : serde_yaml::Value = serde_yaml::from_str(r#"
+            db_password: values-password
+            port: 5433
+            replicas: 7
+            enabled: true
+            short: abc
+            nested:
+              - values-token
+              - { key: values-key }
+            tagged: !Custom values-tagged
+        "#).unwrap();
+        add_secret_values(&value);
+        assert_eq!(redact(&String::from("values-password values-token values-key values-tagged")), format!("{} {} {} {}", REDACTED, REDACTED, REDACTED, REDACTED));
+        assert_eq!(redact(&String::from("port 5433 replicas 7 enabled true short abc")), "port 5433 replicas 7 enabled true short abc");
+    }
+}
@@ -14,6 +14,8 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::io::{self,Write};
+
 pub fn markdown_print(markdown: &String) {
     termimad::print_text(markdown);
 }
@@ -47,4 +49,31 @@ pub fn captioned_display(caption: &String, body: &String) {
         println!("    {}", line);
     }
     println!("");
-}
\ No newline at end of file
+}
+
+pub fn prompt_choice(question: &String, choices: &Vec<(&str, &str)>) -> String {
+
+    // asks until one of the choices is typed. the choices are (answer, description) pairs
+    // and an empty answer picks the first one
+
+    let described : Vec<String> = choices.iter().map(|(a,b)| format!("({}){}", a, b)).collect();
+    loop {
+        print!("{} [{}]: ", question, described.join("/"));
+        let _ = io::stdout().flush();
+        let mut value = String::new();
+        match io::stdin().read_line(&mut value) {
+            Ok(0) => { return String::from(choices[0].0); },
+            Ok(_) => {},
+            Err(_) => { return String::from(choices[0].0); }
+        }
+        let answer = value.trim().to_lowercase();
+        if answer.is_empty() {
+            return String::from(choices[0].0);
+        }
+        for (a, b) in choices.iter() {
+            if answer.eq(a) || answer.eq(&format!("{}{}", a, b)) {
+                return String::from(*a);
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+// Jetporch
+// Copyright (C) 2023 - Michael DeHaan <michael@michaeldehaan.net> + contributors
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::util::io::read_local_file;
+use crate::util::yaml::show_yaml_error_in_contents;
+use crate::util::secrets::add_secret_values;
+use aes_gcm::{Aes256Gcm, Key, Nonce, KeyInit};
+use aes_gcm::aead::{Aead, OsRng};
+use aes_gcm::aead::rand_core::RngCore;
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use sha2::Sha256;
+use std::path::Path;
+
+// vault files are variable files encrypted with a password so they can be committed
+// next to the rest of the content. the file is a header line followed by base64 lines
+// holding the salt, the nonce and the AES-256-GCM ciphertext. the key comes from the
+// password with PBKDF2, so every encryption of the same file looks different.
+
+pub const VAULT_HEADER: &str = "$JET_VAULT;1;AES256-GCM";
+const VAULT_PREFIX: &str = "$JET_VAULT;";
+
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const KEY_ROUNDS: u32 = 100_000;
+const LINE_WIDTH: usize = 76;
+
+// ==============================================================================================================
+// PUBLIC API
+// ==============================================================================================================
+
+pub fn is_vault_encrypted(contents: &String) -> bool {
+    // any version counts, so a newer file is reported rather than read as plain YAML
+    return contents.starts_with(VAULT_PREFIX);
+}
+
+pub fn vault_encrypt(plaintext: &String, password: &String) -> Result<String, String> {
+    let mut salt = [0u8; SALT_SIZE];
+    OsRng.fill_bytes(&mut salt);
+    let mut nonce = [0u8; NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+
+    let cipher = get_cipher(password, &salt);
+    let ciphertext = match cipher.encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes()) {
+        Ok(x) => x,
+        Err(_) => { return Err(String::from("vault encryption failed")); }
+    };
+
+    let mut payload : Vec<u8> = Vec::new();
+    payload.extend_from_slice(&salt);
+    payload.extend_from_slice(&nonce);
+    payload.extend_from_slice(&ciphertext);
+    let encoded = STANDARD.encode(&payload);
+
+    let mut result = String::from(VAULT_HEADER);
+    result.push('\n');
+    for chunk in encoded.as_bytes().chunks(LINE_WIDTH) {
+        result.push_str(std::str::from_utf8(chunk).unwrap());
+        result.push('\n');
+    }
+    return Ok(result);
+}
+
+pub fn vault_decrypt(contents: &String, password: &String, path: &Path) -> Result<String, String> {
+    if ! is_vault_encrypted(contents) {
+        return Err(format!("{} is not vault encrypted", path.display()));
+    }
+    let header = contents.lines().next().unwrap().trim();
+    if ! header.eq(VAULT_HEADER) {
+        return Err(format!("{} uses an unsupported vault format ({}), expected {}", path.display(), header, VAULT_HEADER));
+    }
+    let encoded : String = contents.lines().skip(1).map(|x| x.trim()).collect();
+    let payload = match STANDARD.decode(encoded) {
+        Ok(x) => x,
+        Err(_) => { return Err(format!("{} is not a valid vault file", path.display())); }
+    };
+    if payload.len() < SALT_SIZE + NONCE_SIZE {
+        return Err(format!("{} is not a valid vault file", path.display()));
+    }
+    let (salt, rest) = payload.split_at(SALT_SIZE);
+    let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+
+    let cipher = get_cipher(password, salt);
+    let plaintext = match cipher.decrypt(Nonce::from_slice(nonce), ciphertext) {
+        Ok(x) => x,
+        Err(_) => { return Err(format!("unable to decrypt {}, is the vault password correct?", path.display())); }
+    };
+    return match String::from_utf8(plaintext) {
+        Ok(x) => Ok(x),
+        Err(_) => Err(format!("{} does not contain text", path.display()))
+    };
+}
+
+// used by everything that loads variables, files may or may not be encrypted. everything
+// in an encrypted file is treated as a secret, see util/secrets.rs
+
+pub fn load_vars_file(path: &Path, vault_password: &Option<String>) -> Result<serde_yaml::Mapping, String> {
+    let (contents, encrypted) = read_possibly_encrypted(path, vault_password)?;
+    let parsed: Result<serde_yaml::Mapping, serde_yaml::Error> = serde_yaml::from_str(&contents);
+    if parsed.is_err() {
+        // show the decrypted lines rather than the ciphertext on disk
+        show_yaml_error_in_contents(&parsed.unwrap_err(), path, &contents);
+        return Err(format!("edit the file and try again?"));
+    }
+    let mapping = parsed.unwrap();
+    if encrypted {
+        add_secret_values(&serde_yaml::Value::Mapping(mapping.clone()));
+    }
+    return Ok(mapping);
+}
+
+// ==============================================================================================================
+// PRIVATE
+// ==============================================================================================================
+
+fn read_possibly_encrypted(path: &Path, vault_password: &Option<String>) -> Result<(String, bool), String> {
+    let contents = read_local_file(path)?;
+    if ! is_vault_encrypted(&contents) {
+        return Ok((contents, false));
+    }
+    if vault_password.is_none() {
+        return Err(format!("{} is vault encrypted, use --vault-password-file, --ask-vault-password or $JET_VAULT_PASSWORD", path.display()));
+    }
+    return Ok((vault_decrypt(&contents, vault_password.as_ref().unwrap(), path)?, true));
+}
+
+fn get_cipher(password: &String, salt: &[u8]) -> Aes256Gcm {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, KEY_ROUNDS, &mut key);
+    return Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path() -> &'static Path {
+        return Path::new("secrets.yml");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let plaintext = String::from("db_password: hunter22\nport: 5432\n");
+        let password = String::from("correct horse");
+        let encrypted = vault_encrypt(&plaintext, &password).unwrap();
+        assert!(is_vault_encrypted(&encrypted));
+        assert!(encrypted.lines().next().unwrap().eq(VAULT_HEADER));
+        assert!(encrypted.lines().skip(1).all(|x| x.len() <= LINE_WIDTH));
+        assert!(! encrypted.contains("hunter22"));
+        assert_eq!(vault_decrypt(&encrypted, &password, path()).unwrap(), plaintext);
+
+        // a fresh salt and nonce make each encryption different
+        let again = vault_encrypt(&plaintext, &password).unwrap();
+        assert!(! again.eq(&encrypted));
+        assert_eq!(vault_decrypt(&again, &password, path()).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn test_wrong_password() {
+        let encrypted = vault_encrypt(&String::from("a: b\n"), &String::from("right")).unwrap();
+        let err = vault_decrypt(&encrypted, &String::from("wrong"), path()).unwrap_err();
+        assert!(err.contains("is the vault password correct"), "{}", err);
+    }
+
+    #[test]
+    fn test_truncated_or_corrupt_payload() {
+        let password = String::from("pw");
+        let encrypted = vault_encrypt(&String::from("a: b\n"), &password).unwrap();
+
+        // shorter than a salt and a nonce
+        let short = format!("{}\n{}\n", VAULT_HEADER, STANDARD.encode([0u8; SALT_SIZE]));
+        assert!(vault_decrypt(&short, &password, path()).unwrap_err().contains("is not a valid vault file"));
+
+        // not base64
+        let garbage = format!("{}\n!!!! not base64 !!!!\n", VAULT_HEADER);
+        assert!(vault_decrypt(&garbage, &password, path()).unwrap_err().contains("is not a valid vault file"));
+
+        // ciphertext cut short fails authentication
+        let mut payload = STANDARD.decode(encrypted.lines().skip(1).collect::<String>()).unwrap();
+        payload.truncate(payload.len() - 4);
+        let truncated = format!("{}\n{}\n", VAULT_HEADER, STANDARD.encode(&payload));
+        assert!(vault_decrypt(&truncated, &password, path()).unwrap_err().contains("unable to decrypt"));
+
+        // a flipped bit fails authentication
+        let mut payload = STANDARD.decode(encrypted.lines().skip(1).collect::<String>()).unwrap();
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+        let corrupt = format!("{}\n{}\n", VAULT_HEADER, STANDARD.encode(&payload));
+        assert!(vault_decrypt(&corrupt, &password, path()).unwrap_err().contains("unable to decrypt"));
+    }
+
+    #[test]
+    fn test_bad_header() {
+        let password = String::from("pw");
+        let encrypted = vault_encrypt(&String::from("a: b\n"), &password).unwrap();
+        let body : String = encrypted.lines().skip(1).map(|x| format!("{}\n", x)).collect();
+
+        let plain = String::from("a: b\n");
+        assert!(! is_vault_encrypted(&plain));
+        assert!(vault_decrypt(&plain, &password, path()).unwrap_err().contains("is not vault encrypted"));
+
+        let other_version = format!("$JET_VAULT;2;AES256-GCM\n{}", body);
+        assert!(is_vault_encrypted(&other_version));
+        assert!(vault_decrypt(&other_version, &password, path()).unwrap_err().contains("unsupported vault format"));
+
+        let other_cipher = format!("$JET_VAULT;1;AES128-CBC\n{}", body);
+        assert!(vault_decrypt(&other_cipher, &password, path()).unwrap_err().contains("unsupported vault format"));
+
+        let missing_header = body.clone();
+        assert!(vault_decrypt(&missing_header, &password, path()).is_err());
+    }
+}
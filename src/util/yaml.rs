@@ -26,6 +26,16 @@ const YAML_ERROR_WIDTH:usize = 180; // things will wrap in terminal anyway
 // ==============================================================================================================
 
 pub fn show_yaml_error_in_context(yaml_error: &serde_yaml::Error, path: &Path) {
+    let contents = match yaml_error.location() {
+        Some(_) => read_to_string(path).unwrap(),
+        None => String::new()
+    };
+    show_yaml_error_in_contents(yaml_error, path, &contents);
+}
+
+// same as above, for YAML that did not come straight from the file, such as a decrypted vault
+
+pub fn show_yaml_error_in_contents(yaml_error: &serde_yaml::Error, path: &Path, contents: &String) {
 
     println!("");
 
@@ -51,7 +61,7 @@ pub fn show_yaml_error_in_context(yaml_error: &serde_yaml::Error, path: &Path) {
     let error_line = location.line();
     let error_column = location.column();
 
-    let lines: Vec<String> = read_to_string(path).unwrap().lines().map(String::from).collect();
+    let lines: Vec<String> = contents.lines().map(String::from).collect();
     let line_count = lines.len();
 
     banner(&format!("Error reading YAML file: {}, {}", path.display(), yaml_error_str).to_string());
@@ -191,3 +191,53 @@ r#"---
     assert_eq!(output.status.success(), true);
     Ok(())
 }
+
+#[test]
+fn test_block_failure_any_errors_fatal() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    // A host failing inside a block that ends the play must still abort it when any_errors_fatal is set
+    let playbookcontent =
+    r#"---
+- name: block failure
+  groups:
+    - all
+  any_errors_fatal: true
+
+  tasks:
+
+  - !block
+    tasks:
+      - !shell
+        cmd: "false"
+    always:
+      - !echo
+        msg: cleaning up
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+
+    let report = temp_absolute_path(&tempfolder, "report.xml");
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    cmd.arg("local")
+        .arg("-p")
+        .arg(format!("{}/playbooks/play.yml", tempfolder.path().display()))
+        .arg("--report")
+        .arg(&report);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    println!("{}", stdout);
+
+    assert!(stdout.contains("cleaning up"));
+    assert!(stdout.contains("play aborted: any_errors_fatal is set"));
+    assert!(!output.status.success());
+
+    // The aborted play still gets its recap and report
+    assert!(stdout.contains("Failures have occured"));
+    let contents = std::fs::read_to_string(&report)?;
+    assert!(contents.contains("<failure"));
+    Ok(())
+}
@@ -0,0 +1,686 @@
+use assert_cmd::cargo::CommandCargoExt;
+use std::process::Command;
+use assert_fs::TempDir;
+use testinglib::*;
+
+// These tests cover playbook traversal features. They run in local mode, or in simulate mode
+// against a made up inventory, so no containers are needed.
+
+// Runs the playbook created in the temporary folder in local mode, returning stdout and whether it succeeded
+fn run_local(tempfolder: &TempDir, extra_args: &[&str]) -> (String, bool) {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("local")
+        .arg("-p")
+        .arg(format!("{}/playbooks/play.yml", tempfolder.path().display()))
+        .args(extra_args);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+
+    println!("{}", stdout);
+
+    (stdout, output.status.success())
+}
+
+// Runs the playbook created in the temporary folder against its inventory without connecting to anything,
+// every command pretends to succeed
+fn run_simulated(tempfolder: &TempDir, extra_args: &[&str]) -> (String, bool) {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("__simulate")
+        .arg("-p")
+        .arg(format!("{}/playbooks/play.yml", tempfolder.path().display()))
+        .arg("-i")
+        .arg(format!("{}/inventory", tempfolder.path().display()))
+        .args(extra_args);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+
+    println!("{}", stdout);
+
+    (stdout, output.status.success())
+}
+
+// Three web hosts, web2 has a variable the playbooks use to fail it
+fn create_web_inventory(tempfolder: &TempDir) {
+    create_file(tempfolder, "inventory/groups/web", "hosts:\n  - web1\n  - web2\n  - web3\n");
+    create_file(tempfolder, "inventory/host_vars/web2", "broken: true\n");
+}
+
+#[test]
+fn test_report_written_after_failed_run() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    // The only host fails on the first task, which ends the run early
+    let playbookcontent =
+    r#"---
+- name: failing play
+  groups:
+    - all
+  tasks:
+    - !shell
+      name: broken
+      cmd: "false"
+    - !echo
+      msg: never reached
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+
+    let report = temp_absolute_path(&tempfolder, "report.json");
+    let (stdout, success) = run_local(&tempfolder, &["--report", &report]);
+
+    assert!(!success);
+    assert!(stdout.contains("no hosts remaining"));
+    assert!(!stdout.contains("never reached"));
+
+    let contents = std::fs::read_to_string(&report)?;
+    assert!(contents.contains("\"broken\""));
+    assert!(contents.contains("\"failed\": true"));
+    Ok(())
+}
+
+#[test]
+fn test_linear_and_free_strategies_agree() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+    create_web_inventory(&tempfolder);
+
+    // The same play is run with both strategies, each host must end up with the same task results
+    let playbookcontent =
+    r#"---
+- name: strategies
+  groups:
+    - web
+  strategy: STRATEGY
+  tasks:
+    - !shell
+      name: first
+      cmd: "echo one"
+    - !fail
+      name: breaks web2
+      beforetask:
+        checkcondition: broken
+    - !block
+      name: wrapped
+      tasks:
+        - !shell
+          name: inside
+          cmd: "echo two"
+    - !echo
+      name: last
+      msg: done
+"#;
+
+    let mut results : Vec<serde_json::Value> = Vec::new();
+    for strategy in ["linear", "free"] {
+        create_playbook(&tempfolder, &playbookcontent.replace("STRATEGY", strategy));
+        let report = temp_absolute_path(&tempfolder, &format!("{}.json", strategy));
+        let (_, success) = run_simulated(&tempfolder, &["--report", &report]);
+        assert!(!success);
+
+        let mut doc : serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&report)?)?;
+        for (_, host) in doc["hosts"].as_object_mut().unwrap().iter_mut() {
+            for task in host["tasks"].as_array_mut().unwrap().iter_mut() {
+                task.as_object_mut().unwrap().remove("duration");
+            }
+        }
+        results.push(doc);
+    }
+
+    assert_eq!(results[0]["hosts"], results[1]["hosts"]);
+    assert_eq!(results[0]["summary"], results[1]["summary"]);
+    assert_eq!(results[0]["hosts"]["web2"]["failed"], serde_json::json!(true));
+    assert_eq!(results[0]["hosts"]["web1"]["tasks"].as_array().unwrap().len(), 4);
+    assert_eq!(results[0]["hosts"]["web2"]["tasks"].as_array().unwrap().len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_free_strategy_rejects_nested_flush_handlers() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+    create_web_inventory(&tempfolder);
+
+    // Hosts only meet up between top level tasks, so flush_handlers in a block cannot work
+    let playbookcontent =
+    r#"---
+- name: nested flush
+  groups:
+    - web
+  strategy: free
+  tasks:
+    - !block
+      tasks:
+        - !echo
+          msg: never reached
+        - !flush_handlers
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+    let (stdout, success) = run_simulated(&tempfolder, &[]);
+
+    assert!(!success);
+    assert!(stdout.contains("flush_handlers cannot be used inside a block with the free strategy"));
+    assert!(!stdout.contains("never reached"));
+
+    // The same goes for included files, which fail each host that includes them
+    let playbookcontent =
+    r#"---
+- name: included flush
+  groups:
+    - web
+  strategy: free
+  tasks:
+    - !include
+      file: flush.yml
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+    create_file(&tempfolder, "playbooks/flush.yml", "- !flush_handlers\n");
+    let (stdout, success) = run_simulated(&tempfolder, &[]);
+
+    assert!(!success);
+    assert!(stdout.contains("flush_handlers cannot be used in an included file with the free strategy"));
+    Ok(())
+}
+
+// Returns where each of the messages first appears in the output, failing the test if one is missing
+fn positions(stdout: &str, messages: &[&str]) -> Vec<usize> {
+    messages.iter().map(|x| stdout.find(x).unwrap_or_else(|| panic!("missing from output: {}", x))).collect()
+}
+
+#[test]
+fn test_block_rescue_and_always_after_failure() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    // The failure skips the rest of the block, the rescue clears it and the play carries on
+    let playbookcontent =
+    r#"---
+- name: rescue
+  groups:
+    - all
+  tasks:
+    - !block
+      tasks:
+        - !echo
+          msg: step one
+        - !shell
+          cmd: "false"
+        - !echo
+          msg: never reached
+      rescue:
+        - !echo
+          msg: rescuing
+      always:
+        - !echo
+          msg: always ran
+    - !echo
+      msg: after the block
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+    let (stdout, success) = run_local(&tempfolder, &[]);
+
+    let order = positions(&stdout, &["step one", "rescuing", "always ran", "after the block"]);
+    assert!(order.windows(2).all(|x| x[0] < x[1]));
+    assert!(!stdout.contains("never reached"));
+    assert!(success);
+    Ok(())
+}
+
+#[test]
+fn test_block_rescue_failing_with_failed_when() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    // A rescue task that fails through failed_when leaves the host failed, the always tasks still run
+    let playbookcontent =
+    r#"---
+- name: rescue fails
+  groups:
+    - all
+  tasks:
+    - !block
+      tasks:
+        - !shell
+          cmd: "false"
+      rescue:
+        - !shell
+          cmd: "echo rescue output"
+          failed_when: (eq rc 0)
+        - !echo
+          msg: rest of the rescue
+      always:
+        - !echo
+          msg: always ran
+    - !echo
+      msg: after the block
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+    let (stdout, success) = run_local(&tempfolder, &[]);
+
+    assert!(stdout.contains("always ran"));
+    assert!(!stdout.contains("rest of the rescue"));
+    assert!(!stdout.contains("after the block"));
+    assert!(!success);
+    Ok(())
+}
+
+#[test]
+fn test_start_at_task_inside_block() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    // Blocks before the start task are passed over, the one holding it is entered
+    let playbookcontent =
+    r#"---
+- name: start at task
+  groups:
+    - all
+  tasks:
+    - !block
+      name: first block
+      tasks:
+        - !echo
+          msg: skipped task
+      always:
+        - !echo
+          msg: skipped always
+    - !block
+      name: second block
+      tasks:
+        - !echo
+          msg: also skipped
+        - !echo
+          name: start here
+          msg: started
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+    let (stdout, success) = run_local(&tempfolder, &["--start-at-task", "start here"]);
+
+    assert!(stdout.contains("started"));
+    assert!(!stdout.contains("skipped task"));
+    assert!(!stdout.contains("skipped always"));
+    assert!(!stdout.contains("also skipped"));
+    assert!(!stdout.contains("first block"));
+    assert!(success);
+    Ok(())
+}
+
+#[test]
+fn test_nested_includes() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    // Include paths are relative to the playbook, also from inside an included file
+    let playbookcontent =
+    r#"---
+- name: includes
+  groups:
+    - all
+  tasks:
+    - !include
+      file: tasks/outer.yml
+    - !echo
+      msg: back in the playbook
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+    create_file(&tempfolder, "playbooks/tasks/outer.yml", "- !echo\n  msg: in outer\n- !include\n  file: tasks/inner.yml\n");
+    create_file(&tempfolder, "playbooks/tasks/inner.yml", "- !echo\n  msg: in inner\n");
+
+    for strategy in ["linear", "free"] {
+        create_playbook(&tempfolder, &playbookcontent.replace("    - all\n", &format!("    - all\n  strategy: {}\n", strategy)));
+        let (stdout, success) = run_local(&tempfolder, &[]);
+
+        let order = positions(&stdout, &["in outer", "in inner", "back in the playbook"]);
+        assert!(order.windows(2).all(|x| x[0] < x[1]));
+        assert!(success);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_include_loop() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    // A file including itself fails the host once the nesting limit is reached
+    let playbookcontent =
+    r#"---
+- name: include loop
+  groups:
+    - all
+  strategy: STRATEGY
+  tasks:
+    - !include
+      file: tasks/again.yml
+"#;
+
+    create_file(&tempfolder, "playbooks/tasks/again.yml", "- !include\n  file: tasks/again.yml\n");
+
+    for strategy in ["linear", "free"] {
+        create_playbook(&tempfolder, &playbookcontent.replace("STRATEGY", strategy));
+        let (stdout, success) = run_local(&tempfolder, &[]);
+
+        assert!(stdout.contains("tasks/again.yml: nested too deeply"));
+        assert!(!success);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_import_playbook_loop() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    // Playbooks importing each other are caught before the loop runs a second time
+    let playbookcontent =
+    r#"---
+- name: first
+  groups:
+    - all
+  tasks:
+    - !echo
+      msg: in the first playbook
+- import_playbook: other.yml
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+    create_file(&tempfolder, "playbooks/other.yml", "- import_playbook: play.yml\n");
+    let (stdout, success) = run_local(&tempfolder, &[]);
+
+    assert!(stdout.contains("playbook import loop detected"));
+    assert_eq!(stdout.matches("in the first playbook").count(), 1);
+    assert!(!success);
+    Ok(())
+}
+
+#[test]
+fn test_handler_chaining() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    // The handlers are listed backwards so each one in the chain needs another pass, the handler
+    // without a subscribe runs once at the end of the play however many passes there are
+    let playbookcontent =
+    r#"---
+- name: chaining
+  groups:
+    - all
+  tasks:
+    - !shell
+      cmd: "true"
+      aftertask:
+        notify: a
+  handlers:
+    - !echo
+      msg: handler c ran
+      beforetask:
+        subscribe: c
+    - !shell
+      name: handler b
+      cmd: "true"
+      beforetask:
+        subscribe: b
+      aftertask:
+        notify: c
+    - !shell
+      name: handler a
+      cmd: "true"
+      beforetask:
+        subscribe: a
+      aftertask:
+        notify: b
+    - !echo
+      msg: never notified
+      beforetask:
+        subscribe: d
+    - !echo
+      msg: unsubscribed handler ran
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+    let (stdout, success) = run_local(&tempfolder, &[]);
+
+    assert!(success);
+    // a, b and c each ran in a pass of their own, every handler is visited once per pass
+    assert_eq!(stdout.matches("handler c ran").count(), 1);
+    assert_eq!(stdout.matches("begin handler: handler a").count(), 3);
+    assert_eq!(stdout.matches("unsubscribed handler ran").count(), 1);
+    assert!(!stdout.contains("never notified"));
+    Ok(())
+}
+
+#[test]
+fn test_handler_pass_limit() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    // Two handlers notifying each other would otherwise run forever
+    let playbookcontent =
+    r#"---
+- name: ping pong
+  groups:
+    - all
+  tasks:
+    - !shell
+      cmd: "true"
+      aftertask:
+        notify: ping
+  handlers:
+    - !shell
+      cmd: "true"
+      beforetask:
+        subscribe: ping
+      aftertask:
+        notify: pong
+    - !shell
+      cmd: "true"
+      beforetask:
+        subscribe: pong
+      aftertask:
+        notify: ping
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+    let (stdout, success) = run_local(&tempfolder, &[]);
+
+    assert!(!success);
+    assert!(stdout.contains("handlers were still being notified after 32 passes"));
+    Ok(())
+}
+
+#[test]
+fn test_retry_file() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+    create_web_inventory(&tempfolder);
+
+    let playbookcontent =
+    r#"---
+- name: retry
+  groups:
+    - web
+  tasks:
+    - !fail
+      name: breaks web2
+      beforetask:
+        checkcondition: broken
+    - !echo
+      msg: finished
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+    let retry = temp_absolute_path(&tempfolder, "playbooks/play.retry");
+
+    // The failed host is written out with the task it failed in
+    let (_, success) = run_simulated(&tempfolder, &[]);
+    assert!(!success);
+    let contents = std::fs::read_to_string(&retry)?;
+    assert!(contents.starts_with("# failed hosts"));
+    assert!(contents.contains("\nweb2 # breaks web2\n"));
+    assert!(!contents.contains("web1"));
+
+    // Retrying just that host once it is fixed removes the file
+    create_file(&tempfolder, "inventory/host_vars/web2", "broken: false\n");
+    let (stdout, success) = run_simulated(&tempfolder, &["--limit-hosts", &format!("@{}", retry)]);
+    assert!(success);
+    assert!(stdout.contains("web2 =>"));
+    assert!(!stdout.contains("web1 =>"));
+    assert!(!std::path::Path::new(&retry).exists());
+    Ok(())
+}
+
+// Writes a role whose tasks echo a message, with the dependencies given as yaml
+fn create_echo_role(tempfolder: &TempDir, name: &str, dependencies: &str, msg: &str) {
+    create_file(tempfolder, &format!("roles/{}/role.yml", name),
+        &format!("name: {}\ndefaults:\n  flavor: plain\ndependencies: {}\ntasks:\n  - main.yml\n", name, dependencies));
+    create_file(tempfolder, &format!("roles/{}/tasks/main.yml", name), &format!("- !echo\n  msg: \"{}\"\n", msg));
+}
+
+#[test]
+fn test_role_dependencies_deduplicated() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    // Both roles depend on common, web with other vars. Dependencies already running with the
+    // same vars are skipped, roles listed in the play run every time they are listed
+    create_echo_role(&tempfolder, "common", "[]", "common ran with {{ flavor }}");
+    create_echo_role(&tempfolder, "app", "[ { role: common } ]", "app ran");
+    create_echo_role(&tempfolder, "web", "[ { role: common, vars: { flavor: spicy } } ]", "web ran");
+
+    let playbookcontent =
+    r#"---
+- name: dependencies
+  groups:
+    - all
+  roles:
+    - { role: app }
+    - { role: web }
+    - { role: app }
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+    let roles = temp_absolute_path(&tempfolder, "roles");
+    let (stdout, success) = run_local(&tempfolder, &["--roles", &roles]);
+
+    assert!(success);
+    assert_eq!(stdout.matches("common ran with plain").count(), 1);
+    assert_eq!(stdout.matches("common ran with spicy").count(), 1);
+    assert_eq!(stdout.matches("app ran").count(), 2);
+    assert_eq!(stdout.matches("web ran").count(), 1);
+    let order = positions(&stdout, &["common ran with plain", "app ran", "common ran with spicy", "web ran"]);
+    assert!(order.windows(2).all(|x| x[0] < x[1]));
+    Ok(())
+}
+
+#[test]
+fn test_role_dependency_loop() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    create_echo_role(&tempfolder, "a", "[ { role: b } ]", "a ran");
+    create_echo_role(&tempfolder, "b", "[ { role: c } ]", "b ran");
+    create_echo_role(&tempfolder, "c", "[ { role: a } ]", "c ran");
+
+    let playbookcontent =
+    r#"---
+- name: dependency loop
+  groups:
+    - all
+  roles:
+    - { role: a }
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+    let roles = temp_absolute_path(&tempfolder, "roles");
+    let (stdout, success) = run_local(&tempfolder, &["--roles", &roles]);
+
+    assert!(!success);
+    assert!(stdout.contains("role dependency loop detected: a -> b -> c -> a"));
+    assert!(!stdout.contains(" ran"));
+    Ok(())
+}
+
+#[test]
+fn test_templated_role_arguments() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    create_file(&tempfolder, "roles/svc/role.yml",
+        "name: svc\ntasks:\n  - main.yml\nargument_spec:\n  - name: port\n    type: int\n    required: true\n");
+    create_file(&tempfolder, "roles/svc/tasks/main.yml", "- !echo\n  msg: svc ran\n");
+
+    // The port is checked once templated, whatever the variable it comes from was written as
+    let playbookcontent =
+    r#"---
+- name: templated arguments
+  groups:
+    - all
+  vars:
+    base: BASE
+  roles:
+    - { role: svc, vars: { port: "{{ base }}" } }
+"#;
+
+    let roles = temp_absolute_path(&tempfolder, "roles");
+    for (base, valid) in [("8080", true), ("'8080'", true), ("http", false)] {
+        create_playbook(&tempfolder, &playbookcontent.replace("BASE", base));
+        let (stdout, success) = run_local(&tempfolder, &["--roles", &roles]);
+        assert_eq!(success, valid, "{}", base);
+        assert_eq!(stdout.contains("svc ran"), valid, "{}", base);
+        assert_eq!(stdout.contains("port must be of type int, got: \"http\""), !valid, "{}", base);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_handler_result_kept_across_passes() -> Result<(), Box<dyn std::error::Error>> {
+    // Creating a temporary folder to work in
+    let tempfolder = TempDir::new()?;
+
+    // The first handler saves its result, the passes after it skip it as it is not notified again,
+    // which must not replace what it saved
+    let playbookcontent =
+    r#"---
+- name: saved handler results
+  groups:
+    - all
+  tasks:
+    - !shell
+      cmd: "true"
+      aftertask:
+        notify: first
+  handlers:
+    - !shell
+      cmd: "true"
+      beforetask:
+        subscribe: first
+      aftertask:
+        notify: second
+        save: first_result
+    - !shell
+      cmd: "true"
+      beforetask:
+        subscribe: second
+      aftertask:
+        notify: third
+    - !echo
+      msg: "first handler was {{ first_result.status }}"
+      beforetask:
+        subscribe: third
+"#;
+
+    create_playbook(&tempfolder, playbookcontent);
+    let (stdout, success) = run_local(&tempfolder, &[]);
+
+    assert!(success);
+    assert!(stdout.contains("first handler was executed"));
+    Ok(())
+}
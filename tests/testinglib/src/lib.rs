@@ -90,4 +90,11 @@ pub struct ContainerSpec {
     pub container_id: String,
     pub container_ip: String,
     pub container_pubkey: String
-}
\ No newline at end of file
+}
+// This function writes a file at a path relative to the temporary folder, creating its directories
+pub fn create_file(tempfolder: &TempDir, relative_path: &str, content: &str) {
+    let path = std::path::PathBuf::from(temp_absolute_path(tempfolder, relative_path));
+    let _ = std::fs::create_dir_all(path.parent().unwrap());
+    let mut tempfile = File::create(path).unwrap();
+    let _ = tempfile.write_all(content.as_bytes());
+}